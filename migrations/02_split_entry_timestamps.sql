@@ -0,0 +1,15 @@
+-- occurred_at holds the client-controlled time of the event, recorded_at the time it was logged
+ALTER TABLE journal_entry
+    RENAME COLUMN created_at TO occurred_at;
+
+ALTER TABLE journal_entry
+    ADD COLUMN recorded_at timestamptz;
+
+UPDATE journal_entry
+SET recorded_at = occurred_at;
+
+ALTER TABLE journal_entry
+    ALTER COLUMN recorded_at SET NOT NULL,
+    ALTER COLUMN recorded_at SET DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS idx_journal_entry_user_occurred_at on journal_entry (user_id, occurred_at);
@@ -0,0 +1,7 @@
+-- Entries may span over time, running entries (timers) don't have the end set yet
+ALTER TABLE journal_entry
+    ADD COLUMN ended_at      timestamptz,
+    ADD COLUMN running       boolean NOT NULL DEFAULT false,
+    ADD COLUMN duration_secs bigint GENERATED ALWAYS AS (extract(epoch FROM ended_at - occurred_at)::bigint) STORED,
+    ADD CONSTRAINT journal_entry_ended_after_start CHECK (ended_at > occurred_at),
+    ADD CONSTRAINT journal_entry_running_not_ended CHECK (NOT (running AND ended_at IS NOT NULL));
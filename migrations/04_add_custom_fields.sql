@@ -0,0 +1,6 @@
+-- Event types declare a schema of custom fields, entries store the values validated against it
+ALTER TABLE event_type
+    ADD COLUMN fields jsonb NOT NULL DEFAULT '[]'::jsonb;
+
+ALTER TABLE journal_entry
+    ADD COLUMN field_values jsonb NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS user_settings
+(
+    user_id  uuid PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    timezone text NOT NULL DEFAULT 'UTC'
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS event_type_goal
+(
+    event_type_id uuid PRIMARY KEY REFERENCES event_type (id) ON DELETE CASCADE,
+    period        text    NOT NULL CHECK (period IN ('day', 'week', 'month')),
+    target        integer NOT NULL CHECK (target > 0)
+);
@@ -0,0 +1,12 @@
+ALTER TABLE user_settings
+    ADD COLUMN IF NOT EXISTS search_language regconfig NOT NULL DEFAULT 'simple';
+
+-- Language is stored per entry, since generated columns cannot refer to the user's settings.
+ALTER TABLE journal_entry
+    ADD COLUMN IF NOT EXISTS search_language regconfig NOT NULL DEFAULT 'simple';
+
+ALTER TABLE journal_entry
+    ADD COLUMN IF NOT EXISTS description_tsv tsvector
+        GENERATED ALWAYS AS (to_tsvector(search_language, coalesce(description, ''))) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_journal_entry_description_tsv ON journal_entry USING gin (description_tsv);
@@ -0,0 +1,6 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_journal_entry_description_trgm
+    ON journal_entry USING gin (description gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS idx_event_type_name_trgm ON event_type USING gin (name gin_trgm_ops);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS saved_search
+(
+    id      uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id uuid  NOT NULL REFERENCES users (id),
+    name    text  NOT NULL,
+    filter  jsonb NOT NULL,
+    UNIQUE (user_id, name)
+);
@@ -0,0 +1,5 @@
+-- Keyset pagination of the entries is ordered by (occurred_at, id), like the sorted listing.
+-- The new index also replaces the one on (user_id, occurred_at).
+CREATE INDEX IF NOT EXISTS idx_journal_entry_user_occurred_at_id
+    ON journal_entry (user_id, occurred_at, id);
+DROP INDEX IF EXISTS idx_journal_entry_user_occurred_at;
@@ -0,0 +1,14 @@
+-- Responses of the requests made with an Idempotency-Key header, replayed for their retries.
+-- Status is NULL while the request is being processed.
+CREATE TABLE IF NOT EXISTS idempotency_key
+(
+    user_id      uuid        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    key          text        NOT NULL,
+    request_hash bytea       NOT NULL,
+    status       smallint,
+    content_type text,
+    body         bytea,
+    created_at   timestamptz NOT NULL DEFAULT now(),
+    expires_at   timestamptz NOT NULL,
+    PRIMARY KEY (user_id, key)
+);
@@ -0,0 +1,7 @@
+-- Secret tokens of the users' calendar feeds. Only the SHA-256 hash of the token is stored.
+CREATE TABLE IF NOT EXISTS calendar_feed
+(
+    user_id    uuid PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    token_hash bytea UNIQUE NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
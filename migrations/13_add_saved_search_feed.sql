@@ -0,0 +1,4 @@
+-- Secret token of the capability URL of the saved search's Atom feed. Only the SHA-256 hash of
+-- the token is stored.
+ALTER TABLE saved_search
+    ADD COLUMN feed_token_hash bytea UNIQUE;
@@ -0,0 +1,82 @@
+-- Row versions and deletion tombstones for the delta sync. Versions are drawn from one sequence
+-- by triggers on every write. Writes of a user are serialized by an advisory lock taken before
+-- the version is drawn, so the user's versions become visible in increasing order and a change
+-- cursor never skips a change committed after it.
+CREATE SEQUENCE IF NOT EXISTS change_version_seq;
+
+-- The default only versions the existing rows, new versions are set by the triggers.
+ALTER TABLE event_type
+    ADD COLUMN version bigint NOT NULL DEFAULT nextval('change_version_seq');
+ALTER TABLE event_type
+    ALTER COLUMN version DROP DEFAULT;
+
+ALTER TABLE journal_entry
+    ADD COLUMN version bigint NOT NULL DEFAULT nextval('change_version_seq');
+ALTER TABLE journal_entry
+    ALTER COLUMN version DROP DEFAULT;
+
+CREATE TABLE IF NOT EXISTS sync_tombstone
+(
+    user_id    uuid        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind       text        NOT NULL CHECK (kind IN ('event_type', 'entry')),
+    id         uuid        NOT NULL,
+    version    bigint      NOT NULL,
+    deleted_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (kind, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_type_user_version on event_type (user_id, version);
+CREATE INDEX IF NOT EXISTS idx_journal_entry_user_version on journal_entry (user_id, version);
+CREATE INDEX IF NOT EXISTS idx_sync_tombstone_user_version on sync_tombstone (user_id, version);
+
+CREATE OR REPLACE FUNCTION next_change_version(owner uuid) RETURNS bigint AS
+$$
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtextextended(owner::text, 0));
+    RETURN nextval('change_version_seq');
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION set_change_version() RETURNS trigger AS
+$$
+BEGIN
+    NEW.version := next_change_version(NEW.user_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- The kind of the deleted row is passed as the trigger argument.
+CREATE OR REPLACE FUNCTION record_sync_tombstone() RETURNS trigger AS
+$$
+BEGIN
+    INSERT INTO sync_tombstone (user_id, kind, id, version)
+    VALUES (OLD.user_id, TG_ARGV[0], OLD.id, next_change_version(OLD.user_id))
+    ON CONFLICT (kind, id) DO UPDATE SET version = EXCLUDED.version, deleted_at = now();
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER event_type_version
+    BEFORE INSERT OR UPDATE
+    ON event_type
+    FOR EACH ROW
+EXECUTE FUNCTION set_change_version();
+
+CREATE TRIGGER journal_entry_version
+    BEFORE INSERT OR UPDATE
+    ON journal_entry
+    FOR EACH ROW
+EXECUTE FUNCTION set_change_version();
+
+CREATE TRIGGER event_type_tombstone
+    AFTER DELETE
+    ON event_type
+    FOR EACH ROW
+EXECUTE FUNCTION record_sync_tombstone('event_type');
+
+-- Also records the entries deleted along with their event type.
+CREATE TRIGGER journal_entry_tombstone
+    AFTER DELETE
+    ON journal_entry
+    FOR EACH ROW
+EXECUTE FUNCTION record_sync_tombstone('entry');
@@ -0,0 +1,52 @@
+-- Notifies the backends of the changes of the event types and entries, which are streamed to the
+-- users' live clients. Notifications are delivered on commit, the payload is kept small since it
+-- is limited to 8000 bytes.
+CREATE OR REPLACE FUNCTION notify_journal_change(kind text, action text, changed jsonb, version bigint)
+    RETURNS void AS
+$$
+BEGIN
+    PERFORM pg_notify('journal_change', jsonb_strip_nulls(jsonb_build_object(
+            'user_id', changed -> 'user_id',
+            'kind', kind,
+            'action', action,
+            'id', changed -> 'id',
+            'event_type_id', changed -> 'event_type_id',
+            'version', version))::text);
+END;
+$$ LANGUAGE plpgsql;
+
+-- The kind of the changed row is passed as the trigger argument.
+CREATE OR REPLACE FUNCTION notify_row_change() RETURNS trigger AS
+$$
+BEGIN
+    PERFORM notify_journal_change(TG_ARGV[0], CASE TG_OP WHEN 'INSERT' THEN 'created' ELSE 'updated' END,
+                                  to_jsonb(NEW), NEW.version);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Deletions are notified along with their tombstones, to share the version.
+CREATE OR REPLACE FUNCTION record_sync_tombstone() RETURNS trigger AS
+$$
+DECLARE
+    deleted_version bigint := next_change_version(OLD.user_id);
+BEGIN
+    INSERT INTO sync_tombstone (user_id, kind, id, version)
+    VALUES (OLD.user_id, TG_ARGV[0], OLD.id, deleted_version)
+    ON CONFLICT (kind, id) DO UPDATE SET version = EXCLUDED.version, deleted_at = now();
+    PERFORM notify_journal_change(TG_ARGV[0], 'deleted', to_jsonb(OLD), deleted_version);
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER event_type_notify
+    AFTER INSERT OR UPDATE
+    ON event_type
+    FOR EACH ROW
+EXECUTE FUNCTION notify_row_change('event_type');
+
+CREATE TRIGGER journal_entry_notify
+    AFTER INSERT OR UPDATE
+    ON journal_entry
+    FOR EACH ROW
+EXECUTE FUNCTION notify_row_change('entry');
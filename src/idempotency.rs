@@ -0,0 +1,4 @@
+pub mod middleware;
+pub mod model;
+pub mod repository;
+pub mod service;
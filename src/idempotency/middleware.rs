@@ -0,0 +1,110 @@
+use crate::idempotency::model::{IdempotencyState, StoredResponse};
+use crate::idempotency::service::IdempotencyService;
+use crate::model::{AppError, InvalidField};
+use crate::user::model::UserId;
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::http::{Method, StatusCode, header};
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, HttpResponse, web};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Middleware function making POST requests with an `Idempotency-Key` header safe to retry.
+/// The response of the first request is stored and replayed for the retries with the same key,
+/// which fail if the key is reused for a different request. Responses with server errors are not
+/// stored, so that the request can be retried. Expects the caller's UserId in request data.
+pub async fn idempotency<T: IdempotencyService + 'static>(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let key = match req.headers().get(IDEMPOTENCY_KEY) {
+        Some(key) if req.method() == Method::POST => key
+            .to_str()
+            .ok()
+            .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+            .map(str::to_string)
+            .ok_or_else(|| AppError::Validation(vec![InvalidField(IDEMPOTENCY_KEY.to_string())]))?,
+        _ => return next.call(req).await.map(ServiceResponse::map_into_boxed_body),
+    };
+    let Some(user_id) = req.extensions().get::<UserId>().copied() else {
+        return Err(AppError::Unauthorized.into());
+    };
+    let Some(service) = req.app_data::<web::Data<T>>().cloned() else {
+        return Err(actix_web::error::ErrorInternalServerError("Missing app data"));
+    };
+
+    let body = read_body(&mut req, service.max_body_size()).await?;
+    let request_hash = hash_request(&req, &body);
+    req.set_payload(body.into());
+
+    if let IdempotencyState::Replay(response) = service.begin(user_id, &key, &request_hash).await? {
+        return Ok(req.into_response(replay(response)));
+    }
+    let res = match next.call(req).await {
+        Ok(res) => res,
+        Err(error) => {
+            service.release(user_id, &key).await?;
+            return Err(error);
+        }
+    };
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = body::to_bytes(body).await;
+    match &body {
+        Ok(body) if !res.status().is_server_error() => {
+            let content_type = res.headers().get(header::CONTENT_TYPE);
+            let response = StoredResponse {
+                status: res.status().as_u16(),
+                content_type: content_type.and_then(|v| v.to_str().ok()).map(str::to_string),
+                body: body.to_vec(),
+            };
+            service.complete(user_id, &key, response).await?;
+        }
+        _ => service.release(user_id, &key).await?,
+    }
+    let body = body.map_err(|_| AppError::ProcessingError)?;
+    Ok(ServiceResponse::new(req, res.set_body(body).map_into_boxed_body()))
+}
+
+async fn read_body(
+    req: &mut ServiceRequest,
+    max_size: usize,
+) -> Result<web::Bytes, actix_web::Error> {
+    let mut payload = req.take_payload();
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > max_size {
+            return Err(PayloadError::Overflow.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// Identifies the request by its target and payload.
+fn hash_request(req: &ServiceRequest, body: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for part in [req.method().as_str(), req.path(), req.query_string()] {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+    hasher.update(body);
+    hasher.finalize().to_vec()
+}
+
+fn replay(response: StoredResponse) -> HttpResponse {
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK);
+    let mut builder = HttpResponse::build(status);
+    if let Some(content_type) = response.content_type {
+        builder.insert_header((header::CONTENT_TYPE, content_type));
+    }
+    builder.insert_header((IDEMPOTENT_REPLAYED, "true")).body(response.body)
+}
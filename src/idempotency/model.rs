@@ -0,0 +1,23 @@
+/// Response stored for replaying retries of a request with the same idempotency key.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// Earlier request made with an idempotency key.
+#[derive(Eq, PartialEq, Debug)]
+pub struct IdempotentRequest {
+    pub request_hash: Vec<u8>,
+    /// Missing while the request is still being processed.
+    pub response: Option<StoredResponse>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum IdempotencyState {
+    /// The key was reserved for the request, which should be processed.
+    New,
+    /// The request was already processed with the response.
+    Replay(StoredResponse),
+}
@@ -0,0 +1,132 @@
+use crate::idempotency::model::{IdempotentRequest, StoredResponse};
+use crate::user::model::UserId;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait IdempotencyRepository {
+    /// Reserves the key for a new request, unless an unexpired request with the key exists,
+    /// in which case the existing request is returned. Requests still processing after the
+    /// `processing_timeout` are assumed to have been interrupted, so their keys are reserved again.
+    async fn reserve(
+        &self,
+        user_id: UserId,
+        key: &str,
+        request_hash: &[u8],
+        ttl: Duration,
+        processing_timeout: Duration,
+    ) -> Result<Option<IdempotentRequest>, sqlx::Error>;
+
+    /// Stores the response of the request reserved with the key.
+    async fn complete(
+        &self,
+        user_id: UserId,
+        key: &str,
+        response: &StoredResponse,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Deletes the key, so that the request can be retried.
+    async fn delete(&self, user_id: UserId, key: &str) -> Result<(), sqlx::Error>;
+}
+
+pub struct PgIdempotencyRepository {
+    pool: PgPool,
+}
+
+impl PgIdempotencyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for PgIdempotencyRepository {
+    async fn reserve(
+        &self,
+        user_id: UserId,
+        key: &str,
+        request_hash: &[u8],
+        ttl: Duration,
+        processing_timeout: Duration,
+    ) -> Result<Option<IdempotentRequest>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"DELETE FROM idempotency_key WHERE user_id = $1
+                AND (expires_at <= now()
+                    OR (status IS NULL AND created_at <= now() - make_interval(secs => $2)))"#,
+            user_id as UserId,
+            processing_timeout.as_secs_f64()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let reserved = sqlx::query!(
+            r#"INSERT INTO idempotency_key (user_id, key, request_hash, expires_at)
+                VALUES ($1, $2, $3, now() + make_interval(secs => $4))
+                ON CONFLICT (user_id, key) DO NOTHING"#,
+            user_id as UserId,
+            key,
+            request_hash,
+            ttl.as_secs_f64()
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        let result = if reserved {
+            None
+        } else {
+            let record = sqlx::query!(
+                r#"SELECT request_hash, status, content_type, body FROM idempotency_key
+                    WHERE user_id = $1 AND key = $2"#,
+                user_id as UserId,
+                key
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            let response = record.status.map(|status| StoredResponse {
+                status: status as u16,
+                content_type: record.content_type,
+                body: record.body.unwrap_or_default(),
+            });
+            Some(IdempotentRequest { request_hash: record.request_hash, response })
+        };
+
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn complete(
+        &self,
+        user_id: UserId,
+        key: &str,
+        response: &StoredResponse,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE idempotency_key SET status = $3, content_type = $4, body = $5
+                WHERE user_id = $1 AND key = $2"#,
+            user_id as UserId,
+            key,
+            response.status as i16,
+            response.content_type,
+            response.body
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: UserId, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM idempotency_key WHERE user_id = $1 AND key = $2"#,
+            user_id as UserId,
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
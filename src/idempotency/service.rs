@@ -0,0 +1,150 @@
+use crate::idempotency::model::{IdempotencyState, IdempotentRequest, StoredResponse};
+use crate::idempotency::repository::IdempotencyRepository;
+use crate::model::AppError;
+use crate::user::model::UserId;
+use async_trait::async_trait;
+use std::time::Duration;
+
+#[async_trait]
+pub trait IdempotencyService {
+    /// Reserves the key for the request identified by its hash, or returns the response of the
+    /// earlier request made with the key.
+    async fn begin(
+        &self,
+        user_id: UserId,
+        key: &str,
+        request_hash: &[u8],
+    ) -> Result<IdempotencyState, AppError>;
+
+    async fn complete(
+        &self,
+        user_id: UserId,
+        key: &str,
+        response: StoredResponse,
+    ) -> Result<(), AppError>;
+
+    /// Releases the key of a request which failed and can be retried.
+    async fn release(&self, user_id: UserId, key: &str) -> Result<(), AppError>;
+
+    /// Largest body of the requests with a key, which must not be below the payload limit of
+    /// any route, since the body is read before the route's extractors.
+    fn max_body_size(&self) -> usize;
+}
+
+pub struct IdempotencyServiceImpl<T: IdempotencyRepository> {
+    idempotency_repository: T,
+    ttl: Duration,
+    processing_timeout: Duration,
+    max_body_size: usize,
+}
+
+impl<T: IdempotencyRepository> IdempotencyServiceImpl<T> {
+    /// Responses are replayed for retries made within the `ttl` from the original request.
+    /// Retries made while the original request is processing fail until the
+    /// `processing_timeout`, which must exceed the duration of the slowest request.
+    pub fn new(
+        idempotency_repository: T,
+        ttl: Duration,
+        processing_timeout: Duration,
+        max_body_size: usize,
+    ) -> Self {
+        Self { idempotency_repository, ttl, processing_timeout, max_body_size }
+    }
+}
+
+#[async_trait]
+impl<T: IdempotencyRepository + Send + Sync> IdempotencyService for IdempotencyServiceImpl<T> {
+    async fn begin(
+        &self,
+        user_id: UserId,
+        key: &str,
+        request_hash: &[u8],
+    ) -> Result<IdempotencyState, AppError> {
+        let existing = self
+            .idempotency_repository
+            .reserve(user_id, key, request_hash, self.ttl, self.processing_timeout)
+            .await?;
+        match existing {
+            None => Ok(IdempotencyState::New),
+            Some(request) if request.request_hash != request_hash => {
+                Err(AppError::IdempotencyKeyReused)
+            }
+            Some(IdempotentRequest { response: Some(response), .. }) => {
+                Ok(IdempotencyState::Replay(response))
+            }
+            Some(_) => Err(AppError::IdempotencyKeyInProgress),
+        }
+    }
+
+    async fn complete(
+        &self,
+        user_id: UserId,
+        key: &str,
+        response: StoredResponse,
+    ) -> Result<(), AppError> {
+        Ok(self.idempotency_repository.complete(user_id, key, &response).await?)
+    }
+
+    async fn release(&self, user_id: UserId, key: &str) -> Result<(), AppError> {
+        Ok(self.idempotency_repository.delete(user_id, key).await?)
+    }
+
+    fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idempotency::repository::MockIdempotencyRepository;
+    use mockall::predicate::*;
+    use uuid::Uuid;
+
+    const TTL: Duration = Duration::from_secs(60);
+    const PROCESSING_TIMEOUT: Duration = Duration::from_secs(600);
+
+    fn service_returning(existing: Option<IdempotentRequest>) -> impl IdempotencyService {
+        let mut repo = MockIdempotencyRepository::new();
+        repo.expect_reserve()
+            .with(always(), eq("key"), always(), eq(TTL), eq(PROCESSING_TIMEOUT))
+            .return_once(move |_, _, _, _, _| Ok(existing));
+        IdempotencyServiceImpl::new(repo, TTL, PROCESSING_TIMEOUT, 1024)
+    }
+
+    #[tokio::test]
+    async fn test_begin_new_request() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let result = service_returning(None).begin(user_id, "key", b"hash").await;
+        assert_eq!(IdempotencyState::New, result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_begin_replays_stored_response() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let response = StoredResponse { status: 200, content_type: None, body: b"{}".to_vec() };
+        let existing =
+            IdempotentRequest { request_hash: b"hash".to_vec(), response: Some(response.clone()) };
+
+        let result = service_returning(Some(existing)).begin(user_id, "key", b"hash").await;
+        assert_eq!(IdempotencyState::Replay(response), result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_begin_with_different_request_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let existing = IdempotentRequest { request_hash: b"other".to_vec(), response: None };
+
+        let result = service_returning(Some(existing)).begin(user_id, "key", b"hash").await;
+        assert!(matches!(result, Err(AppError::IdempotencyKeyReused)));
+    }
+
+    #[tokio::test]
+    async fn test_begin_while_in_progress_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let existing = IdempotentRequest { request_hash: b"hash".to_vec(), response: None };
+
+        let result = service_returning(Some(existing)).begin(user_id, "key", b"hash").await;
+        assert!(matches!(result, Err(AppError::IdempotencyKeyInProgress)));
+    }
+}
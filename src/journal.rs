@@ -1,4 +1,8 @@
+pub mod export;
+pub mod feed;
 pub mod handler;
+pub mod import;
 pub mod model;
+pub mod query;
 pub mod repository;
 pub mod service;
@@ -0,0 +1,281 @@
+//! Encoding of the exported journal entries in one of the [`ExportFormat`]s. Entries are encoded
+//! one by one as they are read from the database, so the export is never held in memory.
+//!
+//! The CSV export uses the column names of the import defaults, so it can be imported back.
+//! The iCalendar export has a `VEVENT` per entry, with the event type as the summary and the
+//! tags as the categories.
+
+use crate::journal::model::{ExportEntry, ExportFormat};
+use crate::journal::repository::ExportEntryStream;
+use crate::model::AppError;
+use actix_web::web::Bytes;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
+
+const CSV_HEADER: [&str; 10] = [
+    "id",
+    "event_type",
+    "occurred_at",
+    "ended_at",
+    "duration_secs",
+    "running",
+    "description",
+    "tags",
+    "values",
+    "recorded_at",
+];
+
+const ICS_HEADER: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//journal-backend//Journal//EN\r\n\
+    CALSCALE:GREGORIAN\r\nX-WR-CALNAME:Journal\r\n";
+const ICS_FOOTER: &str = "END:VCALENDAR\r\n";
+/// Maximum length of an iCalendar content line in octets, longer lines are folded.
+const ICS_LINE_LENGTH: usize = 75;
+
+pub type ExportStream = BoxStream<'static, Result<Bytes, AppError>>;
+
+pub fn encode(format: ExportFormat, entries: ExportEntryStream) -> ExportStream {
+    let mut encoder = Encoder { format, day: None };
+    let header = encoder.header().map(Ok);
+    let footer = encoder.footer().map(Ok);
+    stream::iter(header)
+        .chain(entries.map(move |entry| encoder.entry(&entry?)))
+        .chain(stream::iter(footer))
+        .boxed()
+}
+
+struct Encoder {
+    format: ExportFormat,
+    /// Local day of the last Markdown heading.
+    day: Option<NaiveDate>,
+}
+
+impl Encoder {
+    fn header(&self) -> Option<Bytes> {
+        match self.format {
+            ExportFormat::Csv => csv_record(CSV_HEADER).ok(),
+            ExportFormat::Ics => Some(Bytes::from_static(ICS_HEADER.as_bytes())),
+            ExportFormat::Jsonl | ExportFormat::Markdown => None,
+        }
+    }
+
+    fn footer(&self) -> Option<Bytes> {
+        match self.format {
+            ExportFormat::Ics => Some(Bytes::from_static(ICS_FOOTER.as_bytes())),
+            ExportFormat::Csv | ExportFormat::Jsonl | ExportFormat::Markdown => None,
+        }
+    }
+
+    fn entry(&mut self, entry: &ExportEntry) -> Result<Bytes, AppError> {
+        match self.format {
+            ExportFormat::Csv => csv_entry(entry),
+            ExportFormat::Jsonl => {
+                let mut line = serde_json::to_vec(entry).map_err(|_| AppError::ProcessingError)?;
+                line.push(b'\n');
+                Ok(line.into())
+            }
+            ExportFormat::Markdown => Ok(self.markdown_entry(entry).into()),
+            ExportFormat::Ics => Ok(ics_event(entry).into()),
+        }
+    }
+
+    /// Formats the entry as a list item, preceded by the heading of its day if it is the first
+    /// entry of the day.
+    fn markdown_entry(&mut self, entry: &ExportEntry) -> String {
+        let mut text = String::new();
+        let day = entry.local_occurred_at.date();
+        if self.day != Some(day) {
+            if self.day.is_some() {
+                text.push('\n');
+            }
+            text.push_str(&format!("## {day}\n\n"));
+            self.day = Some(day);
+        }
+
+        let time = entry.local_occurred_at.format("%H:%M");
+        text.push_str(&format!("- {time} **{}**", entry.event_type));
+        if let Some(description) = &entry.entry.description {
+            // Following lines are indented to stay within the list item.
+            text.push(' ');
+            text.push_str(&description.trim().replace('\n', "\n  "));
+        }
+        for tag in &entry.entry.tags {
+            text.push_str(&format!(" #{tag}"));
+        }
+        text.push('\n');
+        text
+    }
+}
+
+fn csv_entry(entry: &ExportEntry) -> Result<Bytes, AppError> {
+    let ExportEntry { entry, event_type, .. } = entry;
+    let timestamp = |t: &DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    let values = serde_json::to_string(&entry.values).map_err(|_| AppError::ProcessingError)?;
+    csv_record([
+        entry.id.to_string(),
+        event_type.clone(),
+        timestamp(&entry.occurred_at),
+        entry.ended_at.as_ref().map(timestamp).unwrap_or_default(),
+        entry.duration_secs.map(|d| d.to_string()).unwrap_or_default(),
+        entry.running.to_string(),
+        entry.description.clone().unwrap_or_default(),
+        entry.tags.join(","),
+        values,
+        timestamp(&entry.recorded_at),
+    ])
+}
+
+fn ics_event(entry: &ExportEntry) -> String {
+    let ExportEntry { entry, event_type, .. } = entry;
+    let timestamp = |t: &DateTime<Utc>| t.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut event = String::from("BEGIN:VEVENT\r\n");
+    event.push_str(&ics_line("UID", &entry.id.to_string()));
+    event.push_str(&ics_line("DTSTAMP", &timestamp(&entry.recorded_at)));
+    event.push_str(&ics_line("DTSTART", &timestamp(&entry.occurred_at)));
+    if let Some(ended_at) = &entry.ended_at {
+        event.push_str(&ics_line("DTEND", &timestamp(ended_at)));
+    }
+    event.push_str(&ics_line("SUMMARY", &escape_ics_text(event_type)));
+    if let Some(description) = &entry.description {
+        event.push_str(&ics_line("DESCRIPTION", &escape_ics_text(description)));
+    }
+    if !entry.tags.is_empty() {
+        let tags: Vec<String> = entry.tags.iter().map(|tag| escape_ics_text(tag)).collect();
+        event.push_str(&ics_line("CATEGORIES", &tags.join(",")));
+    }
+    event.push_str("END:VEVENT\r\n");
+    event
+}
+
+/// Formats the content line, folding it into lines of at most 75 octets.
+fn ics_line(name: &str, value: &str) -> String {
+    let mut line = String::with_capacity(name.len() + value.len() + 3);
+    let mut length = 0;
+    for c in name.chars().chain([':']).chain(value.chars()) {
+        if length + c.len_utf8() > ICS_LINE_LENGTH {
+            line.push_str("\r\n ");
+            length = 1;
+        }
+        line.push(c);
+        length += c.len_utf8();
+    }
+    line.push_str("\r\n");
+    line
+}
+
+fn escape_ics_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn csv_record<I: IntoIterator<Item = T>, T: AsRef<[u8]>>(record: I) -> Result<Bytes, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record).map_err(|_| AppError::ProcessingError)?;
+    writer.into_inner().map(Bytes::from).map_err(|_| AppError::ProcessingError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::model::{EventTypeId, JournalEntry, JournalEntryId};
+    use crate::user::model::UserId;
+    use futures_util::TryStreamExt;
+    use serde_json::json;
+    use sqlx::types::Json;
+    use uuid::Uuid;
+
+    fn export_entry(occurred_at: &str, local: &str, description: Option<&str>) -> ExportEntry {
+        let occurred_at: DateTime<Utc> = occurred_at.parse().unwrap();
+        ExportEntry {
+            entry: JournalEntry {
+                id: JournalEntryId::new(Uuid::nil()),
+                user_id: UserId::new(Uuid::nil()),
+                event_type_id: EventTypeId::new(Uuid::nil()),
+                description: description.map(str::to_string),
+                tags: vec!["a".to_string(), "b c".to_string()],
+                occurred_at,
+                ended_at: None,
+                duration_secs: None,
+                running: false,
+                recorded_at: occurred_at,
+                values: Json(json!({"km": 5}).as_object().unwrap().clone()),
+                snippet: None,
+            },
+            event_type: "Run".to_string(),
+            local_occurred_at: local.parse().unwrap(),
+        }
+    }
+
+    async fn encode_all(format: ExportFormat, entries: Vec<ExportEntry>) -> String {
+        let entries = stream::iter(entries.into_iter().map(Ok)).boxed();
+        let chunks: Vec<Bytes> = encode(format, entries).try_collect().await.unwrap();
+        String::from_utf8(chunks.concat()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_encode_markdown_grouped_by_local_day() {
+        let entries = vec![
+            export_entry("2026-01-01T22:30:00Z", "2026-01-01T23:30:00", Some("Late\nrun")),
+            export_entry("2026-01-01T23:30:00Z", "2026-01-02T00:30:00", None),
+            export_entry("2026-01-02T06:00:00Z", "2026-01-02T07:00:00", Some("Morning")),
+        ];
+
+        let markdown = encode_all(ExportFormat::Markdown, entries).await;
+
+        let expected = "## 2026-01-01\n\n\
+            - 23:30 **Run** Late\n  run #a #b c\n\
+            \n## 2026-01-02\n\n\
+            - 00:30 **Run** #a #b c\n\
+            - 07:00 **Run** Morning #a #b c\n";
+        assert_eq!(expected, markdown);
+    }
+
+    #[tokio::test]
+    async fn test_encode_csv_and_jsonl() {
+        let entries = || vec![export_entry("2026-01-02T06:00:00Z", "2026-01-02T07:00:00", None)];
+
+        let csv = encode_all(ExportFormat::Csv, entries()).await;
+        let expected = format!(
+            "{}\n{},Run,2026-01-02T06:00:00Z,,,false,,\"a,b c\",\"{{\"\"km\"\":5}}\",\
+                2026-01-02T06:00:00Z\n",
+            CSV_HEADER.join(","),
+            Uuid::nil()
+        );
+        assert_eq!(expected, csv);
+
+        let jsonl = encode_all(ExportFormat::Jsonl, entries()).await;
+        let lines: Vec<serde_json::Value> =
+            jsonl.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(1, lines.len());
+        assert_eq!("Run", lines[0]["event_type"]);
+        assert_eq!(json!({"km": 5}), lines[0]["values"]);
+    }
+
+    #[tokio::test]
+    async fn test_encode_ics() {
+        let mut entry = export_entry("2026-01-02T06:00:00Z", "2026-01-02T07:00:00", None);
+        entry.entry.ended_at = Some("2026-01-02T07:30:00Z".parse().unwrap());
+        entry.entry.description = Some(format!("Easy; felt good,\n{}", "long ".repeat(15)));
+
+        let ics = encode_all(ExportFormat::Ics, vec![entry]).await;
+
+        let expected = format!(
+            "{ICS_HEADER}BEGIN:VEVENT\r\nUID:{}\r\nDTSTAMP:20260102T060000Z\r\n\
+                DTSTART:20260102T060000Z\r\nDTEND:20260102T073000Z\r\nSUMMARY:Run\r\n\
+                DESCRIPTION:Easy\\; felt good\\,\\nlong long long long long long long long lon\r\n \
+                 g long long long long long long \r\nCATEGORIES:a,b c\r\nEND:VEVENT\r\n{ICS_FOOTER}",
+            Uuid::nil()
+        );
+        assert_eq!(expected, ics);
+    }
+}
@@ -0,0 +1,122 @@
+//! Atom feed of the most recent entries of a saved search, which can be followed from a feed
+//! reader through its capability URL.
+//!
+//! Each entry is titled by its event type and lists its tags both as Atom categories and in the
+//! content, since many readers don't show the categories.
+
+use crate::journal::model::{FeedEntry, SearchFeed};
+use atom_syndication::{Category, Content, Entry, Feed, Generator, Link, Person, Text};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+pub const CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+pub fn atom(feed: &SearchFeed, self_url: &str) -> String {
+    let SearchFeed { search, entries } = feed;
+    // The entries are the most recent ones, so the first one occurred last.
+    let updated = entries.first().map_or(DateTime::UNIX_EPOCH, |e| e.entry.occurred_at);
+    let feed = Feed {
+        title: Text::plain(&search.name),
+        id: format!("urn:uuid:{}", search.id),
+        updated: updated.fixed_offset(),
+        authors: vec![Person { name: "Journal".to_string(), ..Default::default() }],
+        generator: Some(Generator { value: "journal-backend".to_string(), ..Default::default() }),
+        links: vec![Link {
+            href: self_url.to_string(),
+            rel: "self".to_string(),
+            mime_type: Some("application/atom+xml".to_string()),
+            ..Default::default()
+        }],
+        entries: entries.iter().map(atom_entry).collect(),
+        ..Default::default()
+    };
+    feed.to_string()
+}
+
+fn atom_entry(feed_entry: &FeedEntry) -> Entry {
+    let FeedEntry { entry, event_type } = feed_entry;
+    let timestamp = |t: &DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut content = format!("{event_type} at {}", timestamp(&entry.occurred_at));
+    if let Some(ended_at) = &entry.ended_at {
+        content.push_str(&format!(" until {}", timestamp(ended_at)));
+    }
+    if let Some(description) = &entry.description {
+        content.push_str(&format!("\n\n{description}"));
+    }
+    if !entry.tags.is_empty() {
+        let tags: Vec<String> = entry.tags.iter().map(|tag| format!("#{tag}")).collect();
+        content.push_str(&format!("\n\n{}", tags.join(" ")));
+    }
+
+    Entry {
+        title: Text::plain(event_type),
+        id: format!("urn:uuid:{}", entry.id),
+        updated: entry.occurred_at.fixed_offset(),
+        categories: entry
+            .tags
+            .iter()
+            .map(|tag| Category { term: tag.clone(), ..Default::default() })
+            .collect(),
+        content: Some(Content {
+            value: Some(content),
+            content_type: Some("text".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::model::{
+        EventTypeId, JournalEntry, JournalEntryId, SavedSearch, SavedSearchId, SearchFilter,
+    };
+    use crate::user::model::UserId;
+    use sqlx::types::Json;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_atom_feed() {
+        let occurred_at = "2026-01-02T07:00:00Z".parse().unwrap();
+        let entry = JournalEntry {
+            id: JournalEntryId::new(Uuid::nil()),
+            user_id: UserId::new(Uuid::nil()),
+            event_type_id: EventTypeId::new(Uuid::nil()),
+            description: Some("Knee <3 & ok".to_string()),
+            tags: vec!["outdoor".to_string(), "easy".to_string()],
+            occurred_at,
+            ended_at: Some("2026-01-02T07:45:00Z".parse().unwrap()),
+            duration_secs: Some(2700),
+            running: false,
+            recorded_at: "2026-01-02T08:00:00Z".parse().unwrap(),
+            values: Json(Default::default()),
+            snippet: None,
+        };
+        let feed = SearchFeed {
+            search: SavedSearch {
+                id: SavedSearchId::new(Uuid::nil()),
+                user_id: UserId::new(Uuid::nil()),
+                name: "Team runs".to_string(),
+                filter: Json(SearchFilter::default()),
+            },
+            entries: vec![FeedEntry { entry, event_type: "Run".to_string() }],
+        };
+
+        let xml = atom(&feed, "http://localhost/journal/feeds/token.atom");
+
+        let parsed: Feed = xml.parse().unwrap();
+        assert_eq!("Team runs", parsed.title.value);
+        assert_eq!(occurred_at, parsed.updated);
+        assert_eq!("http://localhost/journal/feeds/token.atom", parsed.links[0].href);
+        let entry = &parsed.entries[0];
+        assert_eq!("Run", entry.title.value);
+        assert_eq!(occurred_at, entry.updated);
+        assert_eq!(
+            vec!["outdoor", "easy"],
+            entry.categories.iter().map(|c| &c.term).collect::<Vec<_>>()
+        );
+        let expected_content = "Run at 2026-01-02T07:00:00Z until 2026-01-02T07:45:00Z\n\n\
+            Knee <3 & ok\n\n#outdoor #easy";
+        assert_eq!(Some(expected_content), entry.content.as_ref().and_then(|c| c.value.as_deref()));
+    }
+}
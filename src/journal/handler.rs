@@ -1,10 +1,15 @@
+use crate::journal::feed;
 use crate::journal::model::{
-    EventTypeData, EventTypeId, JournalEntryId, JournalEntryUpdate, NewJournalEntry, SearchFilter,
+    CalendarQuery, CountsQuery, Cursor, EntryBatch, EventTypeData, EventTypeId, EventTypePatch,
+    ExportFormat, ExportQuery, FuzzySearchQuery, Goal, ImportOptions, JournalEntryId,
+    JournalEntryPatch, JournalEntryUpdate, NewJournalEntry, NewTimer, PageQuery, SavedSearchData,
+    SavedSearchId, SearchFilter, StatsQuery, SyncPush, SyncQuery,
 };
 use crate::journal::service::JournalService;
 use crate::model::{AppError, IdResponse};
 use crate::user::model::UserId;
-use actix_web::{HttpResponse, web};
+use actix_web::http::header;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, web};
 use validator::Validate;
 
 pub async fn find_event_type<T: JournalService>(
@@ -52,6 +57,22 @@ pub async fn update_event_type<T: JournalService>(
         .map(|_| HttpResponse::Ok().finish())
 }
 
+/// Applies a JSON Merge Patch, sent as `application/merge-patch+json` or `application/json`
+/// (the JSON extractor accepts any `+json` media type).
+pub async fn patch_event_type<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<EventTypeId>,
+    patch: web::Json<EventTypePatch>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let patch = patch.into_inner();
+    patch.validate().map_err(AppError::from)?;
+    service
+        .patch_event_type(user_id.into_inner(), id.into_inner(), patch)
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
 pub async fn delete_event_type<T: JournalService>(
     user_id: web::ReqData<UserId>,
     id: web::Path<EventTypeId>,
@@ -63,6 +84,53 @@ pub async fn delete_event_type<T: JournalService>(
         .map(|_| HttpResponse::Ok().finish())
 }
 
+pub async fn find_goal<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<EventTypeId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .find_goal(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|goal| HttpResponse::Ok().json(goal))
+}
+
+pub async fn set_goal<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<EventTypeId>,
+    goal: web::Json<Goal>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let goal = goal.into_inner();
+    goal.validate().map_err(AppError::from)?;
+    service
+        .set_goal(user_id.into_inner(), id.into_inner(), goal)
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+pub async fn delete_goal<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<EventTypeId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .delete_goal(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+pub async fn find_goal_progress<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<EventTypeId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .find_goal_progress(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|progress| HttpResponse::Ok().json(progress))
+}
+
 pub async fn find_journal_entry<T: JournalService>(
     user_id: web::ReqData<UserId>,
     id: web::Path<JournalEntryId>,
@@ -75,16 +143,180 @@ pub async fn find_journal_entry<T: JournalService>(
 }
 
 pub async fn find_journal_entries<T: JournalService>(
+    req: HttpRequest,
+    user_id: web::ReqData<UserId>,
+    filter: web::Query<SearchFilter>,
+    page: web::Query<PageQuery>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let filter = filter.into_inner();
+    filter.validate().map_err(AppError::from)?;
+    let page = page.into_inner();
+    let Some(cursor) = page.cursor else {
+        if page.envelope || accepts_envelope(&req) {
+            return service
+                .find_journal_entries_page_with_total(user_id.into_inner(), filter)
+                .await
+                .map(|page| HttpResponse::Ok().json(page));
+        }
+        return service
+            .find_journal_entries(user_id.into_inner(), filter)
+            .await
+            .map(|et| HttpResponse::Ok().json(et));
+    };
+
+    let page = service.find_journal_entries_page(user_id.into_inner(), filter, cursor).await?;
+    let mut response = HttpResponse::Ok();
+    if let Some(next_cursor) = page.next_cursor {
+        response.insert_header((header::LINK, next_page_link(&req, next_cursor)?));
+    }
+    Ok(response.json(page))
+}
+
+/// Checks whether the client accepts JSON with the `envelope` profile.
+fn accepts_envelope(req: &HttpRequest) -> bool {
+    req.get_header::<header::Accept>().is_some_and(|accept| {
+        accept.iter().any(|item| {
+            item.item.subtype() == mime::JSON
+                && item.item.get_param("profile").is_some_and(|profile| profile == "envelope")
+        })
+    })
+}
+
+/// Link to the same listing with the cursor replaced by the next one.
+fn next_page_link(req: &HttpRequest, next_cursor: Cursor) -> Result<String, AppError> {
+    let mut params: Vec<(String, String)> =
+        serde_urlencoded::from_str(req.query_string()).map_err(|_| AppError::ProcessingError)?;
+    params.retain(|(name, _)| name != "cursor");
+    params.push(("cursor".to_string(), next_cursor.to_string()));
+    let query = serde_urlencoded::to_string(params).map_err(|_| AppError::ProcessingError)?;
+    Ok(format!("<{}?{}>; rel=\"next\"", req.path(), query))
+}
+
+pub async fn export_journal_entries<T: JournalService>(
     user_id: web::ReqData<UserId>,
     filter: web::Query<SearchFilter>,
+    export: web::Query<ExportQuery>,
     service: web::Data<T>,
 ) -> Result<HttpResponse, AppError> {
     let filter = filter.into_inner();
     filter.validate().map_err(AppError::from)?;
+    let format = export.into_inner().format;
+    let entries = service.export_journal_entries(user_id.into_inner(), filter, format).await?;
+    let file_name = format!("journal.{}", format.extension());
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(header::ContentDisposition::attachment(file_name))
+        .streaming(entries))
+}
+
+pub async fn create_calendar_token<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
     service
-        .find_journal_entries(user_id.into_inner(), filter)
+        .create_calendar_token(user_id.into_inner())
         .await
-        .map(|et| HttpResponse::Ok().json(et))
+        .map(|token| HttpResponse::Ok().json(token))
+}
+
+pub async fn delete_calendar_token<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service.delete_calendar_token(user_id.into_inner()).await.map(|_| HttpResponse::Ok().finish())
+}
+
+/// Calendar feed authenticated by the secret token in the path instead of the bearer token, as
+/// calendar apps can't send one.
+pub async fn find_calendar_feed<T: JournalService>(
+    token: web::Path<String>,
+    query: web::Query<CalendarQuery>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    query.validate().map_err(AppError::from)?;
+    let events = service.export_calendar(&token, query).await?;
+    Ok(HttpResponse::Ok().content_type(ExportFormat::Ics.content_type()).streaming(events))
+}
+
+pub async fn create_saved_search_feed_token<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<SavedSearchId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .create_saved_search_feed_token(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|token| HttpResponse::Ok().json(token))
+}
+
+pub async fn delete_saved_search_feed_token<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<SavedSearchId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .delete_saved_search_feed_token(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+/// Atom feed of a saved search, authenticated by the secret token in the path.
+pub async fn find_saved_search_feed<T: JournalService>(
+    req: HttpRequest,
+    token: web::Path<String>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let search_feed = service.find_saved_search_feed(&token).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(feed::CONTENT_TYPE)
+        .body(feed::atom(&search_feed, req.full_url().as_str())))
+}
+
+pub async fn find_entry_stats<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    filter: web::Query<SearchFilter>,
+    stats: web::Query<StatsQuery>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let filter = filter.into_inner();
+    filter.validate().map_err(AppError::from)?;
+    let stats = stats.into_inner();
+    stats.validate().map_err(AppError::from)?;
+    service
+        .find_entry_stats(user_id.into_inner(), filter, stats)
+        .await
+        .map(|stats| HttpResponse::Ok().json(stats))
+}
+
+pub async fn count_journal_entries<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    filter: web::Query<SearchFilter>,
+    counts: web::Query<CountsQuery>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let filter = filter.into_inner();
+    filter.validate().map_err(AppError::from)?;
+    let counts = counts.into_inner();
+    counts.validate().map_err(AppError::from)?;
+    service
+        .count_journal_entries(user_id.into_inner(), filter, counts)
+        .await
+        .map(|counts| HttpResponse::Ok().json(counts))
+}
+
+pub async fn search<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    query: web::Query<FuzzySearchQuery>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    query.validate().map_err(AppError::from)?;
+    service
+        .search(user_id.into_inner(), query)
+        .await
+        .map(|results| HttpResponse::Ok().json(results))
 }
 
 pub async fn insert_journal_entry<T: JournalService>(
@@ -114,6 +346,21 @@ pub async fn update_journal_entry<T: JournalService>(
         .map(|_| HttpResponse::Ok().finish())
 }
 
+/// Applies a JSON Merge Patch, see [`patch_event_type`].
+pub async fn patch_journal_entry<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<JournalEntryId>,
+    patch: web::Json<JournalEntryPatch>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let patch = patch.into_inner();
+    patch.validate().map_err(AppError::from)?;
+    service
+        .patch_journal_entry(user_id.into_inner(), id.into_inner(), patch)
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
 pub async fn delete_journal_entry<T: JournalService>(
     user_id: web::ReqData<UserId>,
     id: web::Path<JournalEntryId>,
@@ -124,3 +371,151 @@ pub async fn delete_journal_entry<T: JournalService>(
         .await
         .map(|_| HttpResponse::Ok().finish())
 }
+
+pub async fn execute_entry_batch<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    batch: web::Json<EntryBatch>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let batch = batch.into_inner();
+    batch.validate().map_err(AppError::from)?;
+    service
+        .execute_entry_batch(user_id.into_inner(), batch)
+        .await
+        .map(|result| HttpResponse::Ok().json(result))
+}
+
+pub async fn find_changes<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    query: web::Query<SyncQuery>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    query.validate().map_err(AppError::from)?;
+    service
+        .find_changes(user_id.into_inner(), query)
+        .await
+        .map(|changes| HttpResponse::Ok().json(changes))
+}
+
+pub async fn push_changes<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    push: web::Json<SyncPush>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let push = push.into_inner();
+    push.validate().map_err(AppError::from)?;
+    service
+        .push_changes(user_id.into_inner(), push)
+        .await
+        .map(|result| HttpResponse::Ok().json(result))
+}
+
+pub async fn import_entries<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    options: web::Query<ImportOptions>,
+    data: web::Bytes,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let options = options.into_inner();
+    options.validate().map_err(AppError::from)?;
+    service
+        .import_entries(user_id.into_inner(), &data, options)
+        .await
+        .map(|report| HttpResponse::Ok().json(report))
+}
+
+pub async fn start_timer<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    timer: web::Json<NewTimer>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let timer = timer.into_inner();
+    timer.validate().map_err(AppError::from)?;
+    service
+        .start_timer(user_id.into_inner(), timer)
+        .await
+        .map(|id| HttpResponse::Ok().json(IdResponse { id }))
+}
+
+pub async fn stop_timer<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<JournalEntryId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .stop_timer(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+pub async fn find_saved_searches<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .find_saved_searches(user_id.into_inner())
+        .await
+        .map(|searches| HttpResponse::Ok().json(searches))
+}
+
+pub async fn find_saved_search<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<SavedSearchId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .find_saved_search(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|search| HttpResponse::Ok().json(search))
+}
+
+pub async fn insert_saved_search<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    search: web::Json<SavedSearchData>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let search = search.into_inner();
+    search.validate().map_err(AppError::from)?;
+    service
+        .insert_saved_search(user_id.into_inner(), search)
+        .await
+        .map(|id| HttpResponse::Ok().json(IdResponse { id }))
+}
+
+pub async fn update_saved_search<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<SavedSearchId>,
+    search: web::Json<SavedSearchData>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let search = search.into_inner();
+    search.validate().map_err(AppError::from)?;
+    service
+        .update_saved_search(user_id.into_inner(), id.into_inner(), search)
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+pub async fn delete_saved_search<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<SavedSearchId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .delete_saved_search(user_id.into_inner(), id.into_inner())
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+pub async fn execute_saved_search<T: JournalService>(
+    user_id: web::ReqData<UserId>,
+    id: web::Path<SavedSearchId>,
+    overrides: web::Query<SearchFilter>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .execute_saved_search(user_id.into_inner(), id.into_inner(), overrides.into_inner())
+        .await
+        .map(|entries| HttpResponse::Ok().json(entries))
+}
@@ -0,0 +1,451 @@
+//! Parsing of the journal history imported from CSV with one entry per row, e.g.
+//!
+//! ```csv
+//! event_type,occurred_at,description,tags
+//! Run,2026-01-02T07:00:00Z,Morning run,"outdoor,easy"
+//! ```
+//!
+//! Columns are mapped by [`ImportOptions`]. Rows which can't be parsed are reported as
+//! [`ImportError`]s instead of failing the whole import.
+//!
+//! The history can also be imported from iCalendar, where each `VEVENT` is an entry with the
+//! summary as the event type and the categories as the tags. Its end is taken from `DTEND` or
+//! `DURATION`. Time zones referenced by `TZID` have to be IANA time zone names.
+
+use crate::journal::model::{ImportEntry, ImportError, ImportOptions};
+use crate::model::{AppError, InvalidField};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use csv::{ReaderBuilder, StringRecord};
+use ical::IcalParser;
+use ical::property::Property;
+
+const ISO_LOCAL_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+pub fn parse_csv(
+    data: &[u8],
+    options: &ImportOptions,
+) -> Result<(Vec<ImportEntry>, Vec<ImportError>), AppError> {
+    let mut reader = ReaderBuilder::new().delimiter(options.delimiter as u8).from_reader(data);
+    let header = reader.headers().map_err(|_| invalid_field("csv"))?;
+    let columns = Columns::new(header, options)?;
+    let timezone = timezone(options)?;
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let result = match record {
+            Ok(record) => columns
+                .entry(&record, options, timezone)
+                .map_err(|message| ImportError { line: line(record.position()), message }),
+            Err(error) => {
+                Err(ImportError { line: line(error.position()), message: error.to_string() })
+            }
+        };
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(error) => errors.push(error),
+        }
+    }
+    Ok((entries, errors))
+}
+
+pub fn parse_ics(
+    data: &[u8],
+    options: &ImportOptions,
+) -> Result<(Vec<ImportEntry>, Vec<ImportError>), AppError> {
+    let timezone = timezone(options)?;
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut position = 0;
+    for calendar in IcalParser::new(data) {
+        let calendar = calendar.map_err(|_| invalid_field("ics"))?;
+        for event in calendar.events {
+            position += 1;
+            match ics_entry(position, &event.properties, timezone) {
+                Ok(entry) => entries.push(entry),
+                Err(message) => errors.push(ImportError { line: position, message }),
+            }
+        }
+    }
+    Ok((entries, errors))
+}
+
+/// Indexes of the mapped columns in the CSV header.
+struct Columns {
+    event_type: usize,
+    occurred_at: usize,
+    description: Option<usize>,
+    tags: Option<usize>,
+}
+
+impl Columns {
+    fn new(header: &StringRecord, options: &ImportOptions) -> Result<Self, AppError> {
+        let find = |name: &str| header.iter().position(|column| column.trim() == name);
+        let required = |name: &str, field: &str| find(name).ok_or_else(|| invalid_field(field));
+        // Optional columns set explicitly have to be present as well.
+        let optional = |name: &Option<String>, default: &str, field: &str| match name {
+            Some(name) => required(name, field).map(Some),
+            None => Ok(find(default)),
+        };
+
+        Ok(Self {
+            event_type: required(&options.event_type_column, "event_type_column")?,
+            occurred_at: required(&options.occurred_at_column, "occurred_at_column")?,
+            description: optional(
+                &options.description_column,
+                "description",
+                "description_column",
+            )?,
+            tags: optional(&options.tags_column, "tags", "tags_column")?,
+        })
+    }
+
+    fn entry(
+        &self,
+        record: &StringRecord,
+        options: &ImportOptions,
+        timezone: Tz,
+    ) -> Result<ImportEntry, String> {
+        let value = |index: usize| record.get(index).map(str::trim).unwrap_or_default();
+        let event_type = value(self.event_type);
+        if event_type.is_empty() {
+            return Err(format!("missing event type in column `{}`", options.event_type_column));
+        }
+        let occurred_at = value(self.occurred_at);
+        let occurred_at =
+            parse_timestamp(occurred_at, options.timestamp_format.as_deref(), timezone)
+                .ok_or_else(|| format!("invalid timestamp `{occurred_at}`"))?;
+        let description = self.description.map(value).filter(|d| !d.is_empty());
+        let tags = self.tags.map(value).unwrap_or_default();
+        let mut unique_tags: Vec<String> = Vec::new();
+        for tag in tags.split(options.tag_separator).map(str::trim).filter(|t| !t.is_empty()) {
+            if !unique_tags.iter().any(|t| t == tag) {
+                unique_tags.push(tag.to_string());
+            }
+        }
+
+        Ok(ImportEntry {
+            line: line(record.position()),
+            event_type: event_type.to_string(),
+            occurred_at,
+            ended_at: None,
+            description: description.map(str::to_string),
+            tags: unique_tags,
+        })
+    }
+}
+
+fn ics_entry(line: u64, properties: &[Property], timezone: Tz) -> Result<ImportEntry, String> {
+    let property = |name: &str| properties.iter().find(|p| p.name == name);
+    let value = |property: &Property| property.value.clone().unwrap_or_default();
+    let text = |name: &str| {
+        property(name)
+            .and_then(|p| p.value.as_deref())
+            .map(|value| unescape_text(value).trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let event_type = text("SUMMARY").ok_or("missing SUMMARY")?;
+    let start = property("DTSTART").ok_or("missing DTSTART")?;
+    let occurred_at = parse_ics_time(start, timezone)
+        .ok_or_else(|| format!("invalid DTSTART `{}`", value(start)))?;
+    let ended_at = match (property("DTEND"), property("DURATION")) {
+        (Some(end), _) => Some(
+            parse_ics_time(end, timezone)
+                .ok_or_else(|| format!("invalid DTEND `{}`", value(end)))?,
+        ),
+        (None, Some(duration)) => Some(
+            parse_duration(&value(duration))
+                .map(|duration| occurred_at + duration)
+                .ok_or_else(|| format!("invalid DURATION `{}`", value(duration)))?,
+        ),
+        (None, None) => None,
+    };
+    // Events without a duration are point-in-time entries.
+    let ended_at = ended_at.filter(|ended_at| *ended_at != occurred_at);
+    if ended_at.is_some_and(|ended_at| ended_at < occurred_at) {
+        return Err("event ends before it starts".to_string());
+    }
+    let mut tags: Vec<String> = Vec::new();
+    let categories = properties.iter().filter(|p| p.name == "CATEGORIES");
+    for tag in categories.filter_map(|p| p.value.as_deref()).flat_map(split_list) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    Ok(ImportEntry {
+        line,
+        event_type,
+        occurred_at,
+        ended_at,
+        description: text("DESCRIPTION"),
+        tags,
+    })
+}
+
+/// Parses the `DATE-TIME` or `DATE` value in UTC, in the time zone of its `TZID` parameter or in
+/// the given time zone.
+fn parse_ics_time(property: &Property, timezone: Tz) -> Option<DateTime<Utc>> {
+    let value = property.value.as_deref()?;
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok().map(|t| t.and_utc());
+    }
+    let tzid = property.params.iter().flatten().find(|(name, _)| name == "TZID");
+    let timezone = match tzid.and_then(|(_, values)| values.first()) {
+        Some(tzid) => tzid.parse().ok()?,
+        None => timezone,
+    };
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(|date| date.into()))
+        .and_then(|local| timezone.from_local_datetime(&local).earliest())
+        .map(|t| t.to_utc())
+}
+
+/// Parses the iCalendar duration, e.g. `PT1H30M` or `P1W`.
+fn parse_duration(value: &str) -> Option<TimeDelta> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut duration = TimeDelta::zero();
+    let mut number = String::new();
+    let mut time = false;
+    let mut empty = true;
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if !time && number.is_empty() => time = true,
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                empty = false;
+                duration += match (unit, time) {
+                    ('W', false) => TimeDelta::try_weeks(n)?,
+                    ('D', false) => TimeDelta::try_days(n)?,
+                    ('H', true) => TimeDelta::try_hours(n)?,
+                    ('M', true) => TimeDelta::try_minutes(n)?,
+                    ('S', true) => TimeDelta::try_seconds(n)?,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    if empty || !number.is_empty() {
+        return None;
+    }
+    Some(if negative { -duration } else { duration })
+}
+
+/// Splits the list of texts separated by unescaped commas, unescaping and trimming the texts.
+fn split_list(value: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            ',' if !escaped => {
+                texts.push(unescape_text(&value[start..i]).trim().to_string());
+                start = i + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+    texts.push(unescape_text(&value[start..]).trim().to_string());
+    texts
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(c) => text.push(c),
+                None => text.push('\\'),
+            },
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+fn timezone(options: &ImportOptions) -> Result<Tz, AppError> {
+    let timezone = options.timezone.as_deref().map_or(Ok(Tz::UTC), str::parse);
+    timezone.map_err(|_| invalid_field("timezone"))
+}
+
+/// Parses the timestamp with the format, falling back to a local date and time, or a date at
+/// midnight, in the time zone when the format has no offset.
+fn parse_timestamp(value: &str, format: Option<&str>, timezone: Tz) -> Option<DateTime<Utc>> {
+    let local = |naive: NaiveDateTime| timezone.from_local_datetime(&naive).earliest();
+    match format {
+        None => DateTime::parse_from_rfc3339(value).map(|t| t.to_utc()).ok().or_else(|| {
+            ISO_LOCAL_FORMATS
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                .or_else(|| value.parse::<NaiveDate>().ok().map(|date| date.into()))
+                .and_then(local)
+                .map(|t| t.to_utc())
+        }),
+        Some(format) => {
+            DateTime::parse_from_str(value, format).map(|t| t.to_utc()).ok().or_else(|| {
+                NaiveDateTime::parse_from_str(value, format)
+                    .ok()
+                    .or_else(|| NaiveDate::parse_from_str(value, format).ok().map(|d| d.into()))
+                    .and_then(local)
+                    .map(|t| t.to_utc())
+            })
+        }
+    }
+}
+
+fn line(position: Option<&csv::Position>) -> u64 {
+    position.map_or(0, csv::Position::line)
+}
+
+fn invalid_field(field: &str) -> AppError {
+    AppError::Validation(vec![InvalidField(field.to_string())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::web;
+
+    fn options(query: &str) -> ImportOptions {
+        web::Query::<ImportOptions>::from_query(query).unwrap().into_inner()
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_csv_with_default_columns() {
+        let data = "event_type,occurred_at,description,tags\n\
+            Run,2026-01-02T07:00:00+01:00,Morning run,\"outdoor, easy\"\n\
+            Swim,2026-01-03 18:30:00,,\n";
+
+        let (entries, errors) = parse_csv(data.as_bytes(), &options("")).unwrap();
+
+        let expected = vec![
+            ImportEntry {
+                line: 2,
+                event_type: "Run".to_string(),
+                occurred_at: utc("2026-01-02T06:00:00Z"),
+                ended_at: None,
+                description: Some("Morning run".to_string()),
+                tags: vec!["outdoor".to_string(), "easy".to_string()],
+            },
+            ImportEntry {
+                line: 3,
+                event_type: "Swim".to_string(),
+                occurred_at: utc("2026-01-03T18:30:00Z"),
+                ended_at: None,
+                description: None,
+                tags: vec![],
+            },
+        ];
+        assert_eq!(expected, entries);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_csv_with_mapping_format_and_timezone() {
+        let data = "Activity;Date;Labels\nRun;02.01.2026 07:00;a|b\nRun;2026-01-02;a\n;03.01.2026 08:00;\n";
+        let options = options(
+            "event_type_column=Activity&occurred_at_column=Date&tags_column=Labels&delimiter=%3B\
+                &tag_separator=%7C&timestamp_format=%25d.%25m.%25Y%20%25H:%25M&timezone=Europe/Prague",
+        );
+
+        let (entries, errors) = parse_csv(data.as_bytes(), &options).unwrap();
+
+        assert_eq!(1, entries.len());
+        assert_eq!(utc("2026-01-02T06:00:00Z"), entries[0].occurred_at);
+        assert_eq!(vec!["a", "b"], entries[0].tags);
+        let expected_errors = vec![
+            ImportError { line: 3, message: "invalid timestamp `2026-01-02`".to_string() },
+            ImportError { line: 4, message: "missing event type in column `Activity`".to_string() },
+        ];
+        assert_eq!(expected_errors, errors);
+    }
+
+    #[test]
+    fn test_parse_csv_with_missing_column_fails() {
+        let data = "event_type,occurred_at\nRun,2026-01-02\n";
+        assert!(parse_csv(data.as_bytes(), &options("")).is_ok());
+
+        let result = parse_csv(data.as_bytes(), &options("tags_column=tags"));
+        let Err(AppError::Validation(fields)) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert_eq!("tags_column", fields[0].0);
+        assert!(parse_csv(data.as_bytes(), &options("occurred_at_column=date")).is_err());
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let data = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+            BEGIN:VEVENT\r\nUID:1\r\nSUMMARY:Run\r\nDTSTART;TZID=Europe/Prague:20260102T070000\r\n\
+            DURATION:PT1H30M\r\nDESCRIPTION:Morning run\\, easy\\nfelt good\r\n\
+            CATEGORIES:outdoor,with\\,comma\r\nCATEGORIES:outdoor\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Holiday\r\nDTSTART;VALUE=DATE:20260103\r\n\
+            DTEND;VALUE=DATE:20260104\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Swim\r\nDTSTART:20260103T180000Z\r\n\
+            DTEND:20260103T180000Z\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nDTSTART:20260103T180000Z\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Run\r\nDTSTART:20260103T180000Z\r\nDURATION:1H\r\n\
+            END:VEVENT\r\nEND:VCALENDAR\r\n";
+
+        let (entries, errors) =
+            parse_ics(data.as_bytes(), &options("timezone=America/New_York")).unwrap();
+
+        let expected = vec![
+            ImportEntry {
+                line: 1,
+                event_type: "Run".to_string(),
+                occurred_at: utc("2026-01-02T06:00:00Z"),
+                ended_at: Some(utc("2026-01-02T07:30:00Z")),
+                description: Some("Morning run, easy\nfelt good".to_string()),
+                tags: vec!["outdoor".to_string(), "with,comma".to_string()],
+            },
+            ImportEntry {
+                line: 2,
+                event_type: "Holiday".to_string(),
+                occurred_at: utc("2026-01-03T05:00:00Z"),
+                ended_at: Some(utc("2026-01-04T05:00:00Z")),
+                description: None,
+                tags: vec![],
+            },
+            ImportEntry {
+                line: 3,
+                event_type: "Swim".to_string(),
+                occurred_at: utc("2026-01-03T18:00:00Z"),
+                ended_at: None,
+                description: None,
+                tags: vec![],
+            },
+        ];
+        assert_eq!(expected, entries);
+        let expected_errors = vec![
+            ImportError { line: 4, message: "missing SUMMARY".to_string() },
+            ImportError { line: 5, message: "invalid DURATION `1H`".to_string() },
+        ];
+        assert_eq!(expected_errors, errors);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(TimeDelta::weeks(2)), parse_duration("P2W"));
+        assert_eq!(Some(TimeDelta::seconds(93784)), parse_duration("P1DT2H3M4S"));
+        assert_eq!(Some(-TimeDelta::minutes(15)), parse_duration("-PT15M"));
+        assert_eq!(None, parse_duration("P"));
+        assert_eq!(None, parse_duration("PT1D"));
+        assert_eq!(None, parse_duration("P1"));
+    }
+}
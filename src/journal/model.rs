@@ -1,12 +1,23 @@
-use crate::model::IdType;
+use crate::journal::query;
+use crate::model::{AppError, IdType, InvalidField, validate_timezone};
 use crate::user::model::UserId;
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use chrono::prelude::*;
+use chrono::{Days, Months};
 use derive_more::Display;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
 
-#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(transparent)]
 pub struct EventTypeId(Uuid);
 
@@ -30,22 +41,93 @@ impl JournalEntryId {
 
 impl IdType for JournalEntryId {}
 
-#[derive(Eq, PartialEq, Serialize, Debug)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct SavedSearchId(Uuid);
+
+impl SavedSearchId {
+    pub fn new(uuid: Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl IdType for SavedSearchId {}
+
+#[derive(Eq, PartialEq, Serialize, Debug, sqlx::FromRow)]
 pub struct EventType {
     pub id: EventTypeId,
     pub user_id: UserId,
     pub name: String,
     pub tags: Vec<String>,
+    pub fields: Json<Vec<FieldDefinition>>,
 }
 
-#[derive(Eq, PartialEq, Serialize, Debug, sqlx::FromRow)]
+/// Definition of a typed custom field whose values can be stored in the journal entries.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct FieldDefinition {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: FieldKind,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldKind {
+    Number {
+        unit: Option<String>,
+    },
+    Integer,
+    Boolean,
+    Enum {
+        values: Vec<String>,
+    },
+    Text,
+    /// Integer rating from 1 to 5.
+    Rating,
+}
+
+impl FieldKind {
+    fn accepts(&self, value: &Value) -> bool {
+        match self {
+            FieldKind::Number { .. } => value.is_number(),
+            FieldKind::Integer => value.is_i64() || value.is_u64(),
+            FieldKind::Boolean => value.is_boolean(),
+            FieldKind::Enum { values } => {
+                value.as_str().is_some_and(|v| values.iter().any(|e| e == v))
+            }
+            FieldKind::Text => value.is_string(),
+            FieldKind::Rating => value.as_u64().is_some_and(|v| (1..=5).contains(&v)),
+        }
+    }
+}
+
+/// Values of the custom fields of a journal entry, keyed by the field name.
+pub type FieldValues = Map<String, Value>;
+
+#[derive(Clone, Eq, PartialEq, Serialize, Debug, sqlx::FromRow)]
 pub struct JournalEntry {
     pub id: JournalEntryId,
     pub user_id: UserId,
     pub event_type_id: EventTypeId,
     pub description: Option<String>,
     pub tags: Vec<String>,
-    pub created_at: DateTime<Utc>,
+    /// Time when the event happened, provided by the client.
+    pub occurred_at: DateTime<Utc>,
+    /// End of the event for entries spanning over time.
+    pub ended_at: Option<DateTime<Utc>>,
+    pub duration_secs: Option<i64>,
+    /// Whether the entry was started as a timer which hasn't been stopped yet.
+    pub running: bool,
+    /// Time when the entry was logged, set by the server.
+    pub recorded_at: DateTime<Utc>,
+    #[sqlx(rename = "field_values")]
+    pub values: Json<FieldValues>,
+    /// Fragment of the description with highlighted matches of the full-text query.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Validate)]
@@ -55,40 +137,534 @@ pub struct EventTypeData {
     #[serde(default)]
     #[validate(custom(function = "validate_tags"))]
     pub tags: Vec<String>,
+    #[serde(default)]
+    #[validate(custom(function = "validate_field_definitions"))]
+    pub fields: Vec<FieldDefinition>,
+}
+
+/// Habit goal of an event type, e.g. at least 3 entries per week.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Validate)]
+pub struct Goal {
+    pub period: GoalPeriod,
+    #[validate(range(min = 1))]
+    pub target: i32,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug, Display, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum GoalPeriod {
+    #[display("day")]
+    Day,
+    #[display("week")]
+    Week,
+    #[display("month")]
+    Month,
+}
+
+impl GoalPeriod {
+    fn next(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            GoalPeriod::Day => start.checked_add_days(Days::new(1)),
+            GoalPeriod::Week => start.checked_add_days(Days::new(7)),
+            GoalPeriod::Month => start.checked_add_months(Months::new(1)),
+        }
+    }
+}
+
+/// Number of entries of an event type per goal period. Periods are identified by their first
+/// day in the user's time zone.
+#[derive(Debug)]
+pub struct PeriodCounts {
+    pub current_period: NaiveDate,
+    pub counts: Vec<(NaiveDate, i64)>,
+}
+
+#[derive(PartialEq, Serialize, Debug)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub current_period: NaiveDate,
+    pub current_count: i64,
+    pub current_period_met: bool,
+    /// Number of consecutive periods with the goal met, up to the current period. The current
+    /// period doesn't break the streak while it is still in progress.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Share of periods with the goal met since the first logged entry. The current period is
+    /// only counted once its goal is met.
+    pub completion_rate: f64,
+}
+
+impl GoalProgress {
+    pub fn calculate(goal: Goal, counts: &PeriodCounts) -> Self {
+        let current = counts.current_period;
+        let count_in = |period: NaiveDate| {
+            counts.counts.iter().find(|(p, _)| *p == period).map_or(0, |(_, count)| *count)
+        };
+        let current_count = count_in(current);
+        let current_period_met = current_count >= i64::from(goal.target);
+
+        let (mut streak, mut longest_streak, mut met, mut total) = (0, 0, 0, 0);
+        let mut period = counts.counts.first().map_or(current, |(p, _)| *p).min(current);
+        while period < current {
+            total += 1;
+            if count_in(period) >= i64::from(goal.target) {
+                met += 1;
+                streak += 1;
+                longest_streak = longest_streak.max(streak);
+            } else {
+                streak = 0;
+            }
+            let Some(next) = goal.period.next(period) else { break };
+            period = next;
+        }
+        if current_period_met {
+            met += 1;
+            total += 1;
+            streak += 1;
+            longest_streak = longest_streak.max(streak);
+        }
+
+        GoalProgress {
+            goal,
+            current_period: current,
+            current_count,
+            current_period_met,
+            current_streak: streak,
+            longest_streak,
+            completion_rate: if total == 0 { 0.0 } else { f64::from(met) / f64::from(total) },
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Validate)]
+#[validate(schema(function = "validate_new_entry_time_range"))]
 pub struct NewJournalEntry {
     pub event_type_id: EventTypeId,
     pub description: Option<String>,
     #[serde(default)]
     #[validate(custom(function = "validate_tags"))]
     pub tags: Vec<String>,
-    pub created_at: Option<DateTime<Utc>>,
+    #[serde(alias = "created_at")]
+    pub occurred_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub values: FieldValues,
 }
 
 #[derive(Deserialize, Debug, Validate)]
+pub struct NewTimer {
+    pub event_type_id: EventTypeId,
+    pub description: Option<String>,
+    #[serde(default)]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub values: FieldValues,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+#[validate(schema(function = "validate_update_time_range"))]
 pub struct JournalEntryUpdate {
+    /// Moves the entry to another event type, the current one is kept if not provided.
+    pub event_type_id: Option<EventTypeId>,
     pub description: Option<String>,
     #[serde(default)]
     #[validate(custom(function = "validate_tags"))]
     pub tags: Vec<String>,
+    /// Corrects the time of the entry, the current one is kept if not provided.
+    #[serde(alias = "created_at")]
+    pub occurred_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub values: FieldValues,
+}
+
+/// Creates, updates and deletes journal entries in one transaction. The operations are executed
+/// in this order and each of them is reported in the [`BatchResult`].
+#[derive(Deserialize, Debug, Default, Validate)]
+#[validate(schema(function = "validate_batch_size"))]
+pub struct EntryBatch {
+    #[serde(default)]
+    pub mode: BatchMode,
+    #[serde(default)]
+    #[validate(nested)]
+    pub create: Vec<NewJournalEntry>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub update: Vec<BatchUpdate>,
+    #[serde(default)]
+    pub delete: Vec<JournalEntryId>,
+}
+
+impl EntryBatch {
+    pub fn len(&self) -> usize {
+        self.create.len() + self.update.len() + self.delete.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Deserialize, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    /// Nothing is committed if any of the operations fails.
+    #[default]
+    Atomic,
+    /// Successful operations are committed even if some of the others fail.
+    BestEffort,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct BatchUpdate {
+    pub id: JournalEntryId,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub update: JournalEntryUpdate,
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug, Default)]
+pub struct BatchResult {
+    /// False when the batch was rolled back in the atomic mode.
+    pub committed: bool,
+    pub create: Vec<BatchItemResult>,
+    pub update: Vec<BatchItemResult>,
+    pub delete: Vec<BatchItemResult>,
+}
+
+impl BatchResult {
+    pub fn has_failures(&self) -> bool {
+        self.create.iter().chain(&self.update).chain(&self.delete).any(|r| r.error.is_some())
+    }
+}
+
+/// Outcome of a single batch operation, with the status code it would get as a separate request.
+#[derive(Eq, PartialEq, Serialize, Debug)]
+pub struct BatchItemResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<JournalEntryId>,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchItemResult {
+    pub fn succeeded(id: JournalEntryId) -> Self {
+        Self { id: Some(id), status: StatusCode::OK.as_u16(), error: None }
+    }
+
+    pub fn failed(id: Option<JournalEntryId>, error: &AppError) -> Self {
+        Self { id, status: error.status_code().as_u16(), error: Some(error.public_message()) }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    #[default]
+    Csv,
+    /// iCalendar with a `VEVENT` per entry.
+    Ics,
+}
+
+/// Column mapping and parsing options of an import. See [`crate::journal::import`].
+/// Only the time zone applies to the iCalendar import.
+#[derive(Deserialize, Debug, Validate)]
+pub struct ImportOptions {
+    #[serde(default)]
+    pub format: ImportFormat,
+    #[serde(default = "default_event_type_column")]
+    #[validate(custom(function = "validate_not_blank"))]
+    pub event_type_column: String,
+    #[serde(default = "default_occurred_at_column")]
+    #[validate(custom(function = "validate_not_blank"))]
+    pub occurred_at_column: String,
+    /// Optional column, used only if present in the CSV header when not set explicitly.
+    pub description_column: Option<String>,
+    /// Optional column, used only if present in the CSV header when not set explicitly.
+    pub tags_column: Option<String>,
+    /// Separator of the tags within the tags column.
+    #[serde(default = "default_tag_separator")]
+    pub tag_separator: char,
+    #[serde(default = "default_delimiter")]
+    #[validate(custom(function = "validate_delimiter"))]
+    pub delimiter: char,
+    /// `strftime`-like format of the timestamps, RFC 3339 or ISO 8601 local time by default.
+    pub timestamp_format: Option<String>,
+    /// IANA time zone of the timestamps without an offset, UTC by default.
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: Option<String>,
+    /// Validates the import without storing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Journal entry parsed from a CSV row or an iCalendar event.
+#[derive(Eq, PartialEq, Debug)]
+pub struct ImportEntry {
+    /// Line of the row in the CSV, starting at 1 with the header, or the position of the event
+    /// in the iCalendar, starting at 1.
+    pub line: u64,
+    pub event_type: String,
+    pub occurred_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug)]
+pub struct ImportError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug, Default)]
+pub struct ImportReport {
+    /// False in the dry-run mode or when some of the rows are not valid.
+    pub committed: bool,
+    /// Number of the valid rows, which are imported unless some other rows are not valid.
+    pub imported: usize,
+    pub created_event_types: Vec<String>,
+    pub errors: Vec<ImportError>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Markdown,
+    /// iCalendar with a `VEVENT` per entry.
+    Ics,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/jsonl",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Ics => "ics",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+}
+
+/// Filter of the calendar feed.
+#[derive(Deserialize, Debug, Default, Validate)]
+pub struct CalendarQuery {
+    pub event_type_id: Option<EventTypeId>,
+    /// Comma-separated tags which must all be present on the entry.
+    #[serde(default, deserialize_with = "deserialize_comma_separated")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Vec<String>,
+}
+
+impl From<CalendarQuery> for SearchFilter {
+    fn from(query: CalendarQuery) -> Self {
+        SearchFilter {
+            event_type_id: query.event_type_id,
+            tags_all: query.tags,
+            ..Default::default()
+        }
+    }
+}
+
+/// Secret token of a feed, returned only when it's created.
+#[derive(Serialize, Debug)]
+pub struct FeedToken {
+    pub token: String,
 }
 
+/// Exported journal entry with the name of its event type.
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct ExportEntry {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub entry: JournalEntry,
+    pub event_type: String,
+    /// Time of the entry in the user's time zone.
+    #[serde(skip)]
+    pub local_occurred_at: NaiveDateTime,
+}
+
+/// Partial update of an event type following JSON Merge Patch semantics (RFC 7396).
+/// Absent fields are left untouched, explicit `null` clears the tags. The name can't be cleared.
 #[derive(Deserialize, Debug, Default, Validate)]
+pub struct EventTypePatch {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[validate(custom(function = "validate_not_blank"))]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[validate(custom(function = "validate_field_definitions"))]
+    pub fields: Option<Option<Vec<FieldDefinition>>>,
+}
+
+impl EventTypePatch {
+    pub fn merge_into(self, current: EventType) -> EventTypeData {
+        EventTypeData {
+            name: self.name.unwrap_or(current.name),
+            tags: self.tags.map(Option::unwrap_or_default).unwrap_or(current.tags),
+            fields: self.fields.map(Option::unwrap_or_default).unwrap_or(current.fields.0),
+        }
+    }
+}
+
+/// Partial update of a journal entry following JSON Merge Patch semantics (RFC 7396).
+/// Absent fields are left untouched, explicit `null` clears the description or tags.
+/// The event type and time of the entry can't be cleared.
+#[derive(Deserialize, Debug, Default, Validate)]
+#[validate(schema(function = "validate_patch_time_range"))]
+pub struct JournalEntryPatch {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub event_type_id: Option<EventTypeId>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<Option<Vec<String>>>,
+    #[serde(default, alias = "created_at", deserialize_with = "deserialize_some")]
+    pub occurred_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub ended_at: Option<Option<DateTime<Utc>>>,
+    /// Values are merged with the current ones, explicit `null` removes the value.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub values: Option<Option<FieldValues>>,
+}
+
+impl JournalEntryPatch {
+    pub fn merge_into(self, current: JournalEntry) -> JournalEntryUpdate {
+        JournalEntryUpdate {
+            event_type_id: self.event_type_id,
+            description: self.description.unwrap_or(current.description),
+            tags: self.tags.map(Option::unwrap_or_default).unwrap_or(current.tags),
+            occurred_at: self.occurred_at,
+            ended_at: self.ended_at.unwrap_or(current.ended_at),
+            values: match self.values {
+                None => current.values.0,
+                Some(None) => FieldValues::new(),
+                Some(Some(patch)) => merge_values(current.values.0, patch),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default, Validate)]
 #[validate(schema(function = "validate_filters"))]
 pub struct SearchFilter {
+    /// Full-text query over the descriptions, in the web search syntax, e.g. `dentist -checkup`.
+    /// Without an explicit `sort`, matching entries are ordered by relevance.
+    #[validate(custom(function = "validate_not_blank"))]
+    pub q: Option<String>,
+    /// Query in the search query language, e.g. `type:run -tag:indoor "knee pain"`, combined
+    /// with the other criteria. See [`crate::journal::query`].
+    pub query: Option<String>,
     pub event_type_id: Option<EventTypeId>,
-    #[serde(default)]
-    pub tags: Vec<String>,
+    /// Comma-separated tags which must all be present on the entry.
+    #[serde(default, alias = "tags", deserialize_with = "deserialize_comma_separated")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags_all: Vec<String>,
+    /// Comma-separated tags of which at least one must be present on the entry.
+    #[serde(default, deserialize_with = "deserialize_comma_separated")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags_any: Vec<String>,
+    /// Comma-separated tags of which none can be present on the entry.
+    #[serde(default, deserialize_with = "deserialize_comma_separated")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags_none: Vec<String>,
     pub before: Option<DateTime<Utc>>,
     pub after: Option<DateTime<Utc>>,
+    /// Together with `overlaps_to` matches entries whose time span overlaps the given range.
+    pub overlaps_from: Option<DateTime<Utc>>,
+    pub overlaps_to: Option<DateTime<Utc>>,
+    /// Matches entries that occurred on the given day in the user's time zone.
+    pub on: Option<NaiveDate>,
+    /// Together with `to_date` matches entries that occurred within the given days (inclusive)
+    /// in the user's time zone.
+    pub from_date: Option<NaiveDate>,
+    pub to_date: Option<NaiveDate>,
     pub sort: Option<SortOrder>,
     pub offset: Option<u32>,
     pub limit: Option<u32>,
 }
 
-#[derive(Eq, PartialEq, Deserialize, Debug, derive_more::Display)]
+impl SearchFilter {
+    /// Overrides the criteria of this filter with the ones set in `overrides`.
+    pub fn merge(self, overrides: SearchFilter) -> SearchFilter {
+        let or_list = |list: Vec<String>, other: Vec<String>| {
+            if other.is_empty() { list } else { other }
+        };
+        SearchFilter {
+            q: overrides.q.or(self.q),
+            query: overrides.query.or(self.query),
+            event_type_id: overrides.event_type_id.or(self.event_type_id),
+            tags_all: or_list(self.tags_all, overrides.tags_all),
+            tags_any: or_list(self.tags_any, overrides.tags_any),
+            tags_none: or_list(self.tags_none, overrides.tags_none),
+            before: overrides.before.or(self.before),
+            after: overrides.after.or(self.after),
+            overlaps_from: overrides.overlaps_from.or(self.overlaps_from),
+            overlaps_to: overrides.overlaps_to.or(self.overlaps_to),
+            on: overrides.on.or(self.on),
+            from_date: overrides.from_date.or(self.from_date),
+            to_date: overrides.to_date.or(self.to_date),
+            sort: overrides.sort.or(self.sort),
+            offset: overrides.offset.or(self.offset),
+            limit: overrides.limit.or(self.limit),
+        }
+    }
+}
+
+/// Named search filter stored by the user.
+#[derive(Clone, Eq, PartialEq, Serialize, Debug)]
+pub struct SavedSearch {
+    pub id: SavedSearchId,
+    pub user_id: UserId,
+    pub name: String,
+    pub filter: Json<SearchFilter>,
+}
+
+/// Recent entries of a saved search, see [`crate::journal::feed`].
+#[derive(Debug)]
+pub struct SearchFeed {
+    pub search: SavedSearch,
+    pub entries: Vec<FeedEntry>,
+}
+
+#[derive(Debug)]
+pub struct FeedEntry {
+    pub entry: JournalEntry,
+    pub event_type: String,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct SavedSearchData {
+    #[validate(custom(function = "validate_not_blank"))]
+    pub name: String,
+    #[serde(default)]
+    #[validate(nested)]
+    pub filter: SearchFilter,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug, derive_more::Display)]
 pub enum SortOrder {
     #[display("ASC")]
     #[serde(alias = "asc", alias = "ASC")]
@@ -98,6 +674,539 @@ pub enum SortOrder {
     Desc,
 }
 
+/// Position in the journal entries ordered by `(occurred_at, id)` used by the keyset pagination.
+/// Clients get it as an opaque string, where the empty string stands for the first page.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Cursor {
+    Start,
+    After(DateTime<Utc>, JournalEntryId),
+}
+
+impl Cursor {
+    pub fn after(entry: &JournalEntry) -> Self {
+        Cursor::After(entry.occurred_at, entry.id)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cursor::Start => Ok(()),
+            Cursor::After(occurred_at, id) => {
+                let position = format!("{}:{}", occurred_at.timestamp_micros(), id);
+                f.write_str(&URL_SAFE_NO_PAD.encode(position))
+            }
+        }
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Cursor::Start);
+        }
+        URL_SAFE_NO_PAD
+            .decode(s)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|position| {
+                let (micros, id) = position.split_once(':')?;
+                let occurred_at = DateTime::from_timestamp_micros(micros.parse().ok()?)?;
+                Some(Cursor::After(occurred_at, JournalEntryId(id.parse().ok()?)))
+            })
+            .ok_or_else(|| format!("invalid cursor `{s}`"))
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct PageQuery {
+    /// Switches the listing from the offset to the keyset pagination, where `limit` is the page
+    /// size and `sort` the direction of the `(occurred_at, id)` order.
+    pub cursor: Option<Cursor>,
+    /// Wraps the offset listing in an [`OffsetPage`], which can also be requested by the
+    /// `Accept: application/json; profile=envelope` header.
+    #[serde(default)]
+    pub envelope: bool,
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug)]
+pub struct OffsetPage<T> {
+    pub items: Vec<T>,
+    /// Number of all entries matching the filter, regardless of the offset and limit.
+    pub total: i64,
+    pub has_more: bool,
+    pub offset: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, missing on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Position in the change log of the delta sync, i.e. the version of the last synced change.
+/// Clients get it as an opaque string, where the empty string stands for a full sync.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct ChangeCursor(pub i64);
+
+impl fmt::Display for ChangeCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => Ok(()),
+            version => f.write_str(&URL_SAFE_NO_PAD.encode(version.to_string())),
+        }
+    }
+}
+
+impl FromStr for ChangeCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(ChangeCursor::default());
+        }
+        URL_SAFE_NO_PAD
+            .decode(s)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|version| version.parse().ok())
+            .filter(|version: &i64| *version > 0)
+            .map(ChangeCursor)
+            .ok_or_else(|| format!("invalid cursor `{s}`"))
+    }
+}
+
+impl Serialize for ChangeCursor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChangeCursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct SyncQuery {
+    #[serde(default)]
+    pub cursor: ChangeCursor,
+    /// Maximum number of changes returned, the rest is fetched from the returned cursor.
+    #[serde(default = "default_sync_limit")]
+    #[validate(range(min = 1, max = 1000))]
+    pub limit: u32,
+}
+
+/// Synced row together with its version, which increases with every change of the row.
+#[derive(Eq, PartialEq, Serialize, Debug, sqlx::FromRow)]
+pub struct Versioned<T> {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub item: T,
+    pub version: i64,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum SyncKind {
+    EventType,
+    Entry,
+}
+
+/// Record of a deleted event type or entry. Entries deleted along with their event type get
+/// their own tombstones.
+#[derive(Eq, PartialEq, Serialize, Debug, sqlx::FromRow)]
+pub struct Tombstone {
+    pub kind: SyncKind,
+    pub id: Uuid,
+    pub version: i64,
+}
+
+/// Changes after the cursor of a [`SyncQuery`], to be applied by the client in the order of their
+/// versions. A tombstone removes the row of the same id only if the row has a lower version.
+#[derive(Eq, PartialEq, Serialize, Debug, Default)]
+pub struct SyncChanges {
+    pub event_types: Vec<Versioned<EventType>>,
+    pub entries: Vec<Versioned<JournalEntry>>,
+    pub deleted: Vec<Tombstone>,
+    /// Cursor of the next sync, which also continues the current one when there are more changes.
+    pub cursor: ChangeCursor,
+    pub has_more: bool,
+}
+
+/// Changes made by a client while offline, applied one by one in the given order. Creations
+/// carry the ids generated by the client, updates and deletions the version of the row the client
+/// has changed and are applied only if the row hasn't been changed on the server since.
+#[derive(Deserialize, Debug, Validate)]
+#[validate(schema(function = "validate_sync_push_size"))]
+pub struct SyncPush {
+    #[validate(nested)]
+    pub changes: Vec<SyncChange>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SyncChange {
+    CreateEventType {
+        id: EventTypeId,
+        #[serde(flatten)]
+        event_type: EventTypeData,
+    },
+    UpdateEventType {
+        id: EventTypeId,
+        base_version: i64,
+        #[serde(flatten)]
+        event_type: EventTypeData,
+    },
+    DeleteEventType {
+        id: EventTypeId,
+        base_version: i64,
+    },
+    CreateEntry {
+        id: JournalEntryId,
+        #[serde(flatten)]
+        entry: NewJournalEntry,
+    },
+    UpdateEntry {
+        id: JournalEntryId,
+        base_version: i64,
+        #[serde(flatten)]
+        update: JournalEntryUpdate,
+    },
+    DeleteEntry {
+        id: JournalEntryId,
+        base_version: i64,
+    },
+}
+
+impl SyncChange {
+    /// Returns the kind and id of the changed row, and the version the change is based on.
+    pub fn target(&self) -> (SyncKind, Uuid, Option<i64>) {
+        match *self {
+            SyncChange::CreateEventType { id, .. } => (SyncKind::EventType, id.0, None),
+            SyncChange::UpdateEventType { id, base_version, .. }
+            | SyncChange::DeleteEventType { id, base_version } => {
+                (SyncKind::EventType, id.0, Some(base_version))
+            }
+            SyncChange::CreateEntry { id, .. } => (SyncKind::Entry, id.0, None),
+            SyncChange::UpdateEntry { id, base_version, .. }
+            | SyncChange::DeleteEntry { id, base_version } => {
+                (SyncKind::Entry, id.0, Some(base_version))
+            }
+        }
+    }
+}
+
+impl Validate for SyncChange {
+    fn validate(&self) -> Result<(), validator::ValidationErrors> {
+        match self {
+            SyncChange::CreateEventType { event_type, .. }
+            | SyncChange::UpdateEventType { event_type, .. } => event_type.validate(),
+            SyncChange::CreateEntry { entry, .. } => entry.validate(),
+            SyncChange::UpdateEntry { update, .. } => update.validate(),
+            SyncChange::DeleteEventType { .. } | SyncChange::DeleteEntry { .. } => Ok(()),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug)]
+pub struct SyncPushResult {
+    /// Outcomes of the changes, in the order of the pushed changes.
+    pub results: Vec<SyncChangeResult>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Applied,
+    /// The row has been changed or deleted on the server since the client's version, or a row
+    /// with the id of a creation already exists. Nothing was changed.
+    Conflict,
+    /// The change is invalid, e.g. it references an unknown event type.
+    Rejected,
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug)]
+pub struct SyncChangeResult {
+    pub status: SyncStatus,
+    /// Version of the row after the change, or the server's version on a conflict. Missing when
+    /// the row doesn't exist (anymore).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SyncChangeResult {
+    pub fn applied(version: Option<i64>) -> Self {
+        Self { status: SyncStatus::Applied, version, error: None }
+    }
+
+    pub fn conflict(version: Option<i64>) -> Self {
+        Self { status: SyncStatus::Conflict, version, error: None }
+    }
+
+    pub fn rejected(error: &AppError) -> Self {
+        Self { status: SyncStatus::Rejected, version: None, error: Some(error.public_message()) }
+    }
+}
+
+/// Aggregation of a numeric custom field over the entries matching a [`SearchFilter`].
+#[derive(Deserialize, Debug, Validate)]
+pub struct StatsQuery {
+    /// Name of the custom field to aggregate. Entries without a numeric value are skipped.
+    #[validate(custom(function = "validate_not_blank"))]
+    pub field: String,
+    pub bucket: TimeBucket,
+    /// IANA time zone in which the buckets are computed, the user's time zone by default.
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: Option<String>,
+    /// Comma-separated list of percentiles between 0 and 1.
+    #[serde(default = "default_percentiles", deserialize_with = "deserialize_comma_separated")]
+    #[validate(custom(function = "validate_percentiles"))]
+    pub percentiles: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Deserialize, Debug, derive_more::Display)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBucket {
+    #[display("hour")]
+    Hour,
+    #[display("day")]
+    Day,
+    #[display("week")]
+    Week,
+    #[display("month")]
+    Month,
+}
+
+/// Counting of the entries matching a [`SearchFilter`] per time bucket.
+#[derive(Deserialize, Debug, Validate)]
+pub struct CountsQuery {
+    pub bucket: TimeBucket,
+    /// IANA time zone in which the buckets are computed, the user's time zone by default.
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: Option<String>,
+    /// Comma-separated list of additional groupings, e.g. `event_type,tag`.
+    #[serde(default, deserialize_with = "deserialize_comma_separated")]
+    pub group_by: Vec<CountGroup>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CountGroup {
+    EventType,
+    /// Entries are counted once per each of their tags, entries without tags are skipped.
+    Tag,
+}
+
+impl FromStr for CountGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "event_type" => Ok(CountGroup::EventType),
+            "tag" => Ok(CountGroup::Tag),
+            _ => Err(format!("unknown group `{s}`, expected `event_type` or `tag`")),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Serialize, Debug, sqlx::FromRow)]
+pub struct EntryCount {
+    /// Start of the bucket in the local time of the requested time zone.
+    pub bucket: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_type_id: Option<EventTypeId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub count: i64,
+}
+
+#[derive(PartialEq, Serialize, Debug)]
+pub struct FieldStats {
+    /// Start of the bucket in the local time of the requested time zone.
+    pub bucket: NaiveDateTime,
+    pub count: i64,
+    pub sum: f64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub percentiles: Vec<Percentile>,
+}
+
+#[derive(PartialEq, Serialize, Debug)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub value: f64,
+}
+
+/// Fuzzy search over entry descriptions, event type names and tags.
+#[derive(Deserialize, Debug, Validate)]
+pub struct FuzzySearchQuery {
+    #[validate(custom(function = "validate_not_blank"))]
+    pub q: String,
+    #[serde(default = "default_search_limit")]
+    #[validate(range(min = 1, max = 100))]
+    pub limit: u32,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchResult {
+    Entry {
+        id: JournalEntryId,
+        event_type_id: EventTypeId,
+        description: Option<String>,
+        tags: Vec<String>,
+        occurred_at: DateTime<Utc>,
+        score: f32,
+    },
+    EventType {
+        id: EventTypeId,
+        name: String,
+        tags: Vec<String>,
+        score: f32,
+    },
+}
+
+impl SearchResult {
+    /// Trigram similarity of the best matching text, between 0 and 1.
+    pub fn score(&self) -> f32 {
+        match self {
+            SearchResult::Entry { score, .. } | SearchResult::EventType { score, .. } => *score,
+        }
+    }
+}
+
+fn merge_values(mut values: FieldValues, patch: FieldValues) -> FieldValues {
+    for (name, value) in patch {
+        if value.is_null() {
+            values.remove(&name);
+        } else {
+            values.insert(name, value);
+        }
+    }
+    values
+}
+
+/// Validates values of the custom fields against the field definitions of the event type.
+/// Returns the list of invalid (unknown, missing or mistyped) fields.
+pub fn validate_field_values(
+    definitions: &[FieldDefinition],
+    values: &FieldValues,
+) -> Result<(), Vec<InvalidField>> {
+    let unknown = values
+        .keys()
+        .filter(|&name| !definitions.iter().any(|d| &d.name == name))
+        .map(|name| InvalidField(format!("values.{name}")));
+    let invalid = definitions
+        .iter()
+        .filter(|d| match values.get(&d.name) {
+            Some(value) => !d.kind.accepts(value),
+            None => d.required,
+        })
+        .map(|d| InvalidField(format!("values.{}", d.name)));
+
+    let invalid_fields: Vec<InvalidField> = unknown.chain(invalid).collect();
+    if invalid_fields.is_empty() { Ok(()) } else { Err(invalid_fields) }
+}
+
+/// Distinguishes a field present in the payload from an absent one. Combined with
+/// `#[serde(default)]`, an absent field becomes `None`, while a present one becomes `Some`,
+/// so for `Option<Option<T>>` fields an explicit `null` is deserialized as `Some(None)`.
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Deserializes a comma-separated string (as used in query parameters) into a list of values.
+/// A plain list is accepted as well, e.g. from JSON.
+fn deserialize_comma_separated<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: FromStr + Deserialize<'de>,
+    T::Err: fmt::Display,
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CommaSeparated<T> {
+        Joined(String),
+        List(Vec<T>),
+    }
+
+    match CommaSeparated::deserialize(deserializer)? {
+        CommaSeparated::Joined(joined) => joined
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().map_err(de::Error::custom))
+            .collect(),
+        CommaSeparated::List(list) => Ok(list),
+    }
+}
+
+const MAX_BATCH_SIZE: usize = 1000;
+
+fn default_event_type_column() -> String {
+    "event_type".to_string()
+}
+
+fn default_occurred_at_column() -> String {
+    "occurred_at".to_string()
+}
+
+fn default_tag_separator() -> char {
+    ','
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_search_limit() -> u32 {
+    20
+}
+
+fn default_sync_limit() -> u32 {
+    500
+}
+
+fn default_percentiles() -> Vec<f64> {
+    vec![0.5, 0.9]
+}
+
+fn validate_percentiles(percentiles: &[f64]) -> Result<(), ValidationError> {
+    if percentiles.iter().all(|p| (0.0..=1.0).contains(p)) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("percentiles"))
+    }
+}
+
 fn validate_not_blank(value: &str) -> Result<(), ValidationError> {
     if value.trim().is_empty() { Err(ValidationError::new("blank")) } else { Ok(()) }
 }
@@ -110,10 +1219,528 @@ fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
     }
 }
 
-fn validate_filters(filter: &SearchFilter) -> Result<(), ValidationError> {
-    if let (Some(before), Some(after)) = (filter.before, filter.after) {
-        (before <= after).then_some(()).ok_or(ValidationError::new("before, after"))
+fn validate_field_definitions(fields: &[FieldDefinition]) -> Result<(), ValidationError> {
+    let mut names = HashSet::new();
+    let valid = fields.iter().all(|f| {
+        let valid_kind = match &f.kind {
+            FieldKind::Enum { values } => !values.is_empty(),
+            _ => true,
+        };
+        !f.name.trim().is_empty() && names.insert(f.name.as_str()) && valid_kind
+    });
+    if valid { Ok(()) } else { Err(ValidationError::new("fields")) }
+}
+
+fn validate_delimiter(delimiter: &char) -> Result<(), ValidationError> {
+    if delimiter.is_ascii() { Ok(()) } else { Err(ValidationError::new("delimiter")) }
+}
+
+fn validate_batch_size(batch: &EntryBatch) -> Result<(), ValidationError> {
+    if batch.is_empty() || batch.len() > MAX_BATCH_SIZE {
+        Err(ValidationError::new("batch size"))
     } else {
         Ok(())
     }
 }
+
+fn validate_sync_push_size(push: &SyncPush) -> Result<(), ValidationError> {
+    if push.changes.is_empty() || push.changes.len() > MAX_BATCH_SIZE {
+        Err(ValidationError::new("changes size"))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_new_entry_time_range(entry: &NewJournalEntry) -> Result<(), ValidationError> {
+    validate_time_range(entry.occurred_at, entry.ended_at, false, "occurred_at, ended_at")
+}
+
+fn validate_update_time_range(update: &JournalEntryUpdate) -> Result<(), ValidationError> {
+    validate_time_range(update.occurred_at, update.ended_at, false, "occurred_at, ended_at")
+}
+
+fn validate_patch_time_range(patch: &JournalEntryPatch) -> Result<(), ValidationError> {
+    validate_time_range(patch.occurred_at, patch.ended_at.flatten(), false, "occurred_at, ended_at")
+}
+
+fn validate_filters(filter: &SearchFilter) -> Result<(), ValidationError> {
+    if let Some(Err(error)) = filter.query.as_deref().map(query::parse) {
+        let mut validation_error = ValidationError::new("query");
+        validation_error.code = format!("query: {error}").into();
+        return Err(validation_error);
+    }
+    // Excluding a required tag, or all of the alternatives, would never match any entry.
+    if filter.tags_all.iter().any(|t| filter.tags_none.contains(t)) {
+        return Err(ValidationError::new("tags_all, tags_none"));
+    }
+    if !filter.tags_any.is_empty() && filter.tags_any.iter().all(|t| filter.tags_none.contains(t)) {
+        return Err(ValidationError::new("tags_any, tags_none"));
+    }
+    if let (Some(before), Some(after)) = (filter.before, filter.after) {
+        (before <= after).then_some(()).ok_or(ValidationError::new("before, after"))?;
+    }
+    validate_time_range(
+        filter.overlaps_from,
+        filter.overlaps_to,
+        true,
+        "overlaps_from, overlaps_to",
+    )?;
+    match (filter.from_date, filter.to_date) {
+        (Some(from), Some(to)) if to < from => Err(ValidationError::new("from_date, to_date")),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the end of the range is after its start (or equal, if the range can be empty).
+fn validate_time_range(
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    allow_empty: bool,
+    code: &'static str,
+) -> Result<(), ValidationError> {
+    match (start, end) {
+        (Some(start), Some(end)) if end < start || (end == start && !allow_empty) => {
+            Err(ValidationError::new(code))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::error::QueryPayloadError;
+    use actix_web::web;
+    use serde_json::json;
+
+    fn field(name: &str, kind: FieldKind, required: bool) -> FieldDefinition {
+        FieldDefinition { name: name.to_string(), kind, required }
+    }
+
+    fn invalid_names(result: Result<(), Vec<InvalidField>>) -> Vec<String> {
+        result.err().unwrap_or_default().into_iter().map(|f| f.0).collect()
+    }
+
+    #[test]
+    fn test_validate_field_values_all_kinds() {
+        let definitions = vec![
+            field("distance", FieldKind::Number { unit: Some("km".to_string()) }, true),
+            field("laps", FieldKind::Integer, false),
+            field("indoor", FieldKind::Boolean, false),
+            field("surface", FieldKind::Enum { values: vec!["road".to_string()] }, false),
+            field("note", FieldKind::Text, false),
+            field("mood", FieldKind::Rating, false),
+        ];
+        let values = json!({
+            "distance": 5.2, "laps": 12, "indoor": false, "surface": "road", "note": "ok", "mood": 5
+        });
+
+        let result = validate_field_values(&definitions, values.as_object().unwrap());
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validate_field_values_mistyped() {
+        let definitions = vec![
+            field("laps", FieldKind::Integer, false),
+            field("surface", FieldKind::Enum { values: vec!["road".to_string()] }, false),
+            field("mood", FieldKind::Rating, false),
+        ];
+        let values = json!({ "laps": 1.5, "surface": "trail", "mood": 6 });
+
+        let result = validate_field_values(&definitions, values.as_object().unwrap());
+        assert_eq!(vec!["values.laps", "values.surface", "values.mood"], invalid_names(result));
+    }
+
+    #[test]
+    fn test_validate_field_values_unknown_and_missing_required() {
+        let definitions = vec![field("distance", FieldKind::Number { unit: None }, true)];
+        let values = json!({ "pace": 5 });
+
+        let result = validate_field_values(&definitions, values.as_object().unwrap());
+        assert_eq!(vec!["values.pace", "values.distance"], invalid_names(result));
+    }
+
+    #[test]
+    fn test_validate_field_definitions_duplicate_names() {
+        let fields =
+            vec![field("a", FieldKind::Text, false), field("a", FieldKind::Integer, false)];
+        assert!(validate_field_definitions(&fields).is_err());
+    }
+
+    #[test]
+    fn test_patch_merges_field_values() {
+        let current = json!({ "distance": 5, "laps": 3 }).as_object().unwrap().clone();
+        let patch = json!({ "distance": 6, "laps": null, "mood": 4 }).as_object().unwrap().clone();
+
+        let merged = merge_values(current, patch);
+        assert_eq!(json!({ "distance": 6, "mood": 4 }).as_object().unwrap(), &merged);
+    }
+
+    fn current_entry() -> JournalEntry {
+        JournalEntry {
+            id: JournalEntryId(Uuid::new_v4()),
+            user_id: UserId::new(Uuid::new_v4()),
+            event_type_id: EventTypeId(Uuid::new_v4()),
+            description: Some("typo".to_string()),
+            tags: vec!["test".to_string()],
+            occurred_at: Utc::now(),
+            ended_at: None,
+            duration_secs: None,
+            running: false,
+            recorded_at: Utc::now(),
+            values: Json(FieldValues::new()),
+            snippet: None,
+        }
+    }
+
+    #[test]
+    fn test_event_type_patch_keeps_absent_fields() {
+        let current = EventType {
+            id: EventTypeId(Uuid::new_v4()),
+            user_id: UserId::new(Uuid::new_v4()),
+            name: "current".to_string(),
+            tags: vec!["tag1".to_string()],
+            fields: Json(vec![]),
+        };
+        let patch = EventTypePatch { name: Some("patched".to_string()), ..Default::default() };
+
+        let update = patch.merge_into(current);
+        assert_eq!("patched", update.name);
+        assert_eq!(vec!["tag1".to_string()], update.tags);
+        assert!(update.fields.is_empty());
+    }
+
+    #[test]
+    fn test_entry_patch_keeps_absent_fields() {
+        let patch = JournalEntryPatch {
+            description: Some(Some("fixed".to_string())),
+            ..Default::default()
+        };
+
+        let update = patch.merge_into(current_entry());
+        assert_eq!(None, update.event_type_id);
+        assert_eq!(Some("fixed"), update.description.as_deref());
+        assert_eq!(vec!["test".to_string()], update.tags);
+        assert_eq!(None, update.occurred_at);
+    }
+
+    #[test]
+    fn test_entry_patch_null_clears_fields() {
+        let patch =
+            JournalEntryPatch { description: Some(None), tags: Some(None), ..Default::default() };
+
+        let update = patch.merge_into(current_entry());
+        assert_eq!(None, update.description);
+        assert!(update.tags.is_empty());
+    }
+
+    #[test]
+    fn test_entry_patch_moves_entry() {
+        let target_event_type_id = EventTypeId(Uuid::new_v4());
+        let occurred_at = Utc::now();
+        let patch = JournalEntryPatch {
+            event_type_id: Some(target_event_type_id),
+            occurred_at: Some(occurred_at),
+            ..Default::default()
+        };
+
+        let update = patch.merge_into(current_entry());
+        assert_eq!(Some(target_event_type_id), update.event_type_id);
+        assert_eq!(Some("typo"), update.description.as_deref());
+        assert_eq!(vec!["test".to_string()], update.tags);
+        assert_eq!(Some(occurred_at), update.occurred_at);
+    }
+
+    #[test]
+    fn test_stats_query_from_query_string() {
+        let query = web::Query::<StatsQuery>::from_query(
+            "field=distance&bucket=week&timezone=Europe/Prague&percentiles=0.25,%200.75",
+        )
+        .unwrap()
+        .into_inner();
+
+        assert_eq!(TimeBucket::Week, query.bucket);
+        assert_eq!(vec![0.25, 0.75], query.percentiles);
+        assert!(query.validate().is_ok());
+    }
+
+    #[test]
+    fn test_stats_query_defaults_and_validation() {
+        let query =
+            web::Query::<StatsQuery>::from_query("field=distance&bucket=day").unwrap().into_inner();
+        assert_eq!(vec![0.5, 0.9], query.percentiles);
+        assert_eq!(None, query.timezone);
+
+        let invalid = web::Query::<StatsQuery>::from_query(
+            "field=%20&bucket=month&timezone=Mars/Olympus&percentiles=1.5",
+        )
+        .unwrap()
+        .into_inner();
+        let errors = invalid.validate().unwrap_err();
+        let fields = errors.field_errors();
+        assert!(fields.contains_key("field"));
+        assert!(fields.contains_key("timezone"));
+        assert!(fields.contains_key("percentiles"));
+    }
+
+    #[test]
+    fn test_counts_query_group_by() {
+        let query = web::Query::<CountsQuery>::from_query("bucket=hour&group_by=event_type,tag")
+            .unwrap()
+            .into_inner();
+        assert_eq!(TimeBucket::Hour, query.bucket);
+        assert_eq!(vec![CountGroup::EventType, CountGroup::Tag], query.group_by);
+
+        let query = web::Query::<CountsQuery>::from_query("bucket=week").unwrap().into_inner();
+        assert!(query.group_by.is_empty());
+
+        assert!(web::Query::<CountsQuery>::from_query("bucket=week&group_by=mood").is_err());
+    }
+
+    #[test]
+    fn test_filter_local_dates() {
+        let filter =
+            web::Query::<SearchFilter>::from_query("from_date=2024-03-31&to_date=2024-03-30")
+                .unwrap()
+                .into_inner();
+        assert!(filter.validate().is_err());
+
+        let filter = web::Query::<SearchFilter>::from_query("on=2024-03-31").unwrap().into_inner();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()), filter.on);
+        assert!(filter.validate().is_ok());
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_goal_progress_streaks_with_current_period_in_progress() {
+        let goal = Goal { period: GoalPeriod::Week, target: 2 };
+        let counts = PeriodCounts {
+            current_period: date("2024-03-25"),
+            counts: vec![
+                (date("2024-02-19"), 2),
+                (date("2024-02-26"), 3),
+                (date("2024-03-04"), 2),
+                (date("2024-03-18"), 2),
+                (date("2024-03-25"), 1),
+            ],
+        };
+
+        let progress = GoalProgress::calculate(goal, &counts);
+        assert_eq!(1, progress.current_count);
+        assert!(!progress.current_period_met);
+        assert_eq!(1, progress.current_streak);
+        assert_eq!(3, progress.longest_streak);
+        // The week of 2024-03-11 is missing and the current week is still in progress.
+        assert_eq!(0.8, progress.completion_rate);
+    }
+
+    #[test]
+    fn test_goal_progress_daily_goal_met_today() {
+        let goal = Goal { period: GoalPeriod::Day, target: 1 };
+        let counts = PeriodCounts {
+            current_period: date("2024-03-01"),
+            counts: vec![(date("2024-02-28"), 1), (date("2024-02-29"), 1), (date("2024-03-01"), 2)],
+        };
+
+        let progress = GoalProgress::calculate(goal, &counts);
+        assert!(progress.current_period_met);
+        assert_eq!(3, progress.current_streak);
+        assert_eq!(3, progress.longest_streak);
+        assert_eq!(1.0, progress.completion_rate);
+    }
+
+    #[test]
+    fn test_goal_progress_broken_monthly_streak() {
+        let goal = Goal { period: GoalPeriod::Month, target: 1 };
+        let counts = PeriodCounts {
+            current_period: date("2024-04-01"),
+            counts: vec![(date("2024-01-01"), 1), (date("2024-02-01"), 4)],
+        };
+
+        let progress = GoalProgress::calculate(goal, &counts);
+        assert_eq!(0, progress.current_streak);
+        assert_eq!(2, progress.longest_streak);
+        assert_eq!(2.0 / 3.0, progress.completion_rate);
+    }
+
+    #[test]
+    fn test_goal_progress_without_entries() {
+        let goal = Goal { period: GoalPeriod::Week, target: 3 };
+        let counts = PeriodCounts { current_period: date("2024-03-25"), counts: vec![] };
+
+        let progress = GoalProgress::calculate(goal, &counts);
+        assert_eq!(0, progress.current_count);
+        assert_eq!(0, progress.current_streak);
+        assert_eq!(0, progress.longest_streak);
+        assert_eq!(0.0, progress.completion_rate);
+    }
+
+    #[test]
+    fn test_filter_tag_queries() {
+        let filter =
+            web::Query::<SearchFilter>::from_query("tags_all=a,b&tags_any=c,%20d&tags_none=e,c")
+                .unwrap()
+                .into_inner();
+        assert_eq!(vec!["a", "b"], filter.tags_all);
+        assert_eq!(vec!["c", "d"], filter.tags_any);
+        assert_eq!(vec!["e", "c"], filter.tags_none);
+        assert!(filter.validate().is_ok());
+
+        let filter = web::Query::<SearchFilter>::from_query("tags=a").unwrap().into_inner();
+        assert_eq!(vec!["a"], filter.tags_all);
+    }
+
+    #[test]
+    fn test_filter_contradicting_tags() {
+        let filter = |query: &str| web::Query::<SearchFilter>::from_query(query).unwrap();
+
+        let errors = filter("tags_all=a,b&tags_none=b").validate().unwrap_err();
+        assert_eq!("tags_all, tags_none", errors.field_errors()["__all__"][0].code);
+        assert!(filter("tags_any=a,b&tags_none=a,b").validate().is_err());
+        assert!(filter("tags_any=a,b&tags_none=a").validate().is_ok());
+    }
+
+    #[test]
+    fn test_filter_invalid_query() {
+        let filter =
+            web::Query::<SearchFilter>::from_query("query=tag:a%20mood:good").unwrap().into_inner();
+        let errors = filter.validate().unwrap_err();
+        assert_eq!(
+            "query: unknown key `mood` at column 7",
+            errors.field_errors()["__all__"][0].code
+        );
+    }
+
+    #[test]
+    fn test_filter_json_round_trip_and_merge() {
+        let saved: SearchFilter = serde_json::from_value(json!({
+            "tags_all": ["a", "b"], "sort": "Desc", "limit": 10, "query": "type:run"
+        }))
+        .unwrap();
+        let stored = serde_json::to_value(&saved).unwrap();
+        assert_eq!(saved, serde_json::from_value(stored).unwrap());
+
+        let overrides =
+            web::Query::<SearchFilter>::from_query("tags=c&limit=5").unwrap().into_inner();
+        let merged = saved.merge(overrides);
+        assert_eq!(vec!["c"], merged.tags_all);
+        assert_eq!(Some(5), merged.limit);
+        assert_eq!(Some(SortOrder::Desc), merged.sort);
+        assert_eq!(Some("type:run".to_string()), merged.query);
+    }
+
+    #[test]
+    fn test_saved_search_validates_filter() {
+        let data: SavedSearchData = serde_json::from_value(json!({
+            "name": "contradiction", "filter": { "tags_all": ["a"], "tags_none": ["a"] }
+        }))
+        .unwrap();
+        assert!(data.validate().is_err());
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        fn page(query: &str) -> Result<Option<Cursor>, QueryPayloadError> {
+            web::Query::<PageQuery>::from_query(query).map(|q| q.into_inner().cursor)
+        }
+        let cursor = Cursor::After(
+            "2026-01-02T03:04:05.123456Z".parse().unwrap(),
+            JournalEntryId::new(Uuid::new_v4()),
+        );
+
+        assert_eq!(Some(cursor), page(&format!("cursor={cursor}")).unwrap());
+        assert_eq!(Some(Cursor::Start), page("cursor=").unwrap());
+        assert_eq!(None, page("envelope=true").unwrap());
+        assert!(page("cursor=bm90LWEtY3Vyc29y").is_err());
+    }
+
+    #[test]
+    fn test_batch_validation() {
+        let batch: EntryBatch = serde_json::from_value(json!({
+            "mode": "best_effort",
+            "update": [{ "id": Uuid::new_v4(), "tags": ["a"], "description": "moved" }],
+            "delete": [Uuid::new_v4()]
+        }))
+        .unwrap();
+        assert_eq!(BatchMode::BestEffort, batch.mode);
+        assert_eq!(Some("moved"), batch.update[0].update.description.as_deref());
+        assert!(batch.validate().is_ok());
+
+        let errors = EntryBatch::default().validate().unwrap_err();
+        assert_eq!("batch size", errors.field_errors()["__all__"][0].code);
+        let batch: EntryBatch = serde_json::from_value(json!({
+            "create": [{ "event_type_id": Uuid::new_v4(), "tags": [" "] }]
+        }))
+        .unwrap();
+        assert!(batch.validate().is_err());
+    }
+
+    #[test]
+    fn test_failed_batch_item_hides_internal_errors() {
+        let id = Some(JournalEntryId::new(Uuid::nil()));
+        let result =
+            BatchItemResult::failed(id, &AppError::DatabaseError(sqlx::Error::PoolTimedOut));
+        assert_eq!(500, result.status);
+        assert_eq!(Some("could not process request"), result.error.as_deref());
+        let result = BatchItemResult::failed(id, &AppError::NotFound);
+        assert_eq!(Some("requested resource not found"), result.error.as_deref());
+    }
+
+    #[test]
+    fn test_change_cursor_round_trip() {
+        fn sync(query: &str) -> Result<ChangeCursor, QueryPayloadError> {
+            web::Query::<SyncQuery>::from_query(query).map(|q| q.into_inner().cursor)
+        }
+        let cursor = ChangeCursor(42);
+
+        assert_eq!(cursor, sync(&format!("cursor={cursor}")).unwrap());
+        assert_eq!(ChangeCursor::default(), sync("cursor=").unwrap());
+        assert_eq!(ChangeCursor::default(), sync("limit=10").unwrap());
+        assert!(sync("cursor=bm90LWEtY3Vyc29y").is_err());
+    }
+
+    #[test]
+    fn test_sync_push_validation() {
+        let id = Uuid::new_v4();
+        let push: SyncPush = serde_json::from_value(json!({
+            "changes": [
+                { "op": "create_event_type", "id": id, "name": "Run", "tags": ["outdoor"] },
+                { "op": "update_entry", "id": id, "base_version": 7, "description": "moved" },
+                { "op": "delete_entry", "id": id, "base_version": 8 }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            vec![
+                (SyncKind::EventType, id, None),
+                (SyncKind::Entry, id, Some(7)),
+                (SyncKind::Entry, id, Some(8))
+            ],
+            push.changes.iter().map(SyncChange::target).collect::<Vec<_>>()
+        );
+        assert!(push.validate().is_ok());
+
+        let errors = SyncPush { changes: vec![] }.validate().unwrap_err();
+        assert_eq!("changes size", errors.field_errors()["__all__"][0].code);
+        let push: SyncPush = serde_json::from_value(json!({
+            "changes": [{ "op": "create_event_type", "id": id, "name": " " }]
+        }))
+        .unwrap();
+        assert!(push.validate().is_err());
+    }
+
+    #[test]
+    fn test_rejected_sync_change_hides_internal_errors() {
+        let error = |result: SyncChangeResult| result.error.unwrap_or_default();
+        let database_error = AppError::DatabaseError(sqlx::Error::PoolTimedOut);
+        assert_eq!("could not process request", error(SyncChangeResult::rejected(&database_error)));
+        let missing_row = AppError::DatabaseError(sqlx::Error::RowNotFound);
+        assert_eq!("not found", error(SyncChangeResult::rejected(&missing_row)));
+        let tags_used = AppError::TagsStillUsed(vec!["run".to_string()]);
+        assert_eq!(tags_used.to_string(), error(SyncChangeResult::rejected(&tags_used)));
+    }
+}
@@ -0,0 +1,253 @@
+//! Query language for the journal search, e.g.
+//! `type:run tag:morning -tag:indoor after:2026-01-01 "knee pain"`.
+//!
+//! A query is a whitespace-separated list of terms which all have to match. A term is either
+//! a `key:value` condition or a word or "quoted phrase" searched in the entry descriptions.
+//! Terms other than time bounds can be negated with a leading `-`.
+
+use crate::model::{AppError, InvalidField};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Term {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// `type:<name>` matches entries of the event type with the name (case-insensitive).
+    EventType(String),
+    /// `tag:<tag>` matches entries with the tag.
+    Tag(String),
+    /// `after:<date or instant>` matches entries which occurred on or after the bound.
+    After(TimeBound),
+    /// `before:<date or instant>` matches entries which occurred before the day, or at or before
+    /// the instant.
+    Before(TimeBound),
+    /// `on:<date>` matches entries which occurred on the day.
+    On(NaiveDate),
+    /// A word or phrase matched by the full-text search.
+    Text(String),
+}
+
+/// Dates are interpreted as the start of the day in the user's time zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeBound {
+    Date(NaiveDate),
+    Instant(DateTime<Utc>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Column (1-based, in characters) of the query where the error was found.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl From<ParseError> for AppError {
+    fn from(error: ParseError) -> Self {
+        AppError::Validation(vec![InvalidField(format!("query: {error}"))])
+    }
+}
+
+pub fn parse(query: &str) -> Result<Vec<Term>, ParseError> {
+    Parser { query, chars: query.char_indices().peekable() }.parse()
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(mut self) -> Result<Vec<Term>, ParseError> {
+        let mut terms = Vec::new();
+        loop {
+            while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            if self.chars.peek().is_none() {
+                return Ok(terms);
+            }
+            terms.push(self.term()?);
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, ParseError> {
+        let start = self.offset();
+        let negated = self.chars.next_if(|(_, c)| *c == '-').is_some();
+        if negated && self.chars.peek().is_none_or(|(_, c)| c.is_whitespace()) {
+            return Err(self.error(start, "missing term after `-`".to_string()));
+        }
+        if self.peek_char() == Some('"') {
+            let text = self.quoted()?;
+            return Ok(Term { negated, condition: Condition::Text(text) });
+        }
+
+        let word_start = self.offset();
+        let word = self.bare();
+        let Some((key, value)) = word.split_once(':').filter(|(key, _)| is_key(key)) else {
+            return Ok(Term { negated, condition: Condition::Text(word.to_string()) });
+        };
+        let value_start = word_start + key.len() + 1;
+        // The bare word ends at the quote, so the value is read separately.
+        let value = if value.is_empty() && self.peek_char() == Some('"') {
+            self.quoted()?
+        } else {
+            value.to_string()
+        };
+        if value.is_empty() {
+            return Err(self.error(value_start, format!("missing value for `{key}`")));
+        }
+
+        let condition = match key {
+            "type" => Condition::EventType(value),
+            "tag" => Condition::Tag(value),
+            "after" => Condition::After(self.time_bound(value_start, key, &value)?),
+            "before" => Condition::Before(self.time_bound(value_start, key, &value)?),
+            "on" => Condition::On(value.parse().map_err(|_| {
+                self.error(value_start, format!("invalid date `{value}` for `on`"))
+            })?),
+            _ => return Err(self.error(word_start, format!("unknown key `{key}`"))),
+        };
+        if negated && !matches!(condition, Condition::EventType(_) | Condition::Tag(_)) {
+            return Err(self.error(start, format!("`{key}` cannot be negated")));
+        }
+        Ok(Term { negated, condition })
+    }
+
+    /// Reads a phrase enclosed in double quotes, where `\"` and `\\` are escaped characters.
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.offset();
+        self.chars.next();
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => break,
+                Some((_, '\\')) if matches!(self.peek_char(), Some('"' | '\\')) => {
+                    text.extend(self.chars.next().map(|(_, c)| c));
+                }
+                Some((_, c)) => text.push(c),
+                None => return Err(self.error(start, "unterminated quote".to_string())),
+            }
+        }
+        if text.trim().is_empty() {
+            return Err(self.error(start, "empty phrase".to_string()));
+        }
+        Ok(text)
+    }
+
+    /// Reads a word up to the next whitespace or quote.
+    fn bare(&mut self) -> &'a str {
+        let start = self.offset();
+        while self.chars.next_if(|(_, c)| !c.is_whitespace() && *c != '"').is_some() {}
+        &self.query[start..self.offset()]
+    }
+
+    fn time_bound(&self, offset: usize, key: &str, value: &str) -> Result<TimeBound, ParseError> {
+        if let Ok(date) = value.parse() {
+            Ok(TimeBound::Date(date))
+        } else if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+            Ok(TimeBound::Instant(instant.to_utc()))
+        } else {
+            Err(self.error(offset, format!("invalid date or time `{value}` for `{key}`")))
+        }
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    /// Byte offset of the next character.
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.query.len(), |(i, _)| *i)
+    }
+
+    fn error(&self, offset: usize, message: String) -> ParseError {
+        ParseError { column: self.query[..offset].chars().count() + 1, message }
+    }
+}
+
+fn is_key(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(|c| c.is_ascii_alphabetic() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(negated: bool, condition: Condition) -> Term {
+        Term { negated, condition }
+    }
+
+    fn error(column: usize, message: &str) -> Result<Vec<Term>, ParseError> {
+        Err(ParseError { column, message: message.to_string() })
+    }
+
+    #[test]
+    fn test_parse_all_terms() {
+        let terms = parse(
+            r#"type:run tag:morning -tag:indoor after:2026-01-01 "knee pain" -swollen
+                before:2026-02-01T10:00:00+01:00 on:2026-01-15 tag:"two words""#,
+        )
+        .unwrap();
+
+        let date = |s: &str| s.parse().unwrap();
+        let expected = vec![
+            term(false, Condition::EventType("run".to_string())),
+            term(false, Condition::Tag("morning".to_string())),
+            term(true, Condition::Tag("indoor".to_string())),
+            term(false, Condition::After(TimeBound::Date(date("2026-01-01")))),
+            term(false, Condition::Text("knee pain".to_string())),
+            term(true, Condition::Text("swollen".to_string())),
+            term(
+                false,
+                Condition::Before(TimeBound::Instant("2026-02-01T09:00:00Z".parse().unwrap())),
+            ),
+            term(false, Condition::On(date("2026-01-15"))),
+            term(false, Condition::Tag("two words".to_string())),
+        ];
+        assert_eq!(expected, terms);
+    }
+
+    #[test]
+    fn test_parse_words_with_colon_and_escapes() {
+        let terms = parse(r#"10:30 "say \"hi\"""#).unwrap();
+        assert_eq!(
+            vec![
+                term(false, Condition::Text("10:30".to_string())),
+                term(false, Condition::Text("say \"hi\"".to_string())),
+            ],
+            terms
+        );
+        assert_eq!(Ok(vec![]), parse("   "));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(error(10, "unknown key `mood`"), parse("tag:a -x mood:good"));
+        assert_eq!(error(5, "missing value for `tag`"), parse("tag: run"));
+        assert_eq!(
+            error(7, "invalid date or time `yesterday` for `after`"),
+            parse("after:yesterday")
+        );
+        assert_eq!(error(4, "invalid date `2026-13-01` for `on`"), parse("on:2026-13-01"));
+        assert_eq!(error(3, "unterminated quote"), parse("a \"knee pain"));
+        assert_eq!(error(1, "`after` cannot be negated"), parse("-after:2026-01-01"));
+        assert_eq!(error(3, "missing term after `-`"), parse("a - b"));
+        assert_eq!(error(1, "empty phrase"), parse("\"  \""));
+    }
+
+    #[test]
+    fn test_parse_error_column_counts_characters() {
+        assert_eq!(error(6, "unknown key `x`"), parse("žluť x:1"));
+    }
+}
@@ -1,9 +1,24 @@
-use crate::journal::model::{EventType, EventTypeId, JournalEntry, JournalEntryId, SearchFilter};
+use crate::journal::model::{
+    BatchItemResult, BatchMode, BatchResult, ChangeCursor, CountGroup, CountsQuery, Cursor,
+    EntryBatch, EntryCount, EventType, EventTypeData, EventTypeId, EventTypePatch, ExportEntry,
+    FieldDefinition, FieldStats, FieldValues, Goal, GoalPeriod, ImportEntry, ImportError,
+    ImportReport, JournalEntry, JournalEntryId, JournalEntryPatch, JournalEntryUpdate,
+    NewJournalEntry, NewTimer, Percentile, PeriodCounts, SavedSearch, SavedSearchData,
+    SavedSearchId, SearchFilter, SearchResult, SortOrder, StatsQuery, SyncChange, SyncChangeResult,
+    SyncChanges, SyncKind, TimeBucket, Tombstone, Versioned, validate_field_values,
+};
+use crate::journal::query::{self, Condition, Term, TimeBound};
 use crate::model::AppError;
 use crate::user::model::UserId;
+use actix_web::ResponseError;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use chrono::{NaiveDate, NaiveDateTime};
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use log::error;
+use sqlx::types::Json;
+use sqlx::{Acquire, PgExecutor, PgPool, Postgres, QueryBuilder, Row, Transaction};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
 
 #[cfg_attr(test, mockall::automock)]
 #[async_trait]
@@ -21,6 +36,7 @@ pub trait EventTypeRepository {
         user_id: UserId,
         name: &str,
         tags: &[String],
+        fields: &[FieldDefinition],
     ) -> Result<EventTypeId, AppError>;
 
     async fn update(
@@ -29,9 +45,39 @@ pub trait EventTypeRepository {
         id: EventTypeId,
         name: &str,
         tags: &[String],
+        fields: &[FieldDefinition],
+    ) -> Result<bool, AppError>;
+
+    /// Merges the patch into the event type locked for the update, so that concurrent patches
+    /// don't overwrite each other's changes. Returns `false` if there is no such event type.
+    async fn patch(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+        patch: EventTypePatch,
     ) -> Result<bool, AppError>;
 
     async fn delete(&self, user_id: UserId, id: EventTypeId) -> Result<bool, AppError>;
+
+    async fn find_goal(&self, user_id: UserId, id: EventTypeId) -> Result<Option<Goal>, AppError>;
+
+    /// Sets or replaces the goal of the event type. Returns `false` if there is no such event type.
+    async fn upsert_goal(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+        goal: &Goal,
+    ) -> Result<bool, AppError>;
+
+    async fn delete_goal(&self, user_id: UserId, id: EventTypeId) -> Result<bool, AppError>;
+
+    /// Finds event types with a name or tag similar to the query, or starting with it.
+    async fn search(
+        &self,
+        user_id: UserId,
+        q: &str,
+        limit: u32,
+    ) -> Result<Vec<SearchResult>, AppError>;
 }
 
 pub struct PgEventTypeRepository {
@@ -53,8 +99,8 @@ impl EventTypeRepository for PgEventTypeRepository {
     ) -> Result<Option<EventType>, AppError> {
         let result = sqlx::query_as!(
             EventType,
-            r#"SELECT id as "id: _", user_id as "user_id: _", name, tags FROM event_type
-                WHERE id = $1 AND user_id = $2"#,
+            r#"SELECT id as "id: _", user_id as "user_id: _", name, tags, fields as "fields: _"
+                FROM event_type WHERE id = $1 AND user_id = $2"#,
             id as EventTypeId,
             user_id as UserId
         )
@@ -67,11 +113,12 @@ impl EventTypeRepository for PgEventTypeRepository {
     async fn find_by_user_id(&self, user_id: UserId) -> Result<Vec<EventType>, AppError> {
         let result = sqlx::query_as!(
             EventType,
-            r#"SELECT id as "id: _", user_id as "user_id: _", name, tags FROM event_type WHERE user_id = $1"#,
+            r#"SELECT id as "id: _", user_id as "user_id: _", name, tags, fields as "fields: _"
+                FROM event_type WHERE user_id = $1"#,
             user_id as UserId,
         )
-            .fetch_all(&self.pool)
-            .await?;
+        .fetch_all(&self.pool)
+        .await?;
 
         Ok(result)
     }
@@ -81,15 +128,9 @@ impl EventTypeRepository for PgEventTypeRepository {
         user_id: UserId,
         name: &str,
         tags: &[String],
+        fields: &[FieldDefinition],
     ) -> Result<EventTypeId, AppError> {
-        let result = sqlx::query!(
-            r#"INSERT INTO event_type (user_id, name, tags) VALUES ($1, $2, $3) RETURNING id as "id: EventTypeId""#,
-            user_id as UserId, name, tags)
-            .fetch_one(&self.pool)
-            .await
-            .map(|record| record.id)?;
-
-        Ok(result)
+        insert_event_type(&self.pool, None, user_id, name, tags, fields).await
     }
 
     async fn update(
@@ -98,53 +139,86 @@ impl EventTypeRepository for PgEventTypeRepository {
         id: EventTypeId,
         name: &str,
         tags: &[String],
+        fields: &[FieldDefinition],
     ) -> Result<bool, AppError> {
         let mut tx = self.pool.begin().await?;
+        let result = update_event_type(&mut tx, user_id, id, name, tags, fields).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
 
-        // Check if the given event type belongs to the user and lock it for update.
-        sqlx::query!(
-            r#"SELECT id FROM event_type where id = $1 AND user_id = $2 FOR UPDATE"#,
+    async fn patch(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+        patch: EventTypePatch,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_as!(
+            EventType,
+            r#"SELECT id as "id: _", user_id as "user_id: _", name, tags, fields as "fields: _"
+                FROM event_type WHERE id = $1 AND user_id = $2 FOR UPDATE"#,
             id as EventTypeId,
             user_id as UserId
         )
-        .fetch_one(&mut *tx)
+        .fetch_optional(&mut *tx)
         .await?;
+        let Some(current) = current else {
+            return Ok(false);
+        };
 
-        let missing_used_tags = sqlx::query!(
-            r#"
-            SELECT array(SELECT tag_row
-                         FROM (SELECT DISTINCT unnest(tags) as tag_row
-                               FROM journal_entry
-                               WHERE event_type_id = $1) as event_tags
-                         WHERE event_tags.tag_row != ALL ($2)) as used_tags"#,
+        let update = patch.merge_into(current);
+        let result =
+            update_event_type(&mut tx, user_id, id, &update.name, &update.tags, &update.fields)
+                .await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn delete(&self, user_id: UserId, id: EventTypeId) -> Result<bool, AppError> {
+        delete_event_type(&self.pool, user_id, id).await
+    }
+
+    async fn find_goal(&self, user_id: UserId, id: EventTypeId) -> Result<Option<Goal>, AppError> {
+        let result = sqlx::query_as!(
+            Goal,
+            r#"SELECT period as "period: _", target FROM event_type_goal
+                WHERE event_type_id = (SELECT id FROM event_type WHERE id = $1 AND user_id = $2)"#,
             id as EventTypeId,
-            tags
+            user_id as UserId
         )
-        .fetch_one(&mut *tx)
-        .await
-        .map(|r| r.used_tags.unwrap_or_default())?;
+        .fetch_optional(&self.pool)
+        .await?;
 
-        if !missing_used_tags.is_empty() {
-            return Err(AppError::TagsStillUsed(missing_used_tags));
-        }
+        Ok(result)
+    }
 
+    async fn upsert_goal(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+        goal: &Goal,
+    ) -> Result<bool, AppError> {
         let result = sqlx::query!(
-            r#"UPDATE event_type SET name = $1, tags = $2 WHERE id = $3"#,
-            name,
-            tags,
-            id as EventTypeId
+            r#"INSERT INTO event_type_goal (event_type_id, period, target)
+                SELECT id, $3, $4 FROM event_type WHERE id = $1 AND user_id = $2
+                ON CONFLICT (event_type_id) DO UPDATE SET period = EXCLUDED.period, target = EXCLUDED.target"#,
+            id as EventTypeId,
+            user_id as UserId,
+            goal.period as GoalPeriod,
+            goal.target
         )
-        .execute(&mut *tx)
+        .execute(&self.pool)
         .await
         .map(|r| r.rows_affected() > 0)?;
 
-        tx.commit().await?;
         Ok(result)
     }
 
-    async fn delete(&self, user_id: UserId, id: EventTypeId) -> Result<bool, AppError> {
+    async fn delete_goal(&self, user_id: UserId, id: EventTypeId) -> Result<bool, AppError> {
         let result = sqlx::query!(
-            r#"DELETE FROM event_type WHERE id = $1 and user_id = $2"#,
+            r#"DELETE FROM event_type_goal
+                WHERE event_type_id = (SELECT id FROM event_type WHERE id = $1 AND user_id = $2)"#,
             id as EventTypeId,
             user_id as UserId
         )
@@ -154,8 +228,46 @@ impl EventTypeRepository for PgEventTypeRepository {
 
         Ok(result)
     }
+
+    async fn search(
+        &self,
+        user_id: UserId,
+        q: &str,
+        limit: u32,
+    ) -> Result<Vec<SearchResult>, AppError> {
+        let prefix = like_prefix(q);
+        let mut tx = self.pool.begin().await?;
+        set_word_similarity_threshold(&mut tx).await?;
+        let result = sqlx::query!(
+            r#"SELECT id as "id: EventTypeId", name, tags,
+                    GREATEST(word_similarity($2, name),
+                        (SELECT max(similarity($2, tag)) FROM unnest(tags) AS tag)) AS "score!"
+                FROM event_type
+                WHERE user_id = $1
+                    AND ($2 <% name OR name ILIKE $3 OR name ILIKE '% ' || $3
+                        OR EXISTS (SELECT FROM unnest(tags) AS tag WHERE $2 % tag OR tag ILIKE $3))
+                ORDER BY 4 DESC LIMIT $4"#,
+            user_id as UserId,
+            q,
+            prefix,
+            i64::from(limit)
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|r| SearchResult::EventType { id: r.id, name: r.name, tags: r.tags, score: r.score })
+        .collect();
+
+        tx.commit().await?;
+        Ok(result)
+    }
 }
 
+/// Number of the exported entries fetched from the cursor at once.
+const EXPORT_BATCH_SIZE: usize = 500;
+
+pub type ExportEntryStream = BoxStream<'static, Result<ExportEntry, AppError>>;
+
 #[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait JournalEntryRepository {
@@ -171,24 +283,197 @@ pub trait JournalEntryRepository {
         filter: &SearchFilter,
     ) -> Result<Vec<JournalEntry>, AppError>;
 
-    async fn insert<'a>(
+    /// Finds at most `limit` entries following the cursor in the `(occurred_at, id)` order,
+    /// ignoring the offset and limit of the filter.
+    async fn find_page(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+        cursor: Cursor,
+        limit: u32,
+    ) -> Result<Vec<JournalEntry>, AppError>;
+
+    /// Streams the entries matching the filter ordered by `occurred_at` in the filter's sort order
+    /// (ascending by default). Entries are fetched in batches from a cursor held open by a
+    /// transaction until the stream is finished or dropped.
+    async fn export(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+    ) -> Result<ExportEntryStream, AppError>;
+
+    /// Aggregates numeric values of a custom field over the entries matching the filter,
+    /// grouped into time buckets in the requested time zone.
+    async fn stats(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+        stats: &StatsQuery,
+    ) -> Result<Vec<FieldStats>, AppError>;
+
+    /// Counts all entries matching the filter, ignoring its offset and limit.
+    async fn count(&self, user_id: UserId, filter: &SearchFilter) -> Result<i64, AppError>;
+
+    /// Finds the entries matching the filter along with the [`count`](Self::count) of all of
+    /// them, both read from one snapshot.
+    async fn find_with_total(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+    ) -> Result<(Vec<JournalEntry>, i64), AppError>;
+
+    /// Counts the entries matching the filter per time bucket in the requested time zone,
+    /// optionally grouped by event type and/or tag.
+    async fn counts(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+        counts: &CountsQuery,
+    ) -> Result<Vec<EntryCount>, AppError>;
+
+    /// Finds entries with a description or tag similar to the query, or with a word starting
+    /// with it.
+    async fn search(
+        &self,
+        user_id: UserId,
+        q: &str,
+        limit: u32,
+    ) -> Result<Vec<SearchResult>, AppError>;
+
+    /// Counts the entries of the event type per goal period in the user's time zone.
+    async fn count_per_period(
         &self,
         user_id: UserId,
         event_type_id: EventTypeId,
-        description: Option<&'a str>,
-        tags: &[String],
-        created_at: Option<DateTime<Utc>>,
+        period: GoalPeriod,
+    ) -> Result<PeriodCounts, AppError>;
+
+    async fn insert(
+        &self,
+        user_id: UserId,
+        entry: &NewJournalEntry,
     ) -> Result<JournalEntryId, AppError>;
 
-    async fn update<'a>(
+    /// Updates the journal entry. If `event_type_id` or `occurred_at` of the update is `None`,
+    /// the current value is kept.
+    async fn update(
         &self,
         user_id: UserId,
         id: JournalEntryId,
-        description: Option<&'a str>,
-        tags: &[String],
+        update: &JournalEntryUpdate,
+    ) -> Result<bool, AppError>;
+
+    /// Merges the patch into the entry locked for the update, so that concurrent patches don't
+    /// overwrite each other's changes. Returns `false` if there is no such entry.
+    async fn patch(
+        &self,
+        user_id: UserId,
+        id: JournalEntryId,
+        patch: JournalEntryPatch,
     ) -> Result<bool, AppError>;
 
     async fn delete(&self, user_id: UserId, id: JournalEntryId) -> Result<bool, AppError>;
+
+    /// Imports the entries in one transaction, creating the missing event types and adding the
+    /// missing tags to the existing ones. The transaction is committed only if `commit` is set
+    /// and all the entries are valid.
+    async fn import(
+        &self,
+        user_id: UserId,
+        entries: &[ImportEntry],
+        commit: bool,
+    ) -> Result<ImportReport, AppError>;
+
+    /// Executes the batch operations in one transaction, see [`EntryBatch`].
+    async fn batch(&self, user_id: UserId, batch: &EntryBatch) -> Result<BatchResult, AppError>;
+
+    /// Finds the changes of the user's event types and entries after the cursor, at most `limit`
+    /// of them in the order of their versions.
+    async fn find_changes(
+        &self,
+        user_id: UserId,
+        cursor: ChangeCursor,
+        limit: u32,
+    ) -> Result<SyncChanges, AppError>;
+
+    /// Applies the changes pushed by a client in one transaction, see
+    /// [`SyncPush`](crate::journal::model::SyncPush).
+    async fn push_changes(
+        &self,
+        user_id: UserId,
+        changes: &[SyncChange],
+    ) -> Result<Vec<SyncChangeResult>, AppError>;
+
+    async fn find_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<Option<SavedSearch>, AppError>;
+
+    async fn find_saved_searches(&self, user_id: UserId) -> Result<Vec<SavedSearch>, AppError>;
+
+    async fn insert_saved_search(
+        &self,
+        user_id: UserId,
+        search: &SavedSearchData,
+    ) -> Result<SavedSearchId, AppError>;
+
+    async fn update_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        search: &SavedSearchData,
+    ) -> Result<bool, AppError>;
+
+    async fn delete_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<bool, AppError>;
+
+    /// Sets the hash of the saved search's feed token, replacing the previous one.
+    async fn update_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        token_hash: &[u8],
+    ) -> Result<bool, AppError>;
+
+    /// Removes the saved search's feed token. Returns `false` if the search has no feed.
+    async fn delete_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<bool, AppError>;
+
+    /// Finds the saved search whose feed token has the hash.
+    async fn find_saved_search_by_feed_token(
+        &self,
+        token_hash: &[u8],
+    ) -> Result<Option<SavedSearch>, AppError>;
+
+    /// Sets the hash of the user's calendar feed token, replacing the previous one.
+    async fn upsert_calendar_token(
+        &self,
+        user_id: UserId,
+        token_hash: &[u8],
+    ) -> Result<(), AppError>;
+
+    async fn delete_calendar_token(&self, user_id: UserId) -> Result<bool, AppError>;
+
+    /// Finds the user whose calendar feed token has the hash.
+    async fn find_calendar_user(&self, token_hash: &[u8]) -> Result<Option<UserId>, AppError>;
+
+    /// Inserts a new running journal entry starting at the current time.
+    async fn start_timer(
+        &self,
+        user_id: UserId,
+        timer: &NewTimer,
+    ) -> Result<JournalEntryId, AppError>;
+
+    /// Stops the running journal entry at the current time. Returns `false` if there is no such
+    /// running entry, or if it starts in the future.
+    async fn stop_timer(&self, user_id: UserId, id: JournalEntryId) -> Result<bool, AppError>;
 }
 
 pub struct PgJournalEntryRepository {
@@ -200,17 +485,163 @@ impl PgJournalEntryRepository {
         Self { pool }
     }
 
+    /// Inserts the entry with the given id, or a generated one if there is none.
+    async fn insert_entry(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: Option<JournalEntryId>,
+        user_id: UserId,
+        entry: &NewJournalEntry,
+    ) -> Result<JournalEntryId, AppError> {
+        if !self
+            .references_valid_event_type(
+                tx,
+                user_id,
+                entry.event_type_id,
+                &entry.tags,
+                &entry.values,
+            )
+            .await?
+        {
+            return Err(AppError::EventTypeValidation);
+        }
+
+        let result = sqlx::query!(
+            r#"INSERT INTO journal_entry (id, user_id, event_type_id, description, tags, occurred_at, ended_at, field_values, search_language)
+                VALUES (COALESCE($8, gen_random_uuid()), $1, $2, $3, $4, COALESCE($5, now()), $6, $7,
+                    COALESCE((SELECT search_language FROM user_settings WHERE user_id = $1), 'simple'))
+                RETURNING id as "id: JournalEntryId""#,
+            user_id as UserId,
+            entry.event_type_id as EventTypeId,
+            entry.description,
+            &entry.tags,
+            entry.occurred_at,
+            entry.ended_at,
+            Json(&entry.values) as _,
+            id as Option<JournalEntryId>
+        )
+        .fetch_one(&mut **tx)
+        .await
+        .map(|record| record.id)?;
+
+        Ok(result)
+    }
+
+    async fn update_entry(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: UserId,
+        id: JournalEntryId,
+        update: &JournalEntryUpdate,
+    ) -> Result<bool, AppError> {
+        let Some(current_event_type_id) = sqlx::query!(
+            r#"SELECT event_type_id as "event_type_id: EventTypeId" FROM journal_entry
+                WHERE id = $1 AND user_id = $2 FOR UPDATE"#,
+            id as JournalEntryId,
+            user_id as UserId
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .map(|record| record.event_type_id) else {
+            return Ok(false);
+        };
+
+        // When moving the entry, tags are validated against the target event type.
+        let event_type_id = update.event_type_id.unwrap_or(current_event_type_id);
+        if !self
+            .references_valid_event_type(tx, user_id, event_type_id, &update.tags, &update.values)
+            .await?
+        {
+            return Err(AppError::EventTypeValidation);
+        }
+
+        // Setting the end of a running entry stops it.
+        let result = sqlx::query!(
+            r#"UPDATE journal_entry
+                SET event_type_id = $1, description = $2, tags = $3,
+                    occurred_at = COALESCE($4, occurred_at), ended_at = $5,
+                    running = running AND $5::timestamptz IS NULL, field_values = $6
+                WHERE id = $7 AND user_id = $8"#,
+            event_type_id as EventTypeId,
+            update.description,
+            &update.tags,
+            update.occurred_at,
+            update.ended_at,
+            Json(&update.values) as _,
+            id as JournalEntryId,
+            user_id as UserId
+        )
+        .execute(&mut **tx)
+        .await
+        .map(|r| r.rows_affected() > 0)?;
+
+        Ok(result)
+    }
+
+    /// Applies the pushed change, unless the row has been created, changed or deleted since the
+    /// version the change is based on.
+    async fn apply_change(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: UserId,
+        change: &SyncChange,
+    ) -> Result<SyncChangeResult, AppError> {
+        let (kind, id, base_version) = change.target();
+        let version = lock_row_version(tx, user_id, kind, id).await?;
+        let conflict = match (base_version, version) {
+            (None, None) => false,
+            (None, Some(_)) => true,
+            (Some(base_version), Some(version)) => base_version != version,
+            (Some(_), None) => {
+                // Rows which have never existed are not a conflict, but an invalid change.
+                if !has_tombstone(tx, user_id, kind, id).await? {
+                    return Err(AppError::NotFound);
+                }
+                true
+            }
+        };
+        if conflict {
+            return Ok(SyncChangeResult::conflict(version));
+        }
+
+        match change {
+            SyncChange::CreateEventType { id, event_type } => {
+                let EventTypeData { name, tags, fields } = event_type;
+                insert_event_type(&mut **tx, Some(*id), user_id, name, tags, fields).await?;
+            }
+            SyncChange::UpdateEventType { id, event_type, .. } => {
+                let EventTypeData { name, tags, fields } = event_type;
+                update_event_type(tx, user_id, *id, name, tags, fields).await?;
+            }
+            SyncChange::DeleteEventType { id, .. } => {
+                delete_event_type(&mut **tx, user_id, *id).await?;
+            }
+            SyncChange::CreateEntry { id, entry } => {
+                self.insert_entry(tx, Some(*id), user_id, entry).await?;
+            }
+            SyncChange::UpdateEntry { id, update, .. } => {
+                self.update_entry(tx, user_id, *id, update).await?;
+            }
+            SyncChange::DeleteEntry { id, .. } => {
+                delete_entry(&mut **tx, user_id, *id).await?;
+            }
+        }
+        Ok(SyncChangeResult::applied(lock_row_version(tx, user_id, kind, id).await?))
+    }
+
     /// Checks if a provided event type exists and contains the required tags for the new or
-    /// updated journal entry.
+    /// updated journal entry. Values of the custom fields are validated against the field
+    /// definitions of the event type, failing with [`AppError::Validation`] if they don't match.
     async fn references_valid_event_type(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_id: UserId,
         id: EventTypeId,
         tags: &[String],
+        values: &FieldValues,
     ) -> Result<bool, AppError> {
         let mut query: QueryBuilder<Postgres> =
-            QueryBuilder::new(r#"SELECT id FROM event_type WHERE id = "#);
+            QueryBuilder::new(r#"SELECT fields FROM event_type WHERE id = "#);
         query.push_bind(id);
         query.push(" AND user_id = ").push_bind(user_id);
 
@@ -220,8 +651,12 @@ impl PgJournalEntryRepository {
 
         query.push(" FOR UPDATE");
 
-        let result = query.build().fetch_optional(&mut **tx).await?;
-        Ok(result.is_some())
+        let Some(row) = query.build().fetch_optional(&mut **tx).await? else {
+            return Ok(false);
+        };
+        let fields: Json<Vec<FieldDefinition>> = row.try_get("fields")?;
+        validate_field_values(&fields, values).map_err(AppError::Validation)?;
+        Ok(true)
     }
 }
 
@@ -235,7 +670,8 @@ impl JournalEntryRepository for PgJournalEntryRepository {
         let result = sqlx::query_as!(
             JournalEntry,
             r#"SELECT id as "id: _", user_id as "user_id: _", event_type_id as "event_type_id: _",
-                description, tags, created_at
+                description, tags, occurred_at, ended_at, duration_secs, running, recorded_at,
+                field_values as "values: _", NULL as snippet
                 FROM journal_entry WHERE id = $1 AND user_id = $2"#,
             id as JournalEntryId,
             user_id as UserId
@@ -251,27 +687,54 @@ impl JournalEntryRepository for PgJournalEntryRepository {
         user_id: UserId,
         filter: &SearchFilter,
     ) -> Result<Vec<JournalEntry>, AppError> {
+        find_entries(&self.pool, user_id, filter).await
+    }
+
+    async fn find_page(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+        cursor: Cursor,
+        limit: u32,
+    ) -> Result<Vec<JournalEntry>, AppError> {
+        let sort = filter.sort.unwrap_or(SortOrder::Asc);
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::default();
+        push_entry_select(&mut query, user_id, filter)?;
+        if let Cursor::After(occurred_at, id) = cursor {
+            let operator = match sort {
+                SortOrder::Asc => " > (",
+                SortOrder::Desc => " < (",
+            };
+            query
+                .push(" AND (occurred_at, id)")
+                .push(operator)
+                .push_bind(occurred_at)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+        query.push(" ORDER BY occurred_at ").push(sort).push(", id ").push(sort);
+        query.push(" LIMIT ").push(limit);
+
+        let result = query.build_query_as::<JournalEntry>().fetch_all(&self.pool).await?;
+        Ok(result)
+    }
+
+    async fn export(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+    ) -> Result<ExportEntryStream, AppError> {
+        let sort = filter.sort.unwrap_or(SortOrder::Asc);
         let mut query: QueryBuilder<Postgres> = QueryBuilder::new(
-            r#"SELECT id, user_id, event_type_id, description, tags, created_at
-                FROM journal_entry WHERE user_id = "#,
+            "DECLARE export_cursor NO SCROLL CURSOR FOR \
+                SELECT entry.*, event_type.name AS event_type, entry.occurred_at AT TIME ZONE ",
         );
-        query.push_bind(user_id);
-
-        if let Some(id) = &filter.event_type_id {
-            query.push(" AND event_type_id = ").push_bind(id);
-        };
-        if !filter.tags.is_empty() {
-            query.push(" AND tags @> ").push_bind(&filter.tags);
-        };
-        if let Some(before) = &filter.before {
-            query.push(" AND created_at <= ").push_bind(before);
-        };
-        if let Some(after) = &filter.after {
-            query.push(" AND created_at >= ").push_bind(after);
-        };
-        if let Some(sort) = &filter.sort {
-            query.push(" ORDER BY created_at ").push(sort);
-        };
+        push_user_timezone(&mut query, user_id);
+        query.push(" AS local_occurred_at FROM (");
+        push_entry_select(&mut query, user_id, filter)?;
+        query.push(") AS entry JOIN event_type ON event_type.id = entry.event_type_id");
+        query.push(" ORDER BY entry.occurred_at ").push(sort).push(", entry.id ").push(sort);
         if let Some(offset) = filter.offset {
             query.push(" OFFSET ").push(offset);
         };
@@ -279,78 +742,503 @@ impl JournalEntryRepository for PgJournalEntryRepository {
             query.push(" LIMIT ").push(limit);
         };
 
-        let result = query.build_query_as::<JournalEntry>().fetch_all(&self.pool).await?;
-        Ok(result)
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("SET TRANSACTION READ ONLY").execute(&mut *tx).await?;
+        query.build().execute(&mut *tx).await?;
+
+        let batches = stream::try_unfold(Some(tx), fetch_export_batch)
+            .map_ok(|entries| stream::iter(entries.into_iter().map(Ok)));
+        Ok(batches.try_flatten().boxed())
     }
 
-    async fn insert<'a>(
+    async fn stats(
         &self,
         user_id: UserId,
-        event_type_id: EventTypeId,
-        description: Option<&'a str>,
-        tags: &[String],
-        created_at: Option<DateTime<Utc>>,
-    ) -> Result<JournalEntryId, AppError> {
-        let mut tx = self.pool.begin().await?;
-        if !self.references_valid_event_type(&mut tx, user_id, event_type_id, tags).await? {
-            return Err(AppError::EventTypeValidation);
-        }
-
-        let result = sqlx::query!(
-            r#"INSERT INTO journal_entry (user_id, event_type_id, description, tags, created_at)
-                VALUES ($1, $2, $3, $4, $5) RETURNING id as "id: JournalEntryId""#,
-            user_id as UserId,
-            event_type_id as EventTypeId,
-            description,
-            tags,
-            created_at.unwrap_or(Utc::now())
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .map(|record| record.id)?;
+        filter: &SearchFilter,
+        stats: &StatsQuery,
+    ) -> Result<Vec<FieldStats>, AppError> {
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new("SELECT ");
+        push_bucket(&mut query, user_id, stats.bucket, stats.timezone.as_deref());
+        query
+            .push(
+                r#" AS bucket, count(*) AS count, sum(value) AS sum, avg(value) AS avg,
+                    min(value) AS min, max(value) AS max, percentile_cont("#,
+            )
+            .push_bind(&stats.percentiles)
+            .push(
+                r#"::float8[]) WITHIN GROUP (ORDER BY value) AS percentiles
+                FROM (SELECT occurred_at, (field_values ->> "#,
+            )
+            .push_bind(&stats.field)
+            .push(")::float8 AS value FROM journal_entry WHERE jsonb_typeof(field_values -> ")
+            .push_bind(&stats.field)
+            .push(") = 'number' AND ");
+        push_filter_conditions(&mut query, user_id, filter)?;
+        query.push(") AS entry_value GROUP BY 1 ORDER BY 1");
 
-        tx.commit().await?;
+        let rows = query.build_query_as::<StatsRow>().fetch_all(&self.pool).await?;
+        let result = rows
+            .into_iter()
+            .map(|row| FieldStats {
+                bucket: row.bucket,
+                count: row.count,
+                sum: row.sum,
+                avg: row.avg,
+                min: row.min,
+                max: row.max,
+                percentiles: stats
+                    .percentiles
+                    .iter()
+                    .zip(row.percentiles)
+                    .map(|(&percentile, value)| Percentile { percentile, value })
+                    .collect(),
+            })
+            .collect();
         Ok(result)
     }
 
-    async fn update<'a>(
+    async fn count(&self, user_id: UserId, filter: &SearchFilter) -> Result<i64, AppError> {
+        count_entries(&self.pool, user_id, filter).await
+    }
+
+    async fn find_with_total(
         &self,
         user_id: UserId,
-        id: JournalEntryId,
-        description: Option<&'a str>,
-        tags: &[String],
-    ) -> Result<bool, AppError> {
+        filter: &SearchFilter,
+    ) -> Result<(Vec<JournalEntry>, i64), AppError> {
         let mut tx = self.pool.begin().await?;
-        let event_type_id = sqlx::query!(
-            r#"SELECT id, event_type_id as "event_type_id: EventTypeId" FROM journal_entry WHERE id = $1 FOR UPDATE"#,
-            id as JournalEntryId
-        )
-            .fetch_one(&mut *tx)
-            .await
-            .map(|record| record.event_type_id)?;
+        // Both are read from one snapshot, so that the total agrees with the entries.
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+        let entries = find_entries(&mut *tx, user_id, filter).await?;
+        let total = count_entries(&mut *tx, user_id, filter).await?;
+        tx.commit().await?;
+        Ok((entries, total))
+    }
 
-        if !self.references_valid_event_type(&mut tx, user_id, event_type_id, tags).await? {
-            return Err(AppError::EventTypeValidation);
+    async fn counts(
+        &self,
+        user_id: UserId,
+        filter: &SearchFilter,
+        counts: &CountsQuery,
+    ) -> Result<Vec<EntryCount>, AppError> {
+        let by_event_type = counts.group_by.contains(&CountGroup::EventType);
+        let by_tag = counts.group_by.contains(&CountGroup::Tag);
+
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new("SELECT ");
+        push_bucket(&mut query, user_id, counts.bucket, counts.timezone.as_deref());
+        query
+            .push(" AS bucket, ")
+            .push(if by_event_type { "event_type_id" } else { "NULL::uuid AS event_type_id" })
+            .push(if by_tag { ", tag" } else { ", NULL::text AS tag" })
+            .push(", count(*) AS count FROM journal_entry");
+        if by_tag {
+            query.push(" CROSS JOIN LATERAL unnest(tags) AS tag");
         }
+        query.push(" WHERE ");
+        push_filter_conditions(&mut query, user_id, filter)?;
+        query.push(" GROUP BY 1, 2, 3 ORDER BY 1, 2, 3");
+
+        let result = query.build_query_as::<EntryCount>().fetch_all(&self.pool).await?;
+        Ok(result)
+    }
 
+    async fn search(
+        &self,
+        user_id: UserId,
+        q: &str,
+        limit: u32,
+    ) -> Result<Vec<SearchResult>, AppError> {
+        let prefix = like_prefix(q);
+        let mut tx = self.pool.begin().await?;
+        set_word_similarity_threshold(&mut tx).await?;
         let result = sqlx::query!(
-            r#"UPDATE journal_entry SET description = $1, tags = $2 WHERE id = $3 AND user_id = $4"#,
-            description,
-            tags,
+            r#"SELECT id as "id: JournalEntryId", event_type_id as "event_type_id: EventTypeId",
+                    description, tags, occurred_at,
+                    GREATEST(word_similarity($2, coalesce(description, '')),
+                        (SELECT max(similarity($2, tag)) FROM unnest(tags) AS tag)) AS "score!"
+                FROM journal_entry
+                WHERE user_id = $1
+                    AND ($2 <% description OR description ILIKE $3 OR description ILIKE '% ' || $3
+                        OR EXISTS (SELECT FROM unnest(tags) AS tag WHERE $2 % tag OR tag ILIKE $3))
+                ORDER BY 6 DESC, occurred_at DESC LIMIT $4"#,
+            user_id as UserId,
+            q,
+            prefix,
+            i64::from(limit)
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|r| SearchResult::Entry {
+            id: r.id,
+            event_type_id: r.event_type_id,
+            description: r.description,
+            tags: r.tags,
+            occurred_at: r.occurred_at,
+            score: r.score,
+        })
+        .collect();
+
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn count_per_period(
+        &self,
+        user_id: UserId,
+        event_type_id: EventTypeId,
+        period: GoalPeriod,
+    ) -> Result<PeriodCounts, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let current_period = sqlx::query_scalar!(
+            r#"SELECT date_trunc($2, now() AT TIME ZONE COALESCE(
+                    (SELECT timezone FROM user_settings WHERE user_id = $1), 'UTC'))::date AS "period!""#,
+            user_id as UserId,
+            period.to_string()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let counts = sqlx::query!(
+            r#"SELECT date_trunc($3, occurred_at AT TIME ZONE tz.name)::date AS "period!",
+                    count(*) AS "count!"
+                FROM journal_entry,
+                    (SELECT COALESCE((SELECT timezone FROM user_settings WHERE user_id = $1), 'UTC')
+                        AS name) AS tz
+                WHERE user_id = $1 AND event_type_id = $2
+                GROUP BY 1 ORDER BY 1"#,
+            user_id as UserId,
+            event_type_id as EventTypeId,
+            period.to_string()
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|record| (record.period, record.count))
+        .collect();
+
+        tx.commit().await?;
+        Ok(PeriodCounts { current_period, counts })
+    }
+
+    async fn insert(
+        &self,
+        user_id: UserId,
+        entry: &NewJournalEntry,
+    ) -> Result<JournalEntryId, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let result = self.insert_entry(&mut tx, None, user_id, entry).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn update(
+        &self,
+        user_id: UserId,
+        id: JournalEntryId,
+        update: &JournalEntryUpdate,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let result = self.update_entry(&mut tx, user_id, id, update).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn patch(
+        &self,
+        user_id: UserId,
+        id: JournalEntryId,
+        patch: JournalEntryPatch,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_as!(
+            JournalEntry,
+            r#"SELECT id as "id: _", user_id as "user_id: _", event_type_id as "event_type_id: _",
+                description, tags, occurred_at, ended_at, duration_secs, running, recorded_at,
+                field_values as "values: _", NULL as snippet
+                FROM journal_entry WHERE id = $1 AND user_id = $2 FOR UPDATE"#,
             id as JournalEntryId,
             user_id as UserId
         )
-            .execute(&mut *tx)
-            .await
-            .map(|r| r.rows_affected() > 0)?;
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(current) = current else {
+            return Ok(false);
+        };
 
+        let result = self.update_entry(&mut tx, user_id, id, &patch.merge_into(current)).await?;
         tx.commit().await?;
         Ok(result)
     }
 
     async fn delete(&self, user_id: UserId, id: JournalEntryId) -> Result<bool, AppError> {
+        delete_entry(&self.pool, user_id, id).await
+    }
+
+    async fn import(
+        &self,
+        user_id: UserId,
+        entries: &[ImportEntry],
+        commit: bool,
+    ) -> Result<ImportReport, AppError> {
+        let mut event_type_tags: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for entry in entries {
+            let tags = event_type_tags.entry(&entry.event_type).or_default();
+            for tag in &entry.tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut report = ImportReport::default();
+        let mut event_type_ids = HashMap::new();
+        for (name, tags) in event_type_tags {
+            // xmax is zero for the inserted rows. Existing event types which already have all the
+            // tags are left untouched, so that they don't get a new sync version.
+            let record = sqlx::query!(
+                r#"INSERT INTO event_type (user_id, name, tags) VALUES ($1, $2, $3)
+                    ON CONFLICT (user_id, name) DO UPDATE SET tags = event_type.tags
+                        || array(SELECT unnest(EXCLUDED.tags) EXCEPT SELECT unnest(event_type.tags))
+                    WHERE NOT (EXCLUDED.tags <@ event_type.tags)
+                    RETURNING id as "id: EventTypeId", xmax = 0 as "inserted!""#,
+                user_id as UserId,
+                name,
+                &tags
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            let id = match record {
+                Some(record) => {
+                    if record.inserted {
+                        report.created_event_types.push(name.to_string());
+                    }
+                    record.id
+                }
+                None => {
+                    sqlx::query_scalar!(
+                        r#"SELECT id as "id: EventTypeId" FROM event_type
+                            WHERE user_id = $1 AND name = $2"#,
+                        user_id as UserId,
+                        name
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?
+                }
+            };
+            event_type_ids.insert(name, id);
+        }
+
+        for entry in entries {
+            let new_entry = NewJournalEntry {
+                event_type_id: event_type_ids[entry.event_type.as_str()],
+                description: entry.description.clone(),
+                tags: entry.tags.clone(),
+                occurred_at: Some(entry.occurred_at),
+                ended_at: entry.ended_at,
+                values: FieldValues::new(),
+            };
+            let mut savepoint = tx.begin().await?;
+            match self.insert_entry(&mut savepoint, None, user_id, &new_entry).await {
+                Ok(_) => {
+                    savepoint.commit().await?;
+                    report.imported += 1;
+                }
+                Err(error) => {
+                    savepoint.rollback().await?;
+                    report
+                        .errors
+                        .push(ImportError { line: entry.line, message: error.public_message() });
+                }
+            }
+        }
+
+        if commit && report.errors.is_empty() {
+            tx.commit().await?;
+            report.committed = true;
+        } else {
+            tx.rollback().await?;
+        }
+        Ok(report)
+    }
+
+    async fn batch(&self, user_id: UserId, batch: &EntryBatch) -> Result<BatchResult, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut result = BatchResult::default();
+        // Each operation runs in a savepoint, so that a failed one leaves the transaction usable.
+        for entry in &batch.create {
+            let mut savepoint = tx.begin().await?;
+            let outcome = self.insert_entry(&mut savepoint, None, user_id, entry).await;
+            result.create.push(finish_batch_item(savepoint, None, outcome).await?);
+        }
+        for item in &batch.update {
+            let mut savepoint = tx.begin().await?;
+            let outcome = self
+                .update_entry(&mut savepoint, user_id, item.id, &item.update)
+                .await
+                .and_then(|updated| updated.then_some(item.id).ok_or(AppError::NotFound));
+            result.update.push(finish_batch_item(savepoint, Some(item.id), outcome).await?);
+        }
+        for &id in &batch.delete {
+            let mut savepoint = tx.begin().await?;
+            let outcome = delete_entry(&mut *savepoint, user_id, id)
+                .await
+                .and_then(|deleted| deleted.then_some(id).ok_or(AppError::NotFound));
+            result.delete.push(finish_batch_item(savepoint, Some(id), outcome).await?);
+        }
+
+        if batch.mode == BatchMode::Atomic && result.has_failures() {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+            result.committed = true;
+        }
+        Ok(result)
+    }
+
+    async fn find_changes(
+        &self,
+        user_id: UserId,
+        cursor: ChangeCursor,
+        limit: u32,
+    ) -> Result<SyncChanges, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // All tables are read from one snapshot, so that the returned cursor can't skip a change
+        // committed in between.
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+
+        // Each table is read up to one more than the limit, to know if there are more changes.
+        let fetch_limit = i64::from(limit) + 1;
+        let mut event_types = sqlx::query_as::<_, Versioned<EventType>>(
+            r#"SELECT id, user_id, name, tags, fields, version FROM event_type
+                WHERE user_id = $1 AND version > $2 ORDER BY version LIMIT $3"#,
+        )
+        .bind(user_id)
+        .bind(cursor.0)
+        .bind(fetch_limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut entries = sqlx::query_as::<_, Versioned<JournalEntry>>(
+            r#"SELECT id, user_id, event_type_id, description, tags, occurred_at, ended_at,
+                    duration_secs, running, recorded_at, field_values, version
+                FROM journal_entry WHERE user_id = $1 AND version > $2 ORDER BY version LIMIT $3"#,
+        )
+        .bind(user_id)
+        .bind(cursor.0)
+        .bind(fetch_limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut deleted = sqlx::query_as!(
+            Tombstone,
+            r#"SELECT kind as "kind: SyncKind", id, version FROM sync_tombstone
+                WHERE user_id = $1 AND version > $2 ORDER BY version LIMIT $3"#,
+            user_id as UserId,
+            cursor.0,
+            fetch_limit
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let mut versions: Vec<i64> = event_types
+            .iter()
+            .map(|e| e.version)
+            .chain(entries.iter().map(|e| e.version))
+            .chain(deleted.iter().map(|t| t.version))
+            .collect();
+        versions.sort_unstable();
+        let Some(&last) = versions.iter().take(limit as usize).next_back() else {
+            return Ok(SyncChanges { cursor, ..Default::default() });
+        };
+        event_types.retain(|e| e.version <= last);
+        entries.retain(|e| e.version <= last);
+        deleted.retain(|t| t.version <= last);
+
+        Ok(SyncChanges {
+            event_types,
+            entries,
+            deleted,
+            cursor: ChangeCursor(last),
+            has_more: versions.len() > limit as usize,
+        })
+    }
+
+    async fn push_changes(
+        &self,
+        user_id: UserId,
+        changes: &[SyncChange],
+    ) -> Result<Vec<SyncChangeResult>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(changes.len());
+        // Each change runs in a savepoint, so that a rejected one leaves the transaction usable.
+        for change in changes {
+            let mut savepoint = tx.begin().await?;
+            match self.apply_change(&mut savepoint, user_id, change).await {
+                Ok(result) => {
+                    savepoint.commit().await?;
+                    results.push(result);
+                }
+                Err(error) => {
+                    savepoint.rollback().await?;
+                    // The client gets only a generic message of internal errors.
+                    if error.status_code().is_server_error() {
+                        error!("Failed to apply sync change: {error}");
+                    }
+                    results.push(SyncChangeResult::rejected(&error));
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn start_timer(
+        &self,
+        user_id: UserId,
+        timer: &NewTimer,
+    ) -> Result<JournalEntryId, AppError> {
+        let mut tx = self.pool.begin().await?;
+        if !self
+            .references_valid_event_type(
+                &mut tx,
+                user_id,
+                timer.event_type_id,
+                &timer.tags,
+                &timer.values,
+            )
+            .await?
+        {
+            return Err(AppError::EventTypeValidation);
+        }
+
+        let result = sqlx::query!(
+            r#"INSERT INTO journal_entry (user_id, event_type_id, description, tags, field_values, running, search_language)
+                VALUES ($1, $2, $3, $4, $5, true,
+                    COALESCE((SELECT search_language FROM user_settings WHERE user_id = $1), 'simple'))
+                RETURNING id as "id: JournalEntryId""#,
+            user_id as UserId,
+            timer.event_type_id as EventTypeId,
+            timer.description,
+            &timer.tags,
+            Json(&timer.values) as _
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map(|record| record.id)?;
+
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn stop_timer(&self, user_id: UserId, id: JournalEntryId) -> Result<bool, AppError> {
         let result = sqlx::query!(
-            r#"DELETE FROM journal_entry WHERE id = $1 and user_id = $2"#,
+            r#"UPDATE journal_entry SET ended_at = now(), running = false
+                WHERE id = $1 AND user_id = $2 AND running AND occurred_at <= now()"#,
             id as JournalEntryId,
             user_id as UserId
         )
@@ -360,4 +1248,645 @@ impl JournalEntryRepository for PgJournalEntryRepository {
 
         Ok(result)
     }
+
+    async fn find_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<Option<SavedSearch>, AppError> {
+        let result = sqlx::query_as!(
+            SavedSearch,
+            r#"SELECT id as "id: _", user_id as "user_id: _", name, filter as "filter: _"
+                FROM saved_search WHERE id = $1 AND user_id = $2"#,
+            id as SavedSearchId,
+            user_id as UserId
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_saved_searches(&self, user_id: UserId) -> Result<Vec<SavedSearch>, AppError> {
+        let result = sqlx::query_as!(
+            SavedSearch,
+            r#"SELECT id as "id: _", user_id as "user_id: _", name, filter as "filter: _"
+                FROM saved_search WHERE user_id = $1 ORDER BY name"#,
+            user_id as UserId
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn insert_saved_search(
+        &self,
+        user_id: UserId,
+        search: &SavedSearchData,
+    ) -> Result<SavedSearchId, AppError> {
+        let result = sqlx::query!(
+            r#"INSERT INTO saved_search (user_id, name, filter) VALUES ($1, $2, $3)
+                RETURNING id as "id: SavedSearchId""#,
+            user_id as UserId,
+            search.name,
+            Json(&search.filter) as _
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map(|record| record.id)?;
+
+        Ok(result)
+    }
+
+    async fn update_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        search: &SavedSearchData,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"UPDATE saved_search SET name = $3, filter = $4 WHERE id = $1 AND user_id = $2"#,
+            id as SavedSearchId,
+            user_id as UserId,
+            search.name,
+            Json(&search.filter) as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|r| r.rows_affected() > 0)?;
+
+        Ok(result)
+    }
+
+    async fn delete_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"DELETE FROM saved_search WHERE id = $1 AND user_id = $2"#,
+            id as SavedSearchId,
+            user_id as UserId
+        )
+        .execute(&self.pool)
+        .await
+        .map(|r| r.rows_affected() > 0)?;
+
+        Ok(result)
+    }
+
+    async fn update_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        token_hash: &[u8],
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"UPDATE saved_search SET feed_token_hash = $3 WHERE id = $1 AND user_id = $2"#,
+            id as SavedSearchId,
+            user_id as UserId,
+            token_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map(|r| r.rows_affected() > 0)?;
+
+        Ok(result)
+    }
+
+    async fn delete_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"UPDATE saved_search SET feed_token_hash = NULL
+                WHERE id = $1 AND user_id = $2 AND feed_token_hash IS NOT NULL"#,
+            id as SavedSearchId,
+            user_id as UserId
+        )
+        .execute(&self.pool)
+        .await
+        .map(|r| r.rows_affected() > 0)?;
+
+        Ok(result)
+    }
+
+    async fn find_saved_search_by_feed_token(
+        &self,
+        token_hash: &[u8],
+    ) -> Result<Option<SavedSearch>, AppError> {
+        let result = sqlx::query_as!(
+            SavedSearch,
+            r#"SELECT id as "id: _", user_id as "user_id: _", name, filter as "filter: _"
+                FROM saved_search WHERE feed_token_hash = $1"#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn upsert_calendar_token(
+        &self,
+        user_id: UserId,
+        token_hash: &[u8],
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"INSERT INTO calendar_feed (user_id, token_hash) VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE SET token_hash = EXCLUDED.token_hash,
+                    created_at = now()"#,
+            user_id as UserId,
+            token_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_calendar_token(&self, user_id: UserId) -> Result<bool, AppError> {
+        let result =
+            sqlx::query!(r#"DELETE FROM calendar_feed WHERE user_id = $1"#, user_id as UserId)
+                .execute(&self.pool)
+                .await
+                .map(|r| r.rows_affected() > 0)?;
+
+        Ok(result)
+    }
+
+    async fn find_calendar_user(&self, token_hash: &[u8]) -> Result<Option<UserId>, AppError> {
+        let result = sqlx::query_scalar!(
+            r#"SELECT user_id as "user_id: UserId" FROM calendar_feed WHERE token_hash = $1"#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
+
+/// Lowers the threshold of the `<%` operator for the transaction, since the default one (0.6)
+/// doesn't match common typos like swapped letters.
+async fn set_word_similarity_threshold(tx: &mut Transaction<'_, Postgres>) -> Result<(), AppError> {
+    sqlx::query!("SELECT set_config('pg_trgm.word_similarity_threshold', '0.4', true)")
+        .fetch_one(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Returns a `LIKE` pattern matching texts starting with the query.
+fn like_prefix(q: &str) -> String {
+    let escaped = q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("{escaped}%")
+}
+
+/// Pushes the start of the time bucket of `occurred_at`, in local time of the given time zone
+/// or the user's time zone if there is none.
+fn push_bucket<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    user_id: UserId,
+    bucket: TimeBucket,
+    timezone: Option<&'a str>,
+) {
+    query.push("date_trunc('").push(bucket).push("', occurred_at AT TIME ZONE COALESCE(");
+    query.push_bind(timezone).push(", ");
+    push_user_timezone(query, user_id);
+    query.push("))");
+}
+
+/// Pushes the time zone of the user, falling back to UTC if the user has no settings.
+fn push_user_timezone(query: &mut QueryBuilder<'_, Postgres>, user_id: UserId) {
+    query
+        .push("COALESCE((SELECT timezone FROM user_settings WHERE user_id = ")
+        .push_bind(user_id)
+        .push("), 'UTC')");
+}
+
+/// Pushes the instant of the local midnight `days_after` the given day in the user's time zone.
+/// Conversion is done by Postgres, so days around DST transitions are 23 or 25 hours long.
+fn push_local_midnight(
+    query: &mut QueryBuilder<'_, Postgres>,
+    user_id: UserId,
+    day: NaiveDate,
+    days_after: i32,
+) {
+    query.push("((").push_bind(day).push("::date + ").push_bind(days_after);
+    query.push(")::timestamp AT TIME ZONE ");
+    push_user_timezone(query, user_id);
+    query.push(")");
+}
+
+async fn insert_event_type(
+    executor: impl PgExecutor<'_>,
+    id: Option<EventTypeId>,
+    user_id: UserId,
+    name: &str,
+    tags: &[String],
+    fields: &[FieldDefinition],
+) -> Result<EventTypeId, AppError> {
+    let result = sqlx::query!(
+        r#"INSERT INTO event_type (id, user_id, name, tags, fields)
+            VALUES (COALESCE($1, gen_random_uuid()), $2, $3, $4, $5)
+            RETURNING id as "id: EventTypeId""#,
+        id as Option<EventTypeId>,
+        user_id as UserId,
+        name,
+        tags,
+        Json(fields) as _
+    )
+    .fetch_one(executor)
+    .await
+    .map(|record| record.id)?;
+
+    Ok(result)
+}
+
+async fn update_event_type(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: UserId,
+    id: EventTypeId,
+    name: &str,
+    tags: &[String],
+    fields: &[FieldDefinition],
+) -> Result<bool, AppError> {
+    // Check if the given event type belongs to the user and lock it for update.
+    let current_fields = sqlx::query_scalar!(
+        r#"SELECT fields as "fields: Json<Vec<FieldDefinition>>"
+            FROM event_type where id = $1 AND user_id = $2 FOR UPDATE"#,
+        id as EventTypeId,
+        user_id as UserId
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let missing_used_tags = sqlx::query!(
+        r#"
+        SELECT array(SELECT tag_row
+                     FROM (SELECT DISTINCT unnest(tags) as tag_row
+                           FROM journal_entry
+                           WHERE event_type_id = $1) as event_tags
+                     WHERE event_tags.tag_row != ALL ($2)) as used_tags"#,
+        id as EventTypeId,
+        tags
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map(|r| r.used_tags.unwrap_or_default())?;
+
+    if !missing_used_tags.is_empty() {
+        return Err(AppError::TagsStillUsed(missing_used_tags));
+    }
+
+    // Entry writes validate their values while holding the event type lock as well, so no entry
+    // can slip in between the check and the update.
+    if current_fields.0 != fields {
+        let used_values = sqlx::query_scalar!(
+            r#"SELECT DISTINCT field_values as "values: Json<FieldValues>"
+                FROM journal_entry WHERE event_type_id = $1"#,
+            id as EventTypeId
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        let invalid_fields: BTreeSet<String> = used_values
+            .iter()
+            .filter_map(|values| validate_field_values(fields, values).err())
+            .flatten()
+            .map(|field| field.0)
+            .collect();
+        if !invalid_fields.is_empty() {
+            return Err(AppError::FieldsStillUsed(invalid_fields.into_iter().collect()));
+        }
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE event_type SET name = $1, tags = $2, fields = $3 WHERE id = $4"#,
+        name,
+        tags,
+        Json(fields) as _,
+        id as EventTypeId
+    )
+    .execute(&mut **tx)
+    .await
+    .map(|r| r.rows_affected() > 0)?;
+
+    Ok(result)
+}
+
+async fn delete_event_type(
+    executor: impl PgExecutor<'_>,
+    user_id: UserId,
+    id: EventTypeId,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"DELETE FROM event_type WHERE id = $1 and user_id = $2"#,
+        id as EventTypeId,
+        user_id as UserId
+    )
+    .execute(executor)
+    .await
+    .map(|r| r.rows_affected() > 0)?;
+
+    Ok(result)
+}
+
+async fn delete_entry(
+    executor: impl PgExecutor<'_>,
+    user_id: UserId,
+    id: JournalEntryId,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"DELETE FROM journal_entry WHERE id = $1 and user_id = $2"#,
+        id as JournalEntryId,
+        user_id as UserId
+    )
+    .execute(executor)
+    .await
+    .map(|r| r.rows_affected() > 0)?;
+
+    Ok(result)
+}
+
+/// Returns the version of the user's event type or entry, locking the row for a change.
+async fn lock_row_version(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: UserId,
+    kind: SyncKind,
+    id: Uuid,
+) -> Result<Option<i64>, AppError> {
+    let version =
+        match kind {
+            SyncKind::EventType => {
+                sqlx::query_scalar!(
+                    r#"SELECT version FROM event_type WHERE id = $1 AND user_id = $2 FOR UPDATE"#,
+                    id,
+                    user_id as UserId
+                )
+                .fetch_optional(&mut **tx)
+                .await?
+            }
+            SyncKind::Entry => sqlx::query_scalar!(
+                r#"SELECT version FROM journal_entry WHERE id = $1 AND user_id = $2 FOR UPDATE"#,
+                id,
+                user_id as UserId
+            )
+            .fetch_optional(&mut **tx)
+            .await?,
+        };
+    Ok(version)
+}
+
+async fn has_tombstone(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: UserId,
+    kind: SyncKind,
+    id: Uuid,
+) -> Result<bool, AppError> {
+    let result = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM sync_tombstone WHERE kind = $1 AND id = $2 AND user_id = $3) as "exists!""#,
+        kind as SyncKind,
+        id,
+        user_id as UserId
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(result)
+}
+
+/// Fetches the next batch of the exported entries from the cursor, committing the transaction
+/// after the last one.
+async fn fetch_export_batch(
+    tx: Option<Transaction<'static, Postgres>>,
+) -> Result<Option<(Vec<ExportEntry>, Option<Transaction<'static, Postgres>>)>, AppError> {
+    let Some(mut tx) = tx else {
+        return Ok(None);
+    };
+    let fetch = format!("FETCH {EXPORT_BATCH_SIZE} FROM export_cursor");
+    let entries = sqlx::query_as::<_, ExportEntry>(&fetch).fetch_all(&mut *tx).await?;
+    if entries.len() < EXPORT_BATCH_SIZE {
+        tx.commit().await?;
+        return Ok(Some((entries, None)));
+    }
+    Ok(Some((entries, Some(tx))))
+}
+
+/// Releases the savepoint of a successful batch operation, or rolls back the failed one.
+async fn finish_batch_item(
+    savepoint: Transaction<'_, Postgres>,
+    id: Option<JournalEntryId>,
+    outcome: Result<JournalEntryId, AppError>,
+) -> Result<BatchItemResult, AppError> {
+    match outcome {
+        Ok(id) => {
+            savepoint.commit().await?;
+            Ok(BatchItemResult::succeeded(id))
+        }
+        Err(error) => {
+            savepoint.rollback().await?;
+            Ok(BatchItemResult::failed(id, &error))
+        }
+    }
+}
+
+/// Finds the journal entries matching the filter, in its sort order or by relevance to `q`.
+async fn find_entries(
+    executor: impl PgExecutor<'_>,
+    user_id: UserId,
+    filter: &SearchFilter,
+) -> Result<Vec<JournalEntry>, AppError> {
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::default();
+    push_entry_select(&mut query, user_id, filter)?;
+
+    match (&filter.sort, &filter.q) {
+        (Some(sort), _) => {
+            query.push(" ORDER BY occurred_at ").push(sort).push(", id ").push(sort);
+        }
+        (None, Some(q)) => {
+            query
+                .push(" ORDER BY ts_rank(description_tsv, websearch_to_tsquery(search_language, ")
+                .push_bind(q)
+                .push(")) DESC, id");
+        }
+        (None, None) => {
+            query.push(" ORDER BY occurred_at, id");
+        }
+    };
+    if let Some(offset) = filter.offset {
+        query.push(" OFFSET ").push(offset);
+    };
+    if let Some(limit) = filter.limit {
+        query.push(" LIMIT ").push(limit);
+    };
+
+    let result = query.build_query_as::<JournalEntry>().fetch_all(executor).await?;
+    Ok(result)
+}
+
+/// Counts all journal entries matching the filter, ignoring its offset and limit.
+async fn count_entries(
+    executor: impl PgExecutor<'_>,
+    user_id: UserId,
+    filter: &SearchFilter,
+) -> Result<i64, AppError> {
+    let mut query: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT count(*) FROM journal_entry WHERE ");
+    push_filter_conditions(&mut query, user_id, filter)?;
+
+    let result = query.build_query_scalar().fetch_one(executor).await?;
+    Ok(result)
+}
+
+/// Pushes the selection of the journal entries matching the filter, with a snippet of the
+/// description when searching by `q`.
+fn push_entry_select<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    user_id: UserId,
+    filter: &'a SearchFilter,
+) -> Result<(), AppError> {
+    query.push(
+        r#"SELECT id, user_id, event_type_id, description, tags, occurred_at, ended_at,
+            duration_secs, running, recorded_at, field_values"#,
+    );
+    if let Some(q) = &filter.q {
+        query
+            .push(", ts_headline(search_language, coalesce(description, ''), ")
+            .push("websearch_to_tsquery(search_language, ")
+            .push_bind(q)
+            .push(")) AS snippet");
+    };
+    query.push(" FROM journal_entry WHERE ");
+    push_filter_conditions(query, user_id, filter)
+}
+
+/// Pushes the `WHERE` conditions of the search filter, restricted to the user's journal entries.
+/// Sorting and paging of the filter are left to the caller.
+fn push_filter_conditions<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    user_id: UserId,
+    filter: &'a SearchFilter,
+) -> Result<(), AppError> {
+    query.push("user_id = ").push_bind(user_id);
+
+    if let Some(q) = &filter.q {
+        query
+            .push(" AND description_tsv @@ websearch_to_tsquery(search_language, ")
+            .push_bind(q)
+            .push(")");
+    };
+    if let Some(id) = &filter.event_type_id {
+        query.push(" AND event_type_id = ").push_bind(id);
+    };
+    if !filter.tags_all.is_empty() {
+        query.push(" AND tags @> ").push_bind(&filter.tags_all);
+    };
+    if !filter.tags_any.is_empty() {
+        query.push(" AND tags && ").push_bind(&filter.tags_any);
+    };
+    if !filter.tags_none.is_empty() {
+        query.push(" AND NOT tags && ").push_bind(&filter.tags_none);
+    };
+    if let Some(before) = &filter.before {
+        query.push(" AND occurred_at <= ").push_bind(before);
+    };
+    if let Some(after) = &filter.after {
+        query.push(" AND occurred_at >= ").push_bind(after);
+    };
+    if let Some(overlaps_to) = &filter.overlaps_to {
+        query.push(" AND occurred_at <= ").push_bind(overlaps_to);
+    };
+    if let Some(overlaps_from) = &filter.overlaps_from {
+        // Running entries are open-ended, point-in-time entries end when they occurred.
+        query
+            .push(
+                " AND COALESCE(ended_at, CASE WHEN running THEN 'infinity'::timestamptz \
+                    ELSE occurred_at END) >= ",
+            )
+            .push_bind(overlaps_from);
+    };
+    if let Some(on) = filter.on {
+        query.push(" AND occurred_at >= ");
+        push_local_midnight(query, user_id, on, 0);
+        query.push(" AND occurred_at < ");
+        push_local_midnight(query, user_id, on, 1);
+    };
+    if let Some(from_date) = filter.from_date {
+        query.push(" AND occurred_at >= ");
+        push_local_midnight(query, user_id, from_date, 0);
+    };
+    if let Some(to_date) = filter.to_date {
+        query.push(" AND occurred_at < ");
+        push_local_midnight(query, user_id, to_date, 1);
+    };
+    if let Some(search_query) = &filter.query {
+        push_query_conditions(query, user_id, query::parse(search_query)?);
+    };
+    Ok(())
+}
+
+/// Pushes the terms of the parsed search query as `AND` conditions. All values are bound as
+/// parameters, so the query cannot inject any SQL.
+fn push_query_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    user_id: UserId,
+    terms: Vec<Term>,
+) {
+    for Term { negated, condition } in terms {
+        query.push(if negated { " AND NOT " } else { " AND " });
+        match condition {
+            Condition::EventType(name) => {
+                query
+                    .push("event_type_id IN (SELECT id FROM event_type WHERE user_id = ")
+                    .push_bind(user_id)
+                    .push(" AND lower(name) = lower(")
+                    .push_bind(name)
+                    .push("))");
+            }
+            Condition::Tag(tag) => {
+                query.push("tags @> ARRAY[").push_bind(tag).push("]");
+            }
+            Condition::After(TimeBound::Date(date)) => {
+                query.push("occurred_at >= ");
+                push_local_midnight(query, user_id, date, 0);
+            }
+            Condition::After(TimeBound::Instant(instant)) => {
+                query.push("occurred_at >= ").push_bind(instant);
+            }
+            Condition::Before(TimeBound::Date(date)) => {
+                query.push("occurred_at < ");
+                push_local_midnight(query, user_id, date, 0);
+            }
+            Condition::Before(TimeBound::Instant(instant)) => {
+                query.push("occurred_at <= ").push_bind(instant);
+            }
+            Condition::On(date) => {
+                query.push("(occurred_at >= ");
+                push_local_midnight(query, user_id, date, 0);
+                query.push(" AND occurred_at < ");
+                push_local_midnight(query, user_id, date, 1);
+                query.push(")");
+            }
+            Condition::Text(text) => {
+                query
+                    .push("description_tsv @@ phraseto_tsquery(search_language, ")
+                    .push_bind(text)
+                    .push(")");
+            }
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StatsRow {
+    bucket: NaiveDateTime,
+    count: i64,
+    sum: f64,
+    avg: f64,
+    min: f64,
+    max: f64,
+    percentiles: Vec<f64>,
 }
@@ -1,8 +1,24 @@
+use crate::journal::export::{self, ExportStream};
+use crate::journal::import;
 use crate::journal::model::*;
 use crate::journal::repository::{EventTypeRepository, JournalEntryRepository};
-use crate::model::AppError;
+use crate::model::{AppError, InvalidField};
 use crate::user::model::UserId;
 use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use validator::Validate;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// Number of random bytes of the feed tokens.
+const FEED_TOKEN_SIZE: usize = 32;
+/// Number of the most recent entries in the saved search feed.
+const SEARCH_FEED_SIZE: u32 = 50;
+/// Number of the most recent entries in the calendar feed.
+const CALENDAR_FEED_SIZE: u32 = 1000;
 
 #[async_trait]
 pub trait JournalService {
@@ -27,8 +43,28 @@ pub trait JournalService {
         event_type: EventTypeData,
     ) -> Result<(), AppError>;
 
+    async fn patch_event_type(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+        patch: EventTypePatch,
+    ) -> Result<(), AppError>;
+
     async fn delete_event_type(&self, user_id: UserId, id: EventTypeId) -> Result<(), AppError>;
 
+    async fn find_goal(&self, user_id: UserId, id: EventTypeId) -> Result<Goal, AppError>;
+
+    async fn set_goal(&self, user_id: UserId, id: EventTypeId, goal: Goal) -> Result<(), AppError>;
+
+    async fn delete_goal(&self, user_id: UserId, id: EventTypeId) -> Result<(), AppError>;
+
+    /// Reports streaks and completion of the event type's goal, calculated from its entries.
+    async fn find_goal_progress(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+    ) -> Result<GoalProgress, AppError>;
+
     async fn find_journal_entry_by_id(
         &self,
         user_id: UserId,
@@ -41,6 +77,51 @@ pub trait JournalService {
         filter: SearchFilter,
     ) -> Result<Vec<JournalEntry>, AppError>;
 
+    /// Exports the journal entries matching the filter, see [`crate::journal::export`].
+    async fn export_journal_entries(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        format: ExportFormat,
+    ) -> Result<ExportStream, AppError>;
+
+    /// Finds the journal entries together with the number of all entries matching the filter.
+    async fn find_journal_entries_page_with_total(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+    ) -> Result<OffsetPage<JournalEntry>, AppError>;
+
+    /// Finds the page of journal entries following the cursor, with the filter's `limit` as the
+    /// page size.
+    async fn find_journal_entries_page(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        cursor: Cursor,
+    ) -> Result<CursorPage<JournalEntry>, AppError>;
+
+    async fn find_entry_stats(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        stats: StatsQuery,
+    ) -> Result<Vec<FieldStats>, AppError>;
+
+    /// Searches entries and event types by similarity, returning the best matches of both.
+    async fn search(
+        &self,
+        user_id: UserId,
+        query: FuzzySearchQuery,
+    ) -> Result<Vec<SearchResult>, AppError>;
+
+    async fn count_journal_entries(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        counts: CountsQuery,
+    ) -> Result<Vec<EntryCount>, AppError>;
+
     async fn insert_journal_entry(
         &self,
         user_id: UserId,
@@ -54,11 +135,115 @@ pub trait JournalService {
         entry: JournalEntryUpdate,
     ) -> Result<(), AppError>;
 
+    async fn patch_journal_entry(
+        &self,
+        user_id: UserId,
+        id: JournalEntryId,
+        patch: JournalEntryPatch,
+    ) -> Result<(), AppError>;
+
     async fn delete_journal_entry(
         &self,
         user_id: UserId,
         id: JournalEntryId,
     ) -> Result<(), AppError>;
+
+    async fn execute_entry_batch(
+        &self,
+        user_id: UserId,
+        batch: EntryBatch,
+    ) -> Result<BatchResult, AppError>;
+
+    /// Finds the changes of the event types and entries since the cursor of the query.
+    async fn find_changes(
+        &self,
+        user_id: UserId,
+        query: SyncQuery,
+    ) -> Result<SyncChanges, AppError>;
+
+    /// Applies the changes made by a client while offline, see [`SyncPush`].
+    async fn push_changes(
+        &self,
+        user_id: UserId,
+        push: SyncPush,
+    ) -> Result<SyncPushResult, AppError>;
+
+    /// Imports the journal entries from CSV or iCalendar, see [`crate::journal::import`].
+    async fn import_entries(
+        &self,
+        user_id: UserId,
+        data: &[u8],
+        options: ImportOptions,
+    ) -> Result<ImportReport, AppError>;
+
+    async fn start_timer(
+        &self,
+        user_id: UserId,
+        timer: NewTimer,
+    ) -> Result<JournalEntryId, AppError>;
+
+    async fn stop_timer(&self, user_id: UserId, id: JournalEntryId) -> Result<(), AppError>;
+
+    /// Creates a new secret token of the user's calendar feed, revoking the previous one.
+    async fn create_calendar_token(&self, user_id: UserId) -> Result<FeedToken, AppError>;
+
+    async fn delete_calendar_token(&self, user_id: UserId) -> Result<(), AppError>;
+
+    /// Exports the most recent entries of the user with the calendar feed token as iCalendar.
+    async fn export_calendar(
+        &self,
+        token: &str,
+        query: CalendarQuery,
+    ) -> Result<ExportStream, AppError>;
+
+    async fn find_saved_searches(&self, user_id: UserId) -> Result<Vec<SavedSearch>, AppError>;
+
+    async fn find_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<SavedSearch, AppError>;
+
+    async fn insert_saved_search(
+        &self,
+        user_id: UserId,
+        search: SavedSearchData,
+    ) -> Result<SavedSearchId, AppError>;
+
+    async fn update_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        search: SavedSearchData,
+    ) -> Result<(), AppError>;
+
+    async fn delete_saved_search(&self, user_id: UserId, id: SavedSearchId)
+    -> Result<(), AppError>;
+
+    /// Finds the journal entries matching the saved filter, with its criteria replaced by the
+    /// ones set in `overrides`.
+    async fn execute_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        overrides: SearchFilter,
+    ) -> Result<Vec<JournalEntry>, AppError>;
+
+    /// Creates a new secret token of the saved search's feed, revoking the previous one.
+    async fn create_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<FeedToken, AppError>;
+
+    async fn delete_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<(), AppError>;
+
+    /// Finds the most recent entries of the saved search with the feed token.
+    async fn find_saved_search_feed(&self, token: &str) -> Result<SearchFeed, AppError>;
 }
 
 pub struct JournalServiceImpl<E: EventTypeRepository, J: JournalEntryRepository> {
@@ -95,8 +280,10 @@ where
         user_id: UserId,
         event_type: EventTypeData,
     ) -> Result<EventTypeId, AppError> {
-        let inserted_id =
-            self.event_repository.insert(user_id, &event_type.name, &event_type.tags).await?;
+        let inserted_id = self
+            .event_repository
+            .insert(user_id, &event_type.name, &event_type.tags, &event_type.fields)
+            .await?;
         Ok(inserted_id)
     }
 
@@ -107,7 +294,20 @@ where
         event_type: EventTypeData,
     ) -> Result<(), AppError> {
         self.event_repository
-            .update(user_id, id, &event_type.name, &event_type.tags)
+            .update(user_id, id, &event_type.name, &event_type.tags, &event_type.fields)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn patch_event_type(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+        patch: EventTypePatch,
+    ) -> Result<(), AppError> {
+        self.event_repository
+            .patch(user_id, id, patch)
             .await?
             .then_some(())
             .ok_or(AppError::NotFound)
@@ -117,6 +317,36 @@ where
         self.event_repository.delete(user_id, id).await?.then_some(()).ok_or(AppError::NotFound)
     }
 
+    async fn find_goal(&self, user_id: UserId, id: EventTypeId) -> Result<Goal, AppError> {
+        self.event_repository.find_goal(user_id, id).await?.ok_or(AppError::NotFound)
+    }
+
+    async fn set_goal(&self, user_id: UserId, id: EventTypeId, goal: Goal) -> Result<(), AppError> {
+        self.event_repository
+            .upsert_goal(user_id, id, &goal)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn delete_goal(&self, user_id: UserId, id: EventTypeId) -> Result<(), AppError> {
+        self.event_repository
+            .delete_goal(user_id, id)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn find_goal_progress(
+        &self,
+        user_id: UserId,
+        id: EventTypeId,
+    ) -> Result<GoalProgress, AppError> {
+        let goal = self.find_goal(user_id, id).await?;
+        let counts = self.journal_repository.count_per_period(user_id, id, goal.period).await?;
+        Ok(GoalProgress::calculate(goal, &counts))
+    }
+
     async fn find_journal_entry_by_id(
         &self,
         user_id: UserId,
@@ -133,22 +363,88 @@ where
         Ok(self.journal_repository.find(user_id, &filter).await?)
     }
 
-    async fn insert_journal_entry(
+    async fn export_journal_entries(
         &self,
         user_id: UserId,
-        entry: NewJournalEntry,
-    ) -> Result<JournalEntryId, AppError> {
-        let entry_id = self
+        filter: SearchFilter,
+        format: ExportFormat,
+    ) -> Result<ExportStream, AppError> {
+        let entries = self.journal_repository.export(user_id, &filter).await?;
+        Ok(export::encode(format, entries))
+    }
+
+    async fn find_journal_entries_page_with_total(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+    ) -> Result<OffsetPage<JournalEntry>, AppError> {
+        let (items, total) = self.journal_repository.find_with_total(user_id, &filter).await?;
+        let offset = filter.offset.unwrap_or(0);
+        let has_more = i64::from(offset) + (items.len() as i64) < total;
+        Ok(OffsetPage { items, total, has_more, offset, limit: filter.limit })
+    }
+
+    async fn find_journal_entries_page(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        cursor: Cursor,
+    ) -> Result<CursorPage<JournalEntry>, AppError> {
+        if filter.offset.is_some() {
+            return Err(AppError::Validation(vec![InvalidField("cursor, offset".to_string())]));
+        }
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+        // One more entry is fetched to find out whether there is a next page.
+        let mut items = self
             .journal_repository
-            .insert(
-                user_id,
-                entry.event_type_id,
-                entry.description.as_deref(),
-                &entry.tags,
-                entry.created_at,
-            )
+            .find_page(user_id, &filter, cursor, limit.saturating_add(1))
             .await?;
+        let next_cursor = if items.len() > limit as usize {
+            items.truncate(limit as usize);
+            items.last().map(Cursor::after)
+        } else {
+            None
+        };
+        Ok(CursorPage { items, next_cursor })
+    }
+
+    async fn find_entry_stats(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        stats: StatsQuery,
+    ) -> Result<Vec<FieldStats>, AppError> {
+        Ok(self.journal_repository.stats(user_id, &filter, &stats).await?)
+    }
 
+    async fn search(
+        &self,
+        user_id: UserId,
+        query: FuzzySearchQuery,
+    ) -> Result<Vec<SearchResult>, AppError> {
+        let mut results = self.event_repository.search(user_id, &query.q, query.limit).await?;
+        results.extend(self.journal_repository.search(user_id, &query.q, query.limit).await?);
+        // Stable sort keeps the repository ordering for equal scores.
+        results.sort_by(|a, b| b.score().total_cmp(&a.score()));
+        results.truncate(query.limit as usize);
+        Ok(results)
+    }
+
+    async fn count_journal_entries(
+        &self,
+        user_id: UserId,
+        filter: SearchFilter,
+        counts: CountsQuery,
+    ) -> Result<Vec<EntryCount>, AppError> {
+        Ok(self.journal_repository.counts(user_id, &filter, &counts).await?)
+    }
+
+    async fn insert_journal_entry(
+        &self,
+        user_id: UserId,
+        entry: NewJournalEntry,
+    ) -> Result<JournalEntryId, AppError> {
+        let entry_id = self.journal_repository.insert(user_id, &entry).await?;
         Ok(entry_id)
     }
 
@@ -159,7 +455,20 @@ where
         update: JournalEntryUpdate,
     ) -> Result<(), AppError> {
         self.journal_repository
-            .update(user_id, id, update.description.as_deref(), &update.tags)
+            .update(user_id, id, &update)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn patch_journal_entry(
+        &self,
+        user_id: UserId,
+        id: JournalEntryId,
+        patch: JournalEntryPatch,
+    ) -> Result<(), AppError> {
+        self.journal_repository
+            .patch(user_id, id, patch)
             .await?
             .then_some(())
             .ok_or(AppError::NotFound)
@@ -172,32 +481,263 @@ where
     ) -> Result<(), AppError> {
         self.journal_repository.delete(user_id, id).await?.then_some(()).ok_or(AppError::NotFound)
     }
+
+    async fn execute_entry_batch(
+        &self,
+        user_id: UserId,
+        batch: EntryBatch,
+    ) -> Result<BatchResult, AppError> {
+        Ok(self.journal_repository.batch(user_id, &batch).await?)
+    }
+
+    async fn find_changes(
+        &self,
+        user_id: UserId,
+        query: SyncQuery,
+    ) -> Result<SyncChanges, AppError> {
+        self.journal_repository.find_changes(user_id, query.cursor, query.limit).await
+    }
+
+    async fn push_changes(
+        &self,
+        user_id: UserId,
+        push: SyncPush,
+    ) -> Result<SyncPushResult, AppError> {
+        let results = self.journal_repository.push_changes(user_id, &push.changes).await?;
+        Ok(SyncPushResult { results })
+    }
+
+    async fn import_entries(
+        &self,
+        user_id: UserId,
+        data: &[u8],
+        options: ImportOptions,
+    ) -> Result<ImportReport, AppError> {
+        let (entries, mut errors) = match options.format {
+            ImportFormat::Csv => import::parse_csv(data, &options)?,
+            ImportFormat::Ics => import::parse_ics(data, &options)?,
+        };
+        // Rows which can't be parsed prevent the import, but the others are still validated.
+        let commit = !options.dry_run && errors.is_empty();
+        let mut report = self.journal_repository.import(user_id, &entries, commit).await?;
+        errors.append(&mut report.errors);
+        errors.sort_by_key(|error| error.line);
+        report.errors = errors;
+        Ok(report)
+    }
+
+    async fn start_timer(
+        &self,
+        user_id: UserId,
+        timer: NewTimer,
+    ) -> Result<JournalEntryId, AppError> {
+        Ok(self.journal_repository.start_timer(user_id, &timer).await?)
+    }
+
+    async fn stop_timer(&self, user_id: UserId, id: JournalEntryId) -> Result<(), AppError> {
+        if self.journal_repository.stop_timer(user_id, id).await? {
+            return Ok(());
+        }
+        // Distinguish a missing entry from the one which is not running, or which can't end now
+        // because it starts in the future.
+        match self.journal_repository.find_by_id(user_id, id).await? {
+            Some(entry) if entry.running => Err(AppError::EntryNotStarted),
+            Some(_) => Err(AppError::EntryNotRunning),
+            None => Err(AppError::NotFound),
+        }
+    }
+
+    async fn create_calendar_token(&self, user_id: UserId) -> Result<FeedToken, AppError> {
+        let token = new_token();
+        self.journal_repository.upsert_calendar_token(user_id, &hash_token(&token)).await?;
+        Ok(FeedToken { token })
+    }
+
+    async fn delete_calendar_token(&self, user_id: UserId) -> Result<(), AppError> {
+        self.journal_repository
+            .delete_calendar_token(user_id)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn export_calendar(
+        &self,
+        token: &str,
+        query: CalendarQuery,
+    ) -> Result<ExportStream, AppError> {
+        let user_id = self
+            .journal_repository
+            .find_calendar_user(&hash_token(token))
+            .await?
+            .ok_or(AppError::NotFound)?;
+        // The feed is public, so the entries are read up front instead of keeping the export
+        // cursor open for as long as the caller takes to download them.
+        let filter = SearchFilter {
+            sort: Some(SortOrder::Desc),
+            limit: Some(CALENDAR_FEED_SIZE),
+            ..query.into()
+        };
+        let entries: Vec<_> =
+            self.journal_repository.export(user_id, &filter).await?.try_collect().await?;
+        Ok(export::encode(ExportFormat::Ics, stream::iter(entries.into_iter().map(Ok)).boxed()))
+    }
+
+    async fn find_saved_searches(&self, user_id: UserId) -> Result<Vec<SavedSearch>, AppError> {
+        Ok(self.journal_repository.find_saved_searches(user_id).await?)
+    }
+
+    async fn find_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<SavedSearch, AppError> {
+        self.journal_repository.find_saved_search(user_id, id).await?.ok_or(AppError::NotFound)
+    }
+
+    async fn insert_saved_search(
+        &self,
+        user_id: UserId,
+        search: SavedSearchData,
+    ) -> Result<SavedSearchId, AppError> {
+        Ok(self.journal_repository.insert_saved_search(user_id, &search).await?)
+    }
+
+    async fn update_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        search: SavedSearchData,
+    ) -> Result<(), AppError> {
+        self.journal_repository
+            .update_saved_search(user_id, id, &search)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn delete_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<(), AppError> {
+        self.journal_repository
+            .delete_saved_search(user_id, id)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn execute_saved_search(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+        overrides: SearchFilter,
+    ) -> Result<Vec<JournalEntry>, AppError> {
+        let saved_search = self.find_saved_search(user_id, id).await?;
+        let filter = saved_search.filter.0.merge(overrides);
+        filter.validate().map_err(AppError::from)?;
+        Ok(self.journal_repository.find(user_id, &filter).await?)
+    }
+
+    async fn create_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<FeedToken, AppError> {
+        let token = new_token();
+        self.journal_repository
+            .update_saved_search_feed_token(user_id, id, &hash_token(&token))
+            .await?
+            .then_some(FeedToken { token })
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn delete_saved_search_feed_token(
+        &self,
+        user_id: UserId,
+        id: SavedSearchId,
+    ) -> Result<(), AppError> {
+        self.journal_repository
+            .delete_saved_search_feed_token(user_id, id)
+            .await?
+            .then_some(())
+            .ok_or(AppError::NotFound)
+    }
+
+    async fn find_saved_search_feed(&self, token: &str) -> Result<SearchFeed, AppError> {
+        let search = self
+            .journal_repository
+            .find_saved_search_by_feed_token(&hash_token(token))
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let user_id = search.user_id;
+        let filter = SearchFilter { sort: Some(SortOrder::Desc), ..search.filter.0.clone() };
+        let (entries, event_types) = tokio::try_join!(
+            self.journal_repository.find_page(user_id, &filter, Cursor::Start, SEARCH_FEED_SIZE),
+            self.event_repository.find_by_user_id(user_id)
+        )?;
+
+        let event_types: HashMap<EventTypeId, String> =
+            event_types.into_iter().map(|event_type| (event_type.id, event_type.name)).collect();
+        let entries = entries
+            .into_iter()
+            .map(|entry| FeedEntry {
+                event_type: event_types.get(&entry.event_type_id).cloned().unwrap_or_default(),
+                entry,
+            })
+            .collect();
+        Ok(SearchFeed { search, entries })
+    }
+}
+
+fn new_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; FEED_TOKEN_SIZE]>())
+}
+
+/// Only the hashes of the feed tokens are stored, so a leaked database doesn't expose the feeds.
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::journal::repository::{MockEventTypeRepository, MockJournalEntryRepository};
-    use chrono::Utc;
+    use actix_web::web;
+    use chrono::{NaiveDate, Utc};
+    use futures_util::{StreamExt, TryStreamExt, stream};
     use mockall::predicate::*;
+    use sqlx::types::Json;
+    use std::sync::{Arc, Mutex};
     use uuid::Uuid;
 
     #[tokio::test]
     async fn test_update_event_type_success() {
         let user_id = UserId::new(Uuid::new_v4());
         let id = EventTypeId::new(Uuid::new_v4());
-        let update = EventTypeData { name: "update".to_string(), tags: vec!["tag1".to_string()] };
+        let update = EventTypeData {
+            name: "update".to_string(),
+            tags: vec!["tag1".to_string()],
+            fields: vec![],
+        };
 
         let journal_repo = MockJournalEntryRepository::new();
         let mut event_repo = MockEventTypeRepository::new();
         event_repo
             .expect_update()
-            .with(eq(user_id), eq(id), eq(update.name.clone()), eq(update.tags.clone()))
-            .return_once(|_, _, _, _| Ok(true));
+            .with(
+                eq(user_id),
+                eq(id),
+                eq(update.name.clone()),
+                eq(update.tags.clone()),
+                eq(update.fields.clone()),
+            )
+            .return_once(|_, _, _, _, _| Ok(true));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
         let result = service.update_event_type(user_id, id, update).await;
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
@@ -208,11 +748,15 @@ mod tests {
         let mut event_repo = MockEventTypeRepository::new();
         event_repo
             .expect_update()
-            .with(eq(user_id), eq(id), eq("update"), eq(vec!["tag1".to_string()]))
-            .return_once(|_, _, _, _| Ok(false));
+            .with(eq(user_id), eq(id), eq("update"), eq(vec!["tag1".to_string()]), eq(vec![]))
+            .return_once(|_, _, _, _, _| Ok(false));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
-        let update = EventTypeData { name: "update".to_string(), tags: vec!["tag1".to_string()] };
+        let update = EventTypeData {
+            name: "update".to_string(),
+            tags: vec!["tag1".to_string()],
+            fields: vec![],
+        };
         let result = service.update_event_type(user_id, id, update).await;
         assert!(matches!(result, Err(AppError::NotFound)));
     }
@@ -226,11 +770,15 @@ mod tests {
         let mut event_repo = MockEventTypeRepository::new();
         event_repo
             .expect_update()
-            .with(eq(user_id), eq(id), eq("update"), eq(vec!["tag1".to_string()]))
-            .return_once(|_, _, _, _| Err(AppError::TagsStillUsed(vec!["tag2".to_string()])));
+            .with(eq(user_id), eq(id), eq("update"), eq(vec!["tag1".to_string()]), eq(vec![]))
+            .return_once(|_, _, _, _, _| Err(AppError::TagsStillUsed(vec!["tag2".to_string()])));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
-        let update = EventTypeData { name: "update".to_string(), tags: vec!["tag1".to_string()] };
+        let update = EventTypeData {
+            name: "update".to_string(),
+            tags: vec!["tag1".to_string()],
+            fields: vec![],
+        };
         let result = service.update_event_type(user_id, id, update).await;
         assert!(matches!(result, Err(AppError::TagsStillUsed(_))));
     }
@@ -244,20 +792,22 @@ mod tests {
         let mut journal_repo = MockJournalEntryRepository::new();
         journal_repo
             .expect_insert()
-            .withf(move |uid, eid, desc, tags, _| {
+            .withf(move |uid, entry| {
                 uid == &user_id
-                    && eid == &event_type_id
-                    && desc == &Some("test")
-                    && tags == &vec!["test".to_string()]
+                    && entry.event_type_id == event_type_id
+                    && entry.description.as_deref() == Some("test")
+                    && entry.tags == vec!["test".to_string()]
             })
-            .return_once(move |_, _, _, _, _| Ok(id));
+            .return_once(move |_, _| Ok(id));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
         let entry = NewJournalEntry {
             event_type_id,
             description: Some("test".to_string()),
             tags: vec!["test".to_string()],
-            created_at: None,
+            occurred_at: None,
+            ended_at: None,
+            values: FieldValues::new(),
         };
         let result = service.insert_journal_entry(user_id, entry).await.unwrap();
         assert_eq!(id, result);
@@ -269,16 +819,16 @@ mod tests {
         let event_type_id = EventTypeId::new(Uuid::new_v4());
         let event_repo = MockEventTypeRepository::new();
         let mut journal_repo = MockJournalEntryRepository::new();
-        journal_repo
-            .expect_insert()
-            .return_once(|_, _, _, _, _| Err(AppError::EventTypeValidation));
+        journal_repo.expect_insert().return_once(|_, _| Err(AppError::EventTypeValidation));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
         let entry = NewJournalEntry {
             event_type_id,
             description: Some("test".to_string()),
             tags: vec!["test".to_string()],
-            created_at: None,
+            occurred_at: None,
+            ended_at: None,
+            values: FieldValues::new(),
         };
         let result = service.insert_journal_entry(user_id, entry).await;
         assert!(matches!(result, Err(AppError::EventTypeValidation)));
@@ -295,7 +845,13 @@ mod tests {
             event_type_id,
             description: None,
             tags: vec![],
-            created_at: Utc::now(),
+            occurred_at: Utc::now(),
+            ended_at: None,
+            duration_secs: None,
+            running: false,
+            recorded_at: Utc::now(),
+            values: Json(FieldValues::new()),
+            snippet: None,
         };
         let event_repo = MockEventTypeRepository::new();
         let mut journal_repo = MockJournalEntryRepository::new();
@@ -305,21 +861,27 @@ mod tests {
             .return_once(|_, _| Ok(Some(current_entry)));
         journal_repo
             .expect_update()
-            .withf(move |uid, eid, desc, tags| {
+            .withf(move |uid, eid, update| {
                 uid == &user_id
                     && eid == &id
-                    && desc == &Some("test")
-                    && tags == vec!["test".to_string()]
+                    && update.event_type_id.is_none()
+                    && update.description.as_deref() == Some("test")
+                    && update.tags == vec!["test".to_string()]
+                    && update.occurred_at.is_none()
             })
-            .return_once(|_, _, _, _| Ok(true));
+            .return_once(|_, _, _| Ok(true));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
         let update = JournalEntryUpdate {
+            event_type_id: None,
             description: Some("test".to_string()),
             tags: vec!["test".to_string()],
+            occurred_at: None,
+            ended_at: None,
+            values: FieldValues::new(),
         };
         let result = service.update_journal_entry(user_id, id, update).await;
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
@@ -328,14 +890,512 @@ mod tests {
         let id = JournalEntryId::new(Uuid::new_v4());
         let event_repo = MockEventTypeRepository::new();
         let mut journal_repo = MockJournalEntryRepository::new();
-        journal_repo.expect_update().return_once(|_, _, _, _| Err(AppError::EventTypeValidation));
+        journal_repo.expect_update().return_once(|_, _, _| Err(AppError::EventTypeValidation));
         let service = JournalServiceImpl::new(event_repo, journal_repo);
 
         let update = JournalEntryUpdate {
+            event_type_id: None,
             description: Some("test".to_string()),
             tags: vec!["test".to_string()],
+            occurred_at: None,
+            ended_at: None,
+            values: FieldValues::new(),
         };
         let result = service.update_journal_entry(user_id, id, update).await;
         assert!(matches!(result, Err(AppError::EventTypeValidation)));
     }
+
+    #[tokio::test]
+    async fn test_patch_event_type_success() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = EventTypeId::new(Uuid::new_v4());
+        let journal_repo = MockJournalEntryRepository::new();
+        let mut event_repo = MockEventTypeRepository::new();
+        event_repo
+            .expect_patch()
+            .withf(move |&uid, &eid, patch| {
+                uid == user_id && eid == id && patch.name.as_deref() == Some("patched")
+            })
+            .return_once(|_, _, _| Ok(true));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let patch = EventTypePatch { name: Some("patched".to_string()), ..Default::default() };
+        let result = service.patch_event_type(user_id, id, patch).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_patch_event_type_not_found_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = EventTypeId::new(Uuid::new_v4());
+        let journal_repo = MockJournalEntryRepository::new();
+        let mut event_repo = MockEventTypeRepository::new();
+        event_repo.expect_patch().return_once(|_, _, _| Ok(false));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let patch = EventTypePatch { name: Some("patched".to_string()), ..Default::default() };
+        let result = service.patch_event_type(user_id, id, patch).await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_patch_journal_entry_success() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = JournalEntryId::new(Uuid::new_v4());
+        let event_repo = MockEventTypeRepository::new();
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_patch()
+            .withf(move |&uid, &eid, patch| {
+                uid == user_id && eid == id && patch.description == Some(Some("fixed".to_string()))
+            })
+            .return_once(|_, _, _| Ok(true));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let patch = JournalEntryPatch {
+            description: Some(Some("fixed".to_string())),
+            ..Default::default()
+        };
+        let result = service.patch_journal_entry(user_id, id, patch).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_patch_journal_entry_not_found_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = JournalEntryId::new(Uuid::new_v4());
+        let event_repo = MockEventTypeRepository::new();
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo.expect_patch().return_once(|_, _, _| Ok(false));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let result = service.patch_journal_entry(user_id, id, JournalEntryPatch::default()).await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_stop_timer_success() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = JournalEntryId::new(Uuid::new_v4());
+        let event_repo = MockEventTypeRepository::new();
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo.expect_stop_timer().with(eq(user_id), eq(id)).return_once(|_, _| Ok(true));
+        journal_repo.expect_find_by_id().never();
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let result = service.stop_timer(user_id, id).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stop_timer_not_running_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let event_type_id = EventTypeId::new(Uuid::new_v4());
+        let id = JournalEntryId::new(Uuid::new_v4());
+        let occurred_at = Utc::now();
+        let stopped_entry = JournalEntry {
+            id,
+            user_id,
+            event_type_id,
+            description: None,
+            tags: vec![],
+            occurred_at,
+            ended_at: Some(occurred_at + chrono::Duration::minutes(30)),
+            duration_secs: Some(1800),
+            running: false,
+            recorded_at: occurred_at,
+            values: Json(FieldValues::new()),
+            snippet: None,
+        };
+        let event_repo = MockEventTypeRepository::new();
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo.expect_stop_timer().return_once(|_, _| Ok(false));
+        journal_repo.expect_find_by_id().return_once(|_, _| Ok(Some(stopped_entry)));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let result = service.stop_timer(user_id, id).await;
+        assert!(matches!(result, Err(AppError::EntryNotRunning)));
+    }
+
+    #[tokio::test]
+    async fn test_stop_timer_not_started_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = JournalEntryId::new(Uuid::new_v4());
+        let occurred_at = Utc::now() + chrono::Duration::hours(1);
+        let future_entry = JournalEntry {
+            id,
+            user_id,
+            event_type_id: EventTypeId::new(Uuid::new_v4()),
+            description: None,
+            tags: vec![],
+            occurred_at,
+            ended_at: None,
+            duration_secs: None,
+            running: true,
+            recorded_at: Utc::now(),
+            values: Json(FieldValues::new()),
+            snippet: None,
+        };
+        let event_repo = MockEventTypeRepository::new();
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo.expect_stop_timer().return_once(|_, _| Ok(false));
+        journal_repo.expect_find_by_id().return_once(|_, _| Ok(Some(future_entry)));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let result = service.stop_timer(user_id, id).await;
+        assert!(matches!(result, Err(AppError::EntryNotStarted)));
+    }
+
+    #[tokio::test]
+    async fn test_stop_timer_not_found_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = JournalEntryId::new(Uuid::new_v4());
+        let event_repo = MockEventTypeRepository::new();
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo.expect_stop_timer().return_once(|_, _| Ok(false));
+        journal_repo.expect_find_by_id().return_once(|_, _| Ok(None));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let result = service.stop_timer(user_id, id).await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_find_goal_progress() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = EventTypeId::new(Uuid::new_v4());
+        let goal = Goal { period: GoalPeriod::Day, target: 1 };
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let mut event_repo = MockEventTypeRepository::new();
+        let found_goal = goal.clone();
+        event_repo
+            .expect_find_goal()
+            .with(eq(user_id), eq(id))
+            .return_once(move |_, _| Ok(Some(found_goal)));
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_count_per_period()
+            .with(eq(user_id), eq(id), eq(GoalPeriod::Day))
+            .return_once(move |_, _, _| {
+                Ok(PeriodCounts { current_period: today, counts: vec![(today, 1)] })
+            });
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let progress = service.find_goal_progress(user_id, id).await.unwrap();
+        assert_eq!(goal, progress.goal);
+        assert!(progress.current_period_met);
+        assert_eq!(1, progress.current_streak);
+    }
+
+    #[tokio::test]
+    async fn test_find_goal_progress_without_goal_fails() {
+        let mut event_repo = MockEventTypeRepository::new();
+        event_repo.expect_find_goal().return_once(|_, _| Ok(None));
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo.expect_count_per_period().never();
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let user_id = UserId::new(Uuid::new_v4());
+        let result = service.find_goal_progress(user_id, EventTypeId::new(Uuid::new_v4())).await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_search_merges_results_by_score() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let event_type = |name: &str, score| SearchResult::EventType {
+            id: EventTypeId::new(Uuid::new_v4()),
+            name: name.to_string(),
+            tags: vec![],
+            score,
+        };
+        let entry = |description: &str, score| SearchResult::Entry {
+            id: JournalEntryId::new(Uuid::new_v4()),
+            event_type_id: EventTypeId::new(Uuid::new_v4()),
+            description: Some(description.to_string()),
+            tags: vec![],
+            occurred_at: Utc::now(),
+            score,
+        };
+        let (event_types, entries) =
+            (vec![event_type("run", 1.0), event_type("runner", 0.5)], vec![entry("ran", 0.7)]);
+        let expected = vec![event_types[0].clone(), entries[0].clone()];
+
+        let mut event_repo = MockEventTypeRepository::new();
+        event_repo
+            .expect_search()
+            .with(eq(user_id), eq("run"), eq(2))
+            .return_once(move |_, _, _| Ok(event_types));
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_search()
+            .with(eq(user_id), eq("run"), eq(2))
+            .return_once(move |_, _, _| Ok(entries));
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let query = FuzzySearchQuery { q: "run".to_string(), limit: 2 };
+        let results = service.search(user_id, query).await.unwrap();
+        assert_eq!(expected, results);
+    }
+
+    fn saved_search(user_id: UserId, id: SavedSearchId, filter: SearchFilter) -> SavedSearch {
+        SavedSearch { id, user_id, name: "saved".to_string(), filter: Json(filter) }
+    }
+
+    #[tokio::test]
+    async fn test_execute_saved_search_with_overrides() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = SavedSearchId::new(Uuid::new_v4());
+        let saved_filter = SearchFilter {
+            tags_all: vec!["a".to_string()],
+            sort: Some(SortOrder::Desc),
+            ..Default::default()
+        };
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_saved_search()
+            .with(eq(user_id), eq(id))
+            .return_once(move |user_id, id| Ok(Some(saved_search(user_id, id, saved_filter))));
+        journal_repo
+            .expect_find()
+            .withf(move |uid, filter| {
+                uid == &user_id
+                    && filter.tags_all == vec!["a".to_string()]
+                    && filter.sort == Some(SortOrder::Asc)
+            })
+            .return_once(|_, _| Ok(vec![]));
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let overrides = SearchFilter { sort: Some(SortOrder::Asc), ..Default::default() };
+        let result = service.execute_saved_search(user_id, id, overrides).await;
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_saved_search_with_invalid_overrides_fails() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let id = SavedSearchId::new(Uuid::new_v4());
+        let saved_filter = SearchFilter { tags_all: vec!["a".to_string()], ..Default::default() };
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_saved_search()
+            .return_once(move |user_id, id| Ok(Some(saved_search(user_id, id, saved_filter))));
+        journal_repo.expect_find().never();
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let overrides = SearchFilter { tags_none: vec!["a".to_string()], ..Default::default() };
+        let result = service.execute_saved_search(user_id, id, overrides).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+
+    fn entry(user_id: UserId) -> JournalEntry {
+        JournalEntry {
+            id: JournalEntryId::new(Uuid::new_v4()),
+            user_id,
+            event_type_id: EventTypeId::new(Uuid::new_v4()),
+            description: None,
+            tags: vec![],
+            occurred_at: Utc::now(),
+            ended_at: None,
+            duration_secs: None,
+            running: false,
+            recorded_at: Utc::now(),
+            values: Json(FieldValues::new()),
+            snippet: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_journal_entries_page_with_next_cursor() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let entries = vec![entry(user_id), entry(user_id), entry(user_id)];
+        let expected_cursor = Cursor::after(&entries[1]);
+        let expected_ids = vec![entries[0].id, entries[1].id];
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_page()
+            .withf(move |uid, _, cursor, limit| {
+                uid == &user_id && cursor == &Cursor::Start && *limit == 3
+            })
+            .return_once(|_, _, _, _| Ok(entries));
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let filter = SearchFilter { limit: Some(2), ..Default::default() };
+        let page = service.find_journal_entries_page(user_id, filter, Cursor::Start).await.unwrap();
+        assert_eq!(expected_ids, page.items.iter().map(|e| e.id).collect::<Vec<_>>());
+        assert_eq!(Some(expected_cursor), page.next_cursor);
+    }
+
+    #[tokio::test]
+    async fn test_find_journal_entries_last_page() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let entries = vec![entry(user_id)];
+        let cursor = Cursor::after(&entry(user_id));
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_page()
+            .with(always(), always(), eq(cursor), eq(DEFAULT_PAGE_SIZE + 1))
+            .return_once(|_, _, _, _| Ok(entries));
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let page = service
+            .find_journal_entries_page(user_id, SearchFilter::default(), cursor)
+            .await
+            .unwrap();
+        assert_eq!(1, page.items.len());
+        assert_eq!(None, page.next_cursor);
+
+        let filter = SearchFilter { offset: Some(10), ..Default::default() };
+        let result = service.find_journal_entries_page(user_id, filter, cursor).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_find_journal_entries_page_with_total() {
+        let user_id = UserId::new(Uuid::new_v4());
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_with_total()
+            .times(2)
+            .returning(move |user_id, _| Ok((vec![entry(user_id)], 5)));
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let filter = SearchFilter { offset: Some(3), limit: Some(1), ..Default::default() };
+        let page = service.find_journal_entries_page_with_total(user_id, filter).await.unwrap();
+        assert_eq!((5, true, 3, Some(1)), (page.total, page.has_more, page.offset, page.limit));
+
+        let filter = SearchFilter { offset: Some(4), ..Default::default() };
+        let page = service.find_journal_entries_page_with_total(user_id, filter).await.unwrap();
+        assert_eq!((1, false, 4, None), (page.items.len(), page.has_more, page.offset, page.limit));
+    }
+
+    #[tokio::test]
+    async fn test_import_entries_with_invalid_rows_is_not_committed() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let data = "event_type,occurred_at\nRun,2026-01-02T07:00:00Z\nRun,yesterday\n";
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_import()
+            .withf(|_, entries, commit| entries.len() == 1 && !commit)
+            .return_once(|_, _, _| Ok(ImportReport { imported: 1, ..Default::default() }));
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let options = web::Query::<ImportOptions>::from_query("").unwrap().into_inner();
+        let report = service.import_entries(user_id, data.as_bytes(), options).await.unwrap();
+        assert!(!report.committed);
+        assert_eq!(1, report.imported);
+        let expected_error =
+            ImportError { line: 3, message: "invalid timestamp `yesterday`".to_string() };
+        assert_eq!(vec![expected_error], report.errors);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_token_is_stored_hashed() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let stored_hash = Arc::new(Mutex::new(Vec::new()));
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        let stored = stored_hash.clone();
+        journal_repo.expect_upsert_calendar_token().return_once(move |_, hash| {
+            *stored.lock().unwrap() = hash.to_vec();
+            Ok(())
+        });
+        let stored = stored_hash.clone();
+        journal_repo
+            .expect_find_calendar_user()
+            .returning(move |hash| Ok((hash == *stored.lock().unwrap()).then_some(user_id)));
+        journal_repo
+            .expect_export()
+            .withf(move |id, filter| {
+                *id == user_id
+                    && filter.tags_all == ["run"]
+                    && filter.limit == Some(CALENDAR_FEED_SIZE)
+            })
+            .return_once(|_, _| Ok(stream::empty().boxed()));
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let token = service.create_calendar_token(user_id).await.unwrap().token;
+        assert_eq!(43, token.len());
+        assert_ne!(token.as_bytes(), stored_hash.lock().unwrap().as_slice());
+
+        let query = CalendarQuery { tags: vec!["run".to_string()], ..Default::default() };
+        let calendar: Vec<_> =
+            service.export_calendar(&token, query).await.unwrap().try_collect().await.unwrap();
+        assert_eq!(2, calendar.len(), "header and footer");
+        let result = service.export_calendar("unknown", CalendarQuery::default()).await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_find_saved_search_feed() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let event_type_id = EventTypeId::new(Uuid::new_v4());
+        let filter = SearchFilter { tags_all: vec!["team".to_string()], ..Default::default() };
+        let search = SavedSearch {
+            id: SavedSearchId::new(Uuid::new_v4()),
+            user_id,
+            name: "Team".to_string(),
+            filter: Json(filter),
+        };
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_saved_search_by_feed_token()
+            .withf(|hash| hash == hash_token("token"))
+            .return_once(move |_| Ok(Some(search)));
+        journal_repo
+            .expect_find_page()
+            .withf(|_, filter, cursor, _| {
+                filter.tags_all == ["team"]
+                    && filter.sort == Some(SortOrder::Desc)
+                    && *cursor == Cursor::Start
+            })
+            .return_once(move |_, _, _, _| {
+                Ok(vec![JournalEntry { event_type_id, ..entry(user_id) }, entry(user_id)])
+            });
+        let mut event_repo = MockEventTypeRepository::new();
+        event_repo.expect_find_by_user_id().return_once(move |user_id| {
+            Ok(vec![EventType {
+                id: event_type_id,
+                user_id,
+                name: "Run".to_string(),
+                tags: vec![],
+                fields: Json(vec![]),
+            }])
+        });
+        let service = JournalServiceImpl::new(event_repo, journal_repo);
+
+        let feed = service.find_saved_search_feed("token").await.unwrap();
+        assert_eq!("Team", feed.search.name);
+        let event_types: Vec<_> = feed.entries.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(vec!["Run", ""], event_types);
+    }
+
+    #[tokio::test]
+    async fn test_find_changes_from_cursor() {
+        let user_id = UserId::new(Uuid::new_v4());
+
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_changes()
+            .with(eq(user_id), eq(ChangeCursor(42)), eq(500))
+            .return_once(|_, _, _| {
+                Ok(SyncChanges { cursor: ChangeCursor(43), has_more: true, ..Default::default() })
+            });
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+
+        let query = web::Query::<SyncQuery>::from_query(&format!("cursor={}", ChangeCursor(42)))
+            .unwrap()
+            .into_inner();
+        let changes = service.find_changes(user_id, query).await.unwrap();
+        assert_eq!(ChangeCursor(43), changes.cursor);
+        assert!(changes.has_more);
+    }
 }
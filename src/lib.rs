@@ -1,3 +1,5 @@
+pub mod idempotency;
 pub mod journal;
+pub mod live;
 pub mod model;
 pub mod user;
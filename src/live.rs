@@ -0,0 +1,5 @@
+pub mod handler;
+pub mod model;
+pub mod repository;
+pub mod service;
+pub mod socket;
@@ -0,0 +1,85 @@
+use crate::journal::service::JournalService;
+use crate::live::model::SocketQuery;
+use crate::live::service::LiveService;
+use crate::live::socket::{self, MAX_MESSAGE_SIZE, SocketState};
+use crate::model::AppError;
+use crate::user::model::UserId;
+use crate::user::service::UserService;
+use actix_web::dev::ServiceRequest;
+use actix_web::http::header;
+use actix_web::rt::{self, time};
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use futures_util::stream::{self, StreamExt};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Interval of the comments which keep the idle stream from being closed by proxies.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Streams the changes of the user's event types and entries as Server-Sent Events.
+pub async fn stream_changes<T: LiveService>(
+    user_id: web::ReqData<UserId>,
+    service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let events = service.subscribe(user_id.into_inner()).map(|event| event.to_sse());
+    let keep_alive = stream::unfold(time::interval(KEEP_ALIVE_INTERVAL), |mut interval| async {
+        interval.tick().await;
+        Some((": keep-alive\n\n".to_string(), interval))
+    });
+    let body =
+        stream::select(events, keep_alive).map(|message| Ok::<_, AppError>(Bytes::from(message)));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+        .streaming(body))
+}
+
+/// Upgrades the request to a WebSocket of a realtime client, see [`socket`]. The access token is
+/// taken from the `Authorization` header or the `access_token` query parameter, since browsers
+/// can't set the headers of WebSockets. The socket is closed when the token expires.
+///
+/// The query parameter must be left out of the access log, see [`request_line`].
+pub async fn connect_socket<U, J, L>(
+    req: HttpRequest,
+    body: web::Payload,
+    credentials: Option<BearerAuth>,
+    query: web::Query<SocketQuery>,
+    user_service: web::Data<U>,
+    journal_service: web::Data<J>,
+    live_service: web::Data<L>,
+) -> Result<HttpResponse, actix_web::Error>
+where
+    U: UserService,
+    J: JournalService + 'static,
+    L: LiveService,
+{
+    let token = credentials.as_ref().map(BearerAuth::token).or(query.access_token.as_deref());
+    let claims = user_service.validate_token(token.ok_or(AppError::Unauthorized)?)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let expires_in = Duration::from_secs(claims.exp).saturating_sub(now);
+
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    let messages = messages
+        .max_frame_size(MAX_MESSAGE_SIZE)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_MESSAGE_SIZE);
+    let state = SocketState::new(claims.sub, journal_service.into_inner());
+    let changes = live_service.subscribe(claims.sub);
+    rt::spawn(socket::run(session, messages, state, changes, expires_in));
+
+    Ok(response)
+}
+
+/// Request line of the access log, like the `%r` format of the logger, without the access token
+/// of the WebSocket handshake.
+pub fn request_line(req: &ServiceRequest) -> String {
+    let query = req.query_string();
+    if query.is_empty() {
+        format!("{} {} {:?}", req.method(), req.path(), req.version())
+    } else {
+        let query = SocketQuery::redact(query);
+        format!("{} {}?{query} {:?}", req.method(), req.path(), req.version())
+    }
+}
@@ -0,0 +1,208 @@
+use crate::journal::model::{
+    ChangeCursor, EventTypeId, JournalEntry, JournalEntryId, JournalEntryUpdate, NewJournalEntry,
+    SyncKind,
+};
+use crate::user::model::UserId;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    #[display("created")]
+    Created,
+    #[display("updated")]
+    Updated,
+    #[display("deleted")]
+    Deleted,
+}
+
+/// Committed change of an event type or entry, as notified by the database.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ChangeEvent {
+    #[serde(skip_serializing)]
+    pub user_id: UserId,
+    pub kind: SyncKind,
+    pub action: ChangeAction,
+    pub id: Uuid,
+    /// Event type of a changed entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_type_id: Option<EventTypeId>,
+    /// Version of the row after the change, or of its tombstone. See
+    /// [`SyncChanges`](crate::journal::model::SyncChanges).
+    pub version: i64,
+}
+
+/// Message of the live stream of a user.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum LiveEvent {
+    Change(ChangeEvent),
+    /// Some changes may have been missed, so the client should sync.
+    Resync,
+}
+
+impl LiveEvent {
+    /// Formats the event as a Server-Sent Event named by the change action. Changes have the
+    /// change cursor of their version as the event id, which the client can sync from.
+    pub fn to_sse(&self) -> String {
+        match self {
+            LiveEvent::Change(change) => {
+                let data = serde_json::to_string(change).unwrap_or_default();
+                let id = ChangeCursor(change.version);
+                format!("event: {}\nid: {id}\ndata: {data}\n\n", change.action)
+            }
+            LiveEvent::Resync => "event: resync\ndata: {}\n\n".to_string(),
+        }
+    }
+}
+
+/// Credentials of the WebSocket handshake for clients which can't send the `Authorization`
+/// header, like browsers.
+#[derive(Deserialize, Debug)]
+pub struct SocketQuery {
+    pub access_token: Option<String>,
+}
+
+impl SocketQuery {
+    /// Replaces the access token in the query string, so that it can be logged.
+    pub fn redact(query: &str) -> String {
+        query
+            .split('&')
+            .map(|param| {
+                let decoded: Vec<(String, String)> =
+                    serde_urlencoded::from_str(param).unwrap_or_default();
+                match decoded.first() {
+                    Some((key, _)) if key == "access_token" => "access_token=-",
+                    _ => param,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+/// Command sent by a WebSocket client as a JSON text message. Each command is acknowledged by a
+/// [`ServerMessage::Ack`] or [`ServerMessage::Error`] with its request id.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Subscribes to the changes of the entries matching the filter, identified by the request id
+    /// of the subscription.
+    Subscribe {
+        request_id: String,
+        #[serde(default)]
+        filter: EntryFilter,
+    },
+    Unsubscribe {
+        request_id: String,
+        subscription_id: String,
+    },
+    CreateEntry {
+        request_id: String,
+        entry: NewJournalEntry,
+    },
+    UpdateEntry {
+        request_id: String,
+        id: JournalEntryId,
+        entry: JournalEntryUpdate,
+    },
+}
+
+impl ClientMessage {
+    pub fn request_id(&self) -> &str {
+        match self {
+            ClientMessage::Subscribe { request_id, .. }
+            | ClientMessage::Unsubscribe { request_id, .. }
+            | ClientMessage::CreateEntry { request_id, .. }
+            | ClientMessage::UpdateEntry { request_id, .. } => request_id,
+        }
+    }
+}
+
+/// Filter of the entry changes delivered to a subscription.
+#[derive(Clone, Eq, PartialEq, Deserialize, Debug, Default)]
+pub struct EntryFilter {
+    pub event_type_id: Option<EventTypeId>,
+    /// Tags which the entries must all have. Deletions are matched by the event type only, since
+    /// the tags of deleted entries are not known.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl EntryFilter {
+    /// Checks if the entry change matches the filter, where `entry` is the changed entry unless it
+    /// has been deleted.
+    pub fn matches(&self, change: &ChangeEvent, entry: Option<&JournalEntry>) -> bool {
+        change.kind == SyncKind::Entry
+            && self.event_type_id.is_none_or(|id| change.event_type_id == Some(id))
+            && entry.is_none_or(|entry| self.tags.iter().all(|tag| entry.tags.contains(tag)))
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Ack {
+        request_id: String,
+        /// Id of the created entry.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<JournalEntryId>,
+    },
+    Error {
+        /// Missing when the message couldn't be parsed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<String>,
+        status: u16,
+        error: String,
+    },
+    Change {
+        subscription_id: String,
+        #[serde(flatten)]
+        change: ChangeEvent,
+        /// The changed entry, missing for deletions.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        entry: Option<Box<JournalEntry>>,
+    },
+    /// Some changes may have been missed, see [`LiveEvent::Resync`].
+    Resync,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_change_event_from_notification_to_sse() {
+        let user_id = Uuid::new_v4();
+        let id = Uuid::new_v4();
+        let event_type_id = Uuid::new_v4();
+        let payload = json!({
+            "user_id": user_id, "kind": "entry", "action": "created", "id": id,
+            "event_type_id": event_type_id, "version": 42
+        });
+
+        let change: ChangeEvent = serde_json::from_value(payload).unwrap();
+        assert_eq!(UserId::new(user_id), change.user_id);
+        assert_eq!(Some(EventTypeId::new(event_type_id)), change.event_type_id);
+
+        let sse = LiveEvent::Change(change).to_sse();
+        let (head, data) = sse.split_once("data: ").unwrap();
+        assert_eq!(format!("event: created\nid: {}\n", ChangeCursor(42)), head);
+        assert!(data.ends_with("\n\n"));
+        let data: serde_json::Value = serde_json::from_str(data).unwrap();
+        let expected = json!({
+            "kind": "entry", "action": "created", "id": id,
+            "event_type_id": event_type_id, "version": 42
+        });
+        assert_eq!(expected, data);
+    }
+
+    #[test]
+    fn test_redact_access_token() {
+        assert_eq!("", SocketQuery::redact(""));
+        assert_eq!("a=1&access_token=-&b=2", SocketQuery::redact("a=1&access_token=secret&b=2"));
+        assert_eq!("access_token=-", SocketQuery::redact("access%5Ftoken=secret"));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::live::model::ChangeEvent;
+use crate::model::AppError;
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use log::warn;
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+
+/// Channel notified by the database triggers on changes of the event types and entries.
+const CHANGE_CHANNEL: &str = "journal_change";
+
+/// Changes of all users in the order of their commits. `None` is yielded when the connection was
+/// lost, since the changes committed until it is reestablished are missed.
+pub type ChangeStream = BoxStream<'static, Result<Option<ChangeEvent>, AppError>>;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait ChangeRepository {
+    /// Starts listening to the changes committed from now on.
+    async fn listen(&self) -> Result<ChangeStream, AppError>;
+}
+
+pub struct PgChangeRepository {
+    pool: PgPool,
+}
+
+impl PgChangeRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ChangeRepository for PgChangeRepository {
+    async fn listen(&self) -> Result<ChangeStream, AppError> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(CHANGE_CHANNEL).await?;
+
+        let changes = stream::try_unfold(listener, |mut listener| async move {
+            // The listener reconnects on the next call after reporting the lost connection.
+            let notification = listener.try_recv().await?;
+            Ok::<_, AppError>(Some((notification, listener)))
+        })
+        .try_filter_map(|notification| async move {
+            let Some(notification) = notification else {
+                return Ok(Some(None));
+            };
+            match serde_json::from_str(notification.payload()) {
+                Ok(change) => Ok(Some(Some(change))),
+                Err(error) => {
+                    warn!("Skipping invalid change notification: {error}");
+                    Ok(None)
+                }
+            }
+        });
+        Ok(changes.boxed())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::live::model::LiveEvent;
+use crate::live::repository::{ChangeRepository, ChangeStream};
+use crate::user::model::UserId;
+use actix_web::rt::time;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use log::error;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Delay before listening to the changes again after a failure.
+const RELISTEN_DELAY: Duration = Duration::from_secs(5);
+
+pub type LiveStream = BoxStream<'static, LiveEvent>;
+
+pub trait LiveService {
+    /// Subscribes to the changes of the user's event types and entries committed from now on.
+    fn subscribe(&self, user_id: UserId) -> LiveStream;
+}
+
+/// Broadcasts the changes notified by the database to the subscribers of this backend instance.
+/// Every instance listens to the notifications of all changes, so the clients of a user get them
+/// regardless of the instance they are connected to.
+pub struct LiveServiceImpl {
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+impl LiveServiceImpl {
+    /// Up to `capacity` events are buffered for each subscriber, a subscriber falling further
+    /// behind gets a [`LiveEvent::Resync`] instead of the missed events.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Broadcasts the changes until the process ends, listening again after failures.
+    pub async fn run<T: ChangeRepository>(&self, change_repository: T) {
+        loop {
+            match change_repository.listen().await {
+                Ok(changes) => self.forward(changes).await,
+                Err(error) => error!("Failed to listen to changes: {error}"),
+            }
+            // Changes committed until listening again are missed.
+            self.broadcast(LiveEvent::Resync);
+            time::sleep(RELISTEN_DELAY).await;
+        }
+    }
+
+    async fn forward(&self, mut changes: ChangeStream) {
+        while let Some(change) = changes.next().await {
+            match change {
+                Ok(Some(change)) => self.broadcast(LiveEvent::Change(change)),
+                Ok(None) => self.broadcast(LiveEvent::Resync),
+                Err(error) => {
+                    error!("Failed to receive changes: {error}");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn broadcast(&self, event: LiveEvent) {
+        // Sending fails only when there are no subscribers.
+        let _ = self.sender.send(event);
+    }
+}
+
+impl LiveService for LiveServiceImpl {
+    fn subscribe(&self, user_id: UserId) -> LiveStream {
+        stream::unfold(self.sender.subscribe(), move |mut receiver| async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(LiveEvent::Change(change)) if change.user_id != user_id => continue,
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => LiveEvent::Resync,
+                    Err(RecvError::Closed) => return None,
+                };
+                return Some((event, receiver));
+            }
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::model::SyncKind;
+    use crate::live::model::{ChangeAction, ChangeEvent};
+    use uuid::Uuid;
+
+    fn change(user_id: UserId, version: i64) -> ChangeEvent {
+        ChangeEvent {
+            user_id,
+            kind: SyncKind::Entry,
+            action: ChangeAction::Created,
+            id: Uuid::new_v4(),
+            event_type_id: None,
+            version,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_gets_own_changes() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let other_user = UserId::new(Uuid::new_v4());
+        let service = LiveServiceImpl::new(16);
+        let mut events = service.subscribe(user_id);
+
+        let own_change = change(user_id, 2);
+        let changes = vec![Ok(Some(change(other_user, 1))), Ok(Some(own_change.clone())), Ok(None)];
+        service.forward(stream::iter(changes).boxed()).await;
+
+        assert_eq!(Some(LiveEvent::Change(own_change)), events.next().await);
+        assert_eq!(Some(LiveEvent::Resync), events.next().await);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_resyncs() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let service = LiveServiceImpl::new(1);
+        let mut events = service.subscribe(user_id);
+
+        let changes = (1..=3).map(|version| Ok(Some(change(user_id, version)))).collect::<Vec<_>>();
+        service.forward(stream::iter(changes).boxed()).await;
+
+        assert_eq!(Some(LiveEvent::Resync), events.next().await);
+        let Some(LiveEvent::Change(received)) = events.next().await else { panic!() };
+        assert_eq!(3, received.version);
+    }
+}
@@ -0,0 +1,367 @@
+//! WebSocket session of a realtime client, which subscribes to filtered streams of its entry
+//! changes and creates or updates entries over the same socket.
+//!
+//! The client sends [`ClientMessage`]s as JSON text messages and gets a [`ServerMessage::Ack`] or
+//! [`ServerMessage::Error`] with the request id of each of them. The changes matching its
+//! subscriptions are sent as [`ServerMessage::Change`]s, along with the changes made by the
+//! client's own commands.
+
+use crate::journal::model::{JournalEntryId, SyncKind};
+use crate::journal::service::JournalService;
+use crate::live::model::{ChangeAction, ClientMessage, EntryFilter, LiveEvent, ServerMessage};
+use crate::live::service::LiveStream;
+use crate::model::AppError;
+use crate::user::model::UserId;
+use actix_web::ResponseError;
+use actix_web::rt::time;
+use actix_ws::{
+    AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, Closed, Session,
+};
+use futures_util::StreamExt;
+use log::{error, warn};
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use validator::Validate;
+
+/// Interval of the pings which keep the idle socket from being closed by proxies.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// The socket is closed when nothing is heard from the client for this long.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// State of a WebSocket session, which handles the client's messages and the live events.
+pub struct SocketState<T: JournalService> {
+    user_id: UserId,
+    journal_service: Arc<T>,
+    /// Filters of the subscriptions, keyed by the request id of the subscription.
+    subscriptions: HashMap<String, EntryFilter>,
+}
+
+impl<T: JournalService> SocketState<T> {
+    pub fn new(user_id: UserId, journal_service: Arc<T>) -> Self {
+        Self { user_id, journal_service, subscriptions: HashMap::new() }
+    }
+
+    /// Executes the client's command and replies with its acknowledgement or error.
+    pub async fn handle_text(&mut self, text: &str) -> ServerMessage {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(error) => {
+                // The request id is replied if the rest of the message is invalid.
+                let request_id = serde_json::from_str::<serde_json::Value>(text)
+                    .ok()
+                    .and_then(|value| value.get("request_id")?.as_str().map(str::to_string));
+                return ServerMessage::Error { request_id, status: 400, error: error.to_string() };
+            }
+        };
+
+        let request_id = message.request_id().to_string();
+        match self.execute(message).await {
+            Ok(id) => ServerMessage::Ack { request_id, id },
+            Err(error) => ServerMessage::Error {
+                request_id: Some(request_id),
+                status: error.status_code().as_u16(),
+                error: error.public_message(),
+            },
+        }
+    }
+
+    /// Returns the id of the created entry.
+    async fn execute(
+        &mut self,
+        message: ClientMessage,
+    ) -> Result<Option<JournalEntryId>, AppError> {
+        match message {
+            ClientMessage::Subscribe { request_id, filter } => {
+                self.subscriptions.insert(request_id, filter);
+                Ok(None)
+            }
+            ClientMessage::Unsubscribe { subscription_id, .. } => {
+                self.subscriptions.remove(&subscription_id).ok_or(AppError::NotFound)?;
+                Ok(None)
+            }
+            ClientMessage::CreateEntry { entry, .. } => {
+                entry.validate()?;
+                let id = self.journal_service.insert_journal_entry(self.user_id, entry).await?;
+                Ok(Some(id))
+            }
+            ClientMessage::UpdateEntry { id, entry, .. } => {
+                entry.validate()?;
+                self.journal_service.update_journal_entry(self.user_id, id, entry).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns the messages of the subscriptions matching the live event. The changed entry is
+    /// fetched once for all of them, so it may be newer than the change when it has been changed
+    /// again since, in which case the newer change follows.
+    pub async fn handle_event(&self, event: LiveEvent) -> Vec<ServerMessage> {
+        if self.subscriptions.is_empty() {
+            return vec![];
+        }
+        let change = match event {
+            LiveEvent::Change(change) if change.kind == SyncKind::Entry => change,
+            LiveEvent::Change(_) => return vec![],
+            LiveEvent::Resync => return vec![ServerMessage::Resync],
+        };
+
+        let entry = if change.action == ChangeAction::Deleted {
+            None
+        } else {
+            let id = JournalEntryId::new(change.id);
+            match self.journal_service.find_journal_entry_by_id(self.user_id, id).await {
+                Ok(entry) => Some(entry),
+                // Deleted since, its deletion follows.
+                Err(AppError::NotFound) => return vec![],
+                Err(error) => {
+                    error!("Failed to find changed entry {id}: {error}");
+                    return vec![ServerMessage::Resync];
+                }
+            }
+        };
+
+        self.subscriptions
+            .iter()
+            .filter(|(_, filter)| filter.matches(&change, entry.as_ref()))
+            .map(|(subscription_id, _)| ServerMessage::Change {
+                subscription_id: subscription_id.clone(),
+                change: change.clone(),
+                entry: entry.clone().map(Box::new),
+            })
+            .collect()
+    }
+}
+
+/// Runs the session until either side closes the socket, the client times out or its access
+/// token expires.
+pub async fn run<T: JournalService>(
+    mut session: Session,
+    messages: AggregatedMessageStream,
+    mut state: SocketState<T>,
+    changes: LiveStream,
+    expires_in: Duration,
+) {
+    let mut messages = pin!(messages);
+    let mut changes = pin!(changes);
+    let mut expiry = pin!(time::sleep(expires_in));
+    let mut heartbeat = time::interval(HEARTBEAT_INTERVAL);
+    let mut last_heard = Instant::now();
+
+    let reason = loop {
+        let sent = tokio::select! {
+            message = messages.next() => {
+                last_heard = Instant::now();
+                match message {
+                    Some(Ok(AggregatedMessage::Text(text))) => {
+                        let reply = state.handle_text(&text).await;
+                        send(&mut session, [reply]).await
+                    }
+                    Some(Ok(AggregatedMessage::Binary(_))) => {
+                        let error = "Only text messages are supported".to_string();
+                        let reply = ServerMessage::Error { request_id: None, status: 400, error };
+                        send(&mut session, [reply]).await
+                    }
+                    Some(Ok(AggregatedMessage::Ping(bytes))) => session.pong(&bytes).await,
+                    Some(Ok(AggregatedMessage::Pong(_))) => Ok(()),
+                    Some(Ok(AggregatedMessage::Close(reason))) => break reason,
+                    Some(Err(error)) => {
+                        warn!("Closing WebSocket after protocol error: {error}");
+                        break Some(CloseCode::Protocol.into());
+                    }
+                    None => break None,
+                }
+            }
+            Some(event) = changes.next() => {
+                let replies = state.handle_event(event).await;
+                send(&mut session, replies).await
+            }
+            _ = heartbeat.tick() => {
+                if last_heard.elapsed() > CLIENT_TIMEOUT {
+                    break Some(CloseCode::Away.into());
+                }
+                session.ping(b"").await
+            }
+            () = &mut expiry => {
+                let description = Some("Access token expired".to_string());
+                break Some(CloseReason { code: CloseCode::Policy, description });
+            }
+        };
+        if sent.is_err() {
+            return;
+        }
+    };
+
+    let _ = session.close(reason).await;
+}
+
+async fn send(
+    session: &mut Session,
+    messages: impl IntoIterator<Item = ServerMessage>,
+) -> Result<(), Closed> {
+    for message in messages {
+        session.text(serde_json::to_string(&message).unwrap_or_default()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::model::{EventTypeId, JournalEntry};
+    use crate::journal::repository::{MockEventTypeRepository, MockJournalEntryRepository};
+    use crate::journal::service::JournalServiceImpl;
+    use crate::live::model::ChangeEvent;
+    use chrono::Utc;
+    use mockall::predicate::*;
+    use serde_json::json;
+    use sqlx::types::Json;
+    use uuid::Uuid;
+
+    fn entry(user_id: UserId, event_type_id: EventTypeId, tags: &[&str]) -> JournalEntry {
+        JournalEntry {
+            id: JournalEntryId::new(Uuid::new_v4()),
+            user_id,
+            event_type_id,
+            description: None,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            occurred_at: Utc::now(),
+            ended_at: None,
+            duration_secs: None,
+            running: false,
+            recorded_at: Utc::now(),
+            values: Json(Default::default()),
+            snippet: None,
+        }
+    }
+
+    fn socket_state(
+        user_id: UserId,
+        journal_repo: MockJournalEntryRepository,
+    ) -> SocketState<JournalServiceImpl<MockEventTypeRepository, MockJournalEntryRepository>> {
+        let service = JournalServiceImpl::new(MockEventTypeRepository::new(), journal_repo);
+        SocketState::new(user_id, Arc::new(service))
+    }
+
+    #[tokio::test]
+    async fn test_commands_are_acknowledged() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let event_type_id = Uuid::new_v4();
+        let created_id = JournalEntryId::new(Uuid::new_v4());
+        let missing_id = JournalEntryId::new(Uuid::new_v4());
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_insert()
+            .withf(move |&id, entry| id == user_id && entry.tags == ["run"])
+            .return_once(move |_, _| Ok(created_id));
+        journal_repo
+            .expect_update()
+            .with(eq(user_id), eq(missing_id), always())
+            .return_once(|_, _, _| Ok(false));
+        let mut state = socket_state(user_id, journal_repo);
+
+        let mut reply = async |message: serde_json::Value| {
+            let reply = state.handle_text(&message.to_string()).await;
+            serde_json::to_value(reply).unwrap()
+        };
+
+        let create = json!({
+            "type": "create_entry", "request_id": "1",
+            "entry": {"event_type_id": event_type_id, "tags": ["run"]}
+        });
+        assert_eq!(
+            json!({"type": "ack", "request_id": "1", "id": created_id}),
+            reply(create).await
+        );
+        let update =
+            json!({"type": "update_entry", "request_id": "2", "id": missing_id, "entry": {}});
+        let reply_2 = reply(update).await;
+        assert_eq!(json!("error"), reply_2["type"]);
+        assert_eq!(json!("2"), reply_2["request_id"]);
+        assert_eq!(json!(404), reply_2["status"]);
+        let invalid = json!({
+            "type": "create_entry", "request_id": "3",
+            "entry": {"event_type_id": event_type_id, "tags": [""]}
+        });
+        assert_eq!(json!(400), reply(invalid).await["status"]);
+        let malformed = json!({"type": "delete_everything", "request_id": "4"});
+        let reply_4 = reply(malformed).await;
+        assert_eq!(json!("4"), reply_4["request_id"]);
+        assert_eq!(json!(400), reply_4["status"]);
+        let subscribe = json!({"type": "subscribe", "request_id": "5"});
+        assert_eq!(json!({"type": "ack", "request_id": "5"}), reply(subscribe).await);
+        let unsubscribe = json!({"type": "unsubscribe", "request_id": "6", "subscription_id": "5"});
+        assert_eq!(json!({"type": "ack", "request_id": "6"}), reply(unsubscribe).await);
+        let unsubscribe = json!({"type": "unsubscribe", "request_id": "7", "subscription_id": "5"});
+        assert_eq!(json!(404), reply(unsubscribe).await["status"]);
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_sent_to_matching_subscriptions() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let run_id = EventTypeId::new(Uuid::new_v4());
+        let walk_id = EventTypeId::new(Uuid::new_v4());
+        let run_entry_id = Uuid::new_v4();
+        let mut run = entry(user_id, run_id, &["outdoor", "easy"]);
+        run.id = JournalEntryId::new(run_entry_id);
+        let mut journal_repo = MockJournalEntryRepository::new();
+        journal_repo
+            .expect_find_by_id()
+            .with(eq(user_id), eq(JournalEntryId::new(run_entry_id)))
+            .times(1)
+            .return_once(move |_, _| Ok(Some(run)));
+        let mut state = socket_state(user_id, journal_repo);
+        let change = |kind, action, event_type_id| ChangeEvent {
+            user_id,
+            kind,
+            action,
+            id: run_entry_id,
+            event_type_id,
+            version: 1,
+        };
+
+        let created =
+            LiveEvent::Change(change(SyncKind::Entry, ChangeAction::Created, Some(run_id)));
+        assert!(state.handle_event(created.clone()).await.is_empty());
+
+        let subscriptions = [
+            ("all", json!({})),
+            ("runs", json!({"event_type_id": run_id})),
+            ("walks", json!({"event_type_id": walk_id})),
+            ("easy runs", json!({"event_type_id": run_id, "tags": ["easy"]})),
+            ("hard runs", json!({"event_type_id": run_id, "tags": ["easy", "hard"]})),
+        ];
+        for (request_id, filter) in subscriptions {
+            let subscribe =
+                json!({"type": "subscribe", "request_id": request_id, "filter": filter});
+            state.handle_text(&subscribe.to_string()).await;
+        }
+
+        // Pairs of the subscription ids and whether the entry is sent.
+        let sent = |messages: Vec<ServerMessage>| {
+            let mut sent: Vec<(String, bool)> = messages
+                .into_iter()
+                .map(|message| match message {
+                    ServerMessage::Change { subscription_id, entry, .. } => {
+                        (subscription_id, entry.is_some())
+                    }
+                    message => panic!("unexpected message {message:?}"),
+                })
+                .collect();
+            sent.sort();
+            sent
+        };
+        let expected = ["all", "easy runs", "runs"].map(|id| (id.to_string(), true));
+        assert_eq!(expected.to_vec(), sent(state.handle_event(created).await));
+        let deleted =
+            LiveEvent::Change(change(SyncKind::Entry, ChangeAction::Deleted, Some(run_id)));
+        let expected = ["all", "easy runs", "hard runs", "runs"].map(|id| (id.to_string(), false));
+        assert_eq!(expected.to_vec(), sent(state.handle_event(deleted).await));
+        let event_type =
+            LiveEvent::Change(change(SyncKind::EventType, ChangeAction::Updated, None));
+        assert!(state.handle_event(event_type).await.is_empty());
+        assert!(matches!(state.handle_event(LiveEvent::Resync).await[..], [ServerMessage::Resync]));
+    }
+}
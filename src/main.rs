@@ -5,9 +5,15 @@ use actix_web_httpauth::middleware::HttpAuthentication;
 use actix_web_prom::{PrometheusMetrics, PrometheusMetricsBuilder};
 use dotenvy::dotenv;
 use env_logger::Env;
+use journal_backend::idempotency::middleware::idempotency;
+use journal_backend::idempotency::repository::PgIdempotencyRepository;
+use journal_backend::idempotency::service::IdempotencyServiceImpl;
 use journal_backend::journal::handler::*;
 use journal_backend::journal::repository::{PgEventTypeRepository, PgJournalEntryRepository};
 use journal_backend::journal::service::JournalServiceImpl;
+use journal_backend::live::handler::{connect_socket, request_line, stream_changes};
+use journal_backend::live::repository::PgChangeRepository;
+use journal_backend::live::service::LiveServiceImpl;
 use journal_backend::model::Config;
 use journal_backend::user::handler::*;
 use journal_backend::user::middleware::*;
@@ -19,8 +25,16 @@ use std::env;
 use std::time::Duration;
 
 const ROOT: &str = "";
+const DEFAULT_IDEMPOTENCY_KEY_TTL_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_IDEMPOTENCY_PROCESSING_TIMEOUT_SECS: u64 = 10 * 60;
+const MAX_IMPORT_SIZE: usize = 16 * 1024 * 1024;
+const LIVE_EVENT_CAPACITY: usize = 1024;
+/// Default format of the logger, with the request line logged without the access token.
+const LOG_FORMAT: &str = r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#;
 type UserSvc = UserServiceImpl<PgUserRepository>;
 type JournalSvc = JournalServiceImpl<PgEventTypeRepository, PgJournalEntryRepository>;
+type IdempotencySvc = IdempotencyServiceImpl<PgIdempotencyRepository>;
+type LiveSvc = LiveServiceImpl;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -41,43 +55,111 @@ async fn main() -> std::io::Result<()> {
     let journal_repository = PgJournalEntryRepository::new(pool.clone());
     let journal_service =
         web::Data::new(JournalServiceImpl::new(event_repository, journal_repository));
+    let idempotency_repository = PgIdempotencyRepository::new(pool.clone());
+    let idempotency_service = web::Data::new(IdempotencyServiceImpl::new(
+        idempotency_repository,
+        config.idempotency_key_ttl,
+        config.idempotency_processing_timeout,
+        config.idempotency_max_body_size,
+    ));
+    let live_service = web::Data::new(LiveServiceImpl::new(LIVE_EVENT_CAPACITY));
+    let change_repository = PgChangeRepository::new(pool.clone());
+    let live_forwarder = live_service.clone();
+    tokio::spawn(async move { live_forwarder.run(change_repository).await });
 
     HttpServer::new(move || {
         App::new()
-            .wrap(Logger::default())
+            .wrap(Logger::new(LOG_FORMAT).custom_request_replace("request_line", request_line))
             .wrap(Cors::permissive())
             .wrap(metrics.clone())
             .app_data(user_service.clone())
             .app_data(journal_service.clone())
+            .app_data(idempotency_service.clone())
+            .app_data(live_service.clone())
             .service(
                 web::scope("/user")
                     .route(ROOT, web::post().to(register::<UserSvc>))
                     .route("/login", web::post().to(login::<UserSvc>))
                     .service(
-                        web::resource("/{user_id}")
+                        web::scope("/{user_id}")
                             .wrap(from_fn(validate_caller_id))
                             .wrap(HttpAuthentication::bearer(access_token_validator::<UserSvc>))
-                            .put(update_password::<UserSvc>),
+                            .route(ROOT, web::put().to(update_password::<UserSvc>))
+                            .route("/settings", web::get().to(find_settings::<UserSvc>))
+                            .route("/settings", web::put().to(update_settings::<UserSvc>)),
                     ),
             )
+            .route("/journal/socket", web::get().to(connect_socket::<UserSvc, JournalSvc, LiveSvc>))
+            .route("/journal/calendar/{token}.ics", web::get().to(find_calendar_feed::<JournalSvc>))
+            .route(
+                "/journal/feeds/{token}.atom",
+                web::get().to(find_saved_search_feed::<JournalSvc>),
+            )
             .service(
                 web::scope("/journal/my")
+                    .wrap(from_fn(idempotency::<IdempotencySvc>))
                     .wrap(HttpAuthentication::bearer(access_token_validator::<UserSvc>))
+                    .route("/search", web::get().to(search::<JournalSvc>))
+                    .route("/live", web::get().to(stream_changes::<LiveSvc>))
+                    .route("/calendar/token", web::post().to(create_calendar_token::<JournalSvc>))
+                    .route("/calendar/token", web::delete().to(delete_calendar_token::<JournalSvc>))
+                    .route("/sync", web::get().to(find_changes::<JournalSvc>))
+                    .route("/sync", web::post().to(push_changes::<JournalSvc>))
+                    .service(
+                        web::resource("/import")
+                            .app_data(web::PayloadConfig::new(MAX_IMPORT_SIZE))
+                            .route(web::post().to(import_entries::<JournalSvc>)),
+                    )
                     .service(
                         web::scope("/events")
                             .route(ROOT, web::get().to(find_user_event_types::<JournalSvc>))
                             .route(ROOT, web::post().to(insert_event_type::<JournalSvc>))
                             .route("/{id}", web::get().to(find_event_type::<JournalSvc>))
                             .route("/{id}", web::put().to(update_event_type::<JournalSvc>))
-                            .route("/{id}", web::delete().to(delete_event_type::<JournalSvc>)),
+                            .route("/{id}", web::patch().to(patch_event_type::<JournalSvc>))
+                            .route("/{id}", web::delete().to(delete_event_type::<JournalSvc>))
+                            .route("/{id}/goal", web::get().to(find_goal::<JournalSvc>))
+                            .route("/{id}/goal", web::put().to(set_goal::<JournalSvc>))
+                            .route("/{id}/goal", web::delete().to(delete_goal::<JournalSvc>))
+                            .route(
+                                "/{id}/streaks",
+                                web::get().to(find_goal_progress::<JournalSvc>),
+                            ),
                     )
                     .service(
                         web::scope("/entries")
                             .route(ROOT, web::get().to(find_journal_entries::<JournalSvc>))
                             .route(ROOT, web::post().to(insert_journal_entry::<JournalSvc>))
+                            .route("/batch", web::post().to(execute_entry_batch::<JournalSvc>))
+                            .route("/counts", web::get().to(count_journal_entries::<JournalSvc>))
+                            .route("/export", web::get().to(export_journal_entries::<JournalSvc>))
+                            .route("/stats", web::get().to(find_entry_stats::<JournalSvc>))
+                            .route("/timer", web::post().to(start_timer::<JournalSvc>))
                             .route("/{id}", web::get().to(find_journal_entry::<JournalSvc>))
                             .route("/{id}", web::put().to(update_journal_entry::<JournalSvc>))
-                            .route("/{id}", web::delete().to(delete_journal_entry::<JournalSvc>)),
+                            .route("/{id}", web::patch().to(patch_journal_entry::<JournalSvc>))
+                            .route("/{id}", web::delete().to(delete_journal_entry::<JournalSvc>))
+                            .route("/{id}/stop", web::post().to(stop_timer::<JournalSvc>)),
+                    )
+                    .service(
+                        web::scope("/searches")
+                            .route(ROOT, web::get().to(find_saved_searches::<JournalSvc>))
+                            .route(ROOT, web::post().to(insert_saved_search::<JournalSvc>))
+                            .route("/{id}", web::get().to(find_saved_search::<JournalSvc>))
+                            .route("/{id}", web::put().to(update_saved_search::<JournalSvc>))
+                            .route("/{id}", web::delete().to(delete_saved_search::<JournalSvc>))
+                            .route(
+                                "/{id}/entries",
+                                web::get().to(execute_saved_search::<JournalSvc>),
+                            )
+                            .route(
+                                "/{id}/feed",
+                                web::post().to(create_saved_search_feed_token::<JournalSvc>),
+                            )
+                            .route(
+                                "/{id}/feed",
+                                web::delete().to(delete_saved_search_feed_token::<JournalSvc>),
+                            ),
                     ),
             )
     })
@@ -98,12 +180,36 @@ fn load_app_config() -> Config {
         .expect("Could not find JWT_EXPIRATION_SECS env. variable")
         .parse::<u64>()
         .expect("Could not convert string value of JWT_EXPIRATION_SECS to u64");
+    let idempotency_key_ttl_secs = env::var("IDEMPOTENCY_KEY_TTL_SECS")
+        .map(|secs| {
+            secs.parse::<u64>()
+                .expect("Could not convert string value of IDEMPOTENCY_KEY_TTL_SECS to u64")
+        })
+        .unwrap_or(DEFAULT_IDEMPOTENCY_KEY_TTL_SECS);
+    let idempotency_processing_timeout_secs = env::var("IDEMPOTENCY_PROCESSING_TIMEOUT_SECS")
+        .map(|secs| {
+            secs.parse::<u64>().expect(
+                "Could not convert string value of IDEMPOTENCY_PROCESSING_TIMEOUT_SECS to u64",
+            )
+        })
+        .unwrap_or(DEFAULT_IDEMPOTENCY_PROCESSING_TIMEOUT_SECS);
+    // The body is read before the route's extractors, so the limit can't be below any of theirs.
+    let idempotency_max_body_size = env::var("IDEMPOTENCY_MAX_BODY_SIZE")
+        .map(|size| {
+            size.parse::<usize>()
+                .expect("Could not convert string value of IDEMPOTENCY_MAX_BODY_SIZE to usize")
+        })
+        .unwrap_or(MAX_IMPORT_SIZE)
+        .max(MAX_IMPORT_SIZE);
 
     Config {
         database_url: db_url,
         db_migrate_on_start: db_migrate,
         jwt_encoding_key_secret: jwt_secret,
         jwt_exp_duration: Duration::from_secs(jwt_exp_secs),
+        idempotency_key_ttl: Duration::from_secs(idempotency_key_ttl_secs),
+        idempotency_processing_timeout: Duration::from_secs(idempotency_processing_timeout_secs),
+        idempotency_max_body_size,
     }
 }
 
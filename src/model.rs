@@ -1,7 +1,9 @@
 use actix_web::http::{StatusCode, header};
 use actix_web::{HttpResponse, HttpResponseBuilder, ResponseError};
+use chrono_tz::Tz;
 use serde::Serialize;
 use std::time::Duration;
+use validator::ValidationError;
 
 // Ideally, there should be a proc_macro_derive for this marker trait, but since it would require
 // a separate crate of a proc-macro type, the overhead of such a macro is not worth for a small
@@ -29,8 +31,18 @@ pub enum AppError {
     ProcessingError,
     #[error("some of the removed tags {0:?} are still used in journal entries")]
     TagsStillUsed(Vec<String>),
+    #[error("stored values of the fields {0:?} don't match their new definitions")]
+    FieldsStillUsed(Vec<String>),
     #[error("event type missing or some of the tags are not valid")]
     EventTypeValidation,
+    #[error("journal entry is not running")]
+    EntryNotRunning,
+    #[error("journal entry has not started yet")]
+    EntryNotStarted,
+    #[error("idempotency key was already used for a different request")]
+    IdempotencyKeyReused,
+    #[error("request with the same idempotency key is still being processed")]
+    IdempotencyKeyInProgress,
     #[error(transparent)]
     JwtValidation(#[from] jsonwebtoken::errors::Error),
     #[error(transparent)]
@@ -65,6 +77,23 @@ impl From<validator::ValidationErrors> for AppError {
     }
 }
 
+impl AppError {
+    /// Message which can be shown to the client, without the details of database and other
+    /// internal errors.
+    pub fn public_message(&self) -> String {
+        match self {
+            AppError::DatabaseError(_) | AppError::UnexpectedError(_) => {
+                let status = self.status_code();
+                match status.canonical_reason() {
+                    Some(reason) if status.is_client_error() => reason.to_lowercase(),
+                    _ => AppError::ProcessingError.to_string(),
+                }
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
 impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match *self {
@@ -73,10 +102,16 @@ impl ResponseError for AppError {
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
             AppError::JwtValidation(_) => StatusCode::UNAUTHORIZED,
             AppError::TagsStillUsed(_) => StatusCode::CONFLICT,
+            AppError::FieldsStillUsed(_) => StatusCode::CONFLICT,
             AppError::EventTypeValidation => StatusCode::BAD_REQUEST,
+            AppError::EntryNotRunning => StatusCode::CONFLICT,
+            AppError::EntryNotStarted => StatusCode::CONFLICT,
+            AppError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::IdempotencyKeyInProgress => StatusCode::CONFLICT,
             AppError::DatabaseError(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
             AppError::DatabaseError(sqlx::Error::Database(ref db_err)) => match db_err.kind() {
                 sqlx::error::ErrorKind::UniqueViolation => StatusCode::CONFLICT,
+                sqlx::error::ErrorKind::CheckViolation => StatusCode::BAD_REQUEST,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             },
             _ => StatusCode::INTERNAL_SERVER_ERROR,
@@ -90,10 +125,20 @@ impl ResponseError for AppError {
     }
 }
 
+/// Checks that the value is a known IANA time zone name, e.g. `Europe/Prague`.
+pub fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    timezone.parse::<Tz>().map(|_| ()).map_err(|_| ValidationError::new("timezone"))
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub database_url: String,
     pub db_migrate_on_start: bool,
     pub jwt_encoding_key_secret: String,
     pub jwt_exp_duration: Duration,
+    /// How long the responses of requests with an idempotency key are replayed for.
+    pub idempotency_key_ttl: Duration,
+    /// How long a request with an idempotency key may process before its key can be reused.
+    pub idempotency_processing_timeout: Duration,
+    pub idempotency_max_body_size: usize,
 }
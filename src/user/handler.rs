@@ -1,5 +1,7 @@
 use crate::model::{AppError, IdResponse};
-use crate::user::model::{LoginRequest, NewUser, UpdatePasswordRequest, UserId};
+use crate::user::model::{
+    LoginRequest, NewUser, UpdatePasswordRequest, UpdateSettingsRequest, UserId,
+};
 use crate::user::service::UserService;
 use actix_web::http::header;
 use actix_web::http::header::CacheDirective;
@@ -35,3 +37,21 @@ pub async fn update_password<T: UserService>(
         user_service.update_password(user_id.into_inner(), update.into_inner().password).await?;
     if success { Ok(HttpResponse::Ok().finish()) } else { Err(AppError::ProcessingError) }
 }
+
+pub async fn find_settings<T: UserService>(
+    user_id: web::Path<UserId>,
+    user_service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    let settings = user_service.find_settings(user_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+pub async fn update_settings<T: UserService>(
+    user_id: web::Path<UserId>,
+    settings: web::Json<UpdateSettingsRequest>,
+    user_service: web::Data<T>,
+) -> Result<HttpResponse, AppError> {
+    settings.validate().map_err(AppError::from)?;
+    user_service.update_settings(user_id.into_inner(), settings.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
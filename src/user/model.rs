@@ -1,8 +1,8 @@
-use crate::model::IdType;
+use crate::model::{IdType, validate_timezone};
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 #[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(transparent)]
@@ -50,6 +50,77 @@ pub struct UpdatePasswordRequest {
     pub password: String,
 }
 
+/// Text search configurations built into Postgres.
+const SEARCH_LANGUAGES: [&str; 29] = [
+    "arabic",
+    "armenian",
+    "basque",
+    "catalan",
+    "danish",
+    "dutch",
+    "english",
+    "finnish",
+    "french",
+    "german",
+    "greek",
+    "hindi",
+    "hungarian",
+    "indonesian",
+    "irish",
+    "italian",
+    "lithuanian",
+    "nepali",
+    "norwegian",
+    "portuguese",
+    "romanian",
+    "russian",
+    "serbian",
+    "simple",
+    "spanish",
+    "swedish",
+    "tamil",
+    "turkish",
+    "yiddish",
+];
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct UserSettings {
+    /// IANA time zone name used to interpret local dates and days of the user.
+    pub timezone: String,
+    /// Text search configuration used for stemming of the entry descriptions, e.g. `english`.
+    pub search_language: String,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self { timezone: "UTC".to_string(), search_language: default_search_language() }
+    }
+}
+
+/// Text search configuration of the users who have not chosen any.
+pub const DEFAULT_SEARCH_LANGUAGE: &str = "simple";
+
+fn default_search_language() -> String {
+    DEFAULT_SEARCH_LANGUAGE.to_string()
+}
+
+#[derive(Deserialize, Validate, Debug)]
+pub struct UpdateSettingsRequest {
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: String,
+    /// Keeps the stored search language when absent.
+    #[validate(custom(function = "validate_search_language"))]
+    pub search_language: Option<String>,
+}
+
+fn validate_search_language(language: &str) -> Result<(), ValidationError> {
+    if SEARCH_LANGUAGES.contains(&language) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("search_language"))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JwtClaims {
     pub sub: UserId,
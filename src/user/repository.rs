@@ -1,4 +1,6 @@
-use crate::user::model::{User, UserId};
+use crate::user::model::{
+    DEFAULT_SEARCH_LANGUAGE, UpdateSettingsRequest, User, UserId, UserSettings,
+};
 use async_trait::async_trait;
 use sqlx::PgPool;
 
@@ -20,6 +22,16 @@ pub trait UserRepository {
     ) -> Result<UserId, sqlx::Error>;
 
     async fn update_password(&self, id: UserId, new_password: &str) -> Result<bool, sqlx::Error>;
+
+    async fn find_settings(&self, id: UserId) -> Result<Option<UserSettings>, sqlx::Error>;
+
+    /// Inserts or replaces the settings of the user, keeping the stored search language when the
+    /// new one is not set.
+    async fn upsert_settings(
+        &self,
+        id: UserId,
+        settings: &UpdateSettingsRequest,
+    ) -> Result<(), sqlx::Error>;
 }
 
 pub struct PgUserRepository {
@@ -76,4 +88,60 @@ impl UserRepository for PgUserRepository {
             .await
             .map(|result| result.rows_affected() > 0)
     }
+
+    async fn find_settings(&self, id: UserId) -> Result<Option<UserSettings>, sqlx::Error> {
+        sqlx::query_as!(
+            UserSettings,
+            r#"SELECT timezone, search_language::text as "search_language!"
+                FROM user_settings WHERE user_id = $1"#,
+            id as UserId
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn upsert_settings(
+        &self,
+        id: UserId,
+        settings: &UpdateSettingsRequest,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let previous_language = sqlx::query_scalar!(
+            r#"SELECT search_language::text as "search_language!"
+                FROM user_settings WHERE user_id = $1 FOR UPDATE"#,
+            id as UserId
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"INSERT INTO user_settings (user_id, timezone, search_language)
+                VALUES ($1, $2, COALESCE($3::text::regconfig, 'simple'))
+                ON CONFLICT (user_id) DO UPDATE
+                SET timezone = EXCLUDED.timezone,
+                    search_language = COALESCE($3::text::regconfig, user_settings.search_language)"#,
+            id as UserId,
+            settings.timezone,
+            settings.search_language
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Existing entries are re-indexed only when the language changes, as it rewrites them all.
+        let language_changed = settings.search_language.as_deref().is_some_and(|language| {
+            language != previous_language.as_deref().unwrap_or(DEFAULT_SEARCH_LANGUAGE)
+        });
+        if language_changed {
+            sqlx::query!(
+                r#"UPDATE journal_entry SET search_language = $2::text::regconfig
+                    WHERE user_id = $1 AND search_language IS DISTINCT FROM $2::text::regconfig"#,
+                id as UserId,
+                settings.search_language
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
 }
@@ -1,5 +1,7 @@
 use crate::model::AppError;
-use crate::user::model::{JwtClaims, LoginResponse, NewUser, UserId};
+use crate::user::model::{
+    JwtClaims, LoginResponse, NewUser, UpdateSettingsRequest, UserId, UserSettings,
+};
 use crate::user::repository::UserRepository;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
@@ -17,6 +19,13 @@ pub trait UserService {
     async fn register(&self, user: NewUser) -> Result<UserId, AppError>;
     async fn login(&self, username: String, password: String) -> Result<LoginResponse, AppError>;
     async fn update_password(&self, user_id: UserId, password: String) -> Result<bool, AppError>;
+    /// Returns the settings of the user, or the defaults if the user has not stored any yet.
+    async fn find_settings(&self, user_id: UserId) -> Result<UserSettings, AppError>;
+    async fn update_settings(
+        &self,
+        user_id: UserId,
+        settings: UpdateSettingsRequest,
+    ) -> Result<(), AppError>;
     fn validate_token(&self, token: &str) -> Result<JwtClaims, AppError>;
 }
 
@@ -66,6 +75,18 @@ impl<T: UserRepository + Send + Sync> UserService for UserServiceImpl<T> {
         Ok(self.user_repository.update_password(user_id, &password_hash?).await?)
     }
 
+    async fn find_settings(&self, user_id: UserId) -> Result<UserSettings, AppError> {
+        Ok(self.user_repository.find_settings(user_id).await?.unwrap_or_default())
+    }
+
+    async fn update_settings(
+        &self,
+        user_id: UserId,
+        settings: UpdateSettingsRequest,
+    ) -> Result<(), AppError> {
+        Ok(self.user_repository.upsert_settings(user_id, &settings).await?)
+    }
+
     fn validate_token(&self, access_token: &str) -> Result<JwtClaims, AppError> {
         let jwt_claims = decode::<JwtClaims>(
             access_token,
@@ -193,6 +214,16 @@ mod tests {
         assert!(service.update_password(user_id, password.to_string()).await.unwrap())
     }
 
+    #[tokio::test]
+    async fn test_find_settings_defaults() {
+        let user_id = UserId::new(Uuid::new_v4());
+        let mut mock_repository = MockUserRepository::new();
+        mock_repository.expect_find_settings().with(eq(user_id)).return_once(|_| Ok(None));
+
+        let service = UserServiceImpl::new(mock_repository, JWT_SECRET.to_string(), JWT_DURATION);
+        assert_eq!(UserSettings::default(), service.find_settings(user_id).await.unwrap());
+    }
+
     #[test]
     fn test_validate_valid_token() {
         let user_id = UserId::new(Uuid::new_v4());
@@ -0,0 +1,88 @@
+pub mod common;
+
+use common::{
+    Channel, ContainerCommand, channel, clean_up, create_pg_pool, execute_blocking, get_pg_port,
+    start_pg_container,
+};
+use ctor::{ctor, dtor};
+use futures_util::StreamExt;
+use journal_backend::journal::model::{FieldValues, NewJournalEntry, SyncKind};
+use journal_backend::journal::repository::{
+    EventTypeRepository, JournalEntryRepository, PgEventTypeRepository, PgJournalEntryRepository,
+};
+use journal_backend::live::model::{ChangeAction, ChangeEvent};
+use journal_backend::live::repository::{ChangeRepository, PgChangeRepository};
+use journal_backend::user::repository::{PgUserRepository, UserRepository};
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use std::thread;
+use std::time::Duration;
+use tokio::time::timeout;
+
+lazy_static! {
+    static ref CMD_IN: Channel<ContainerCommand> = channel();
+    static ref PG_PORT: Channel<u16> = channel();
+    static ref STOP: Channel<()> = channel();
+}
+
+#[ctor]
+fn on_startup() {
+    thread::spawn(|| execute_blocking(start_pg_container(&CMD_IN, &PG_PORT, &STOP)));
+}
+
+#[dtor]
+fn on_destroy() {
+    clean_up(&CMD_IN, &STOP);
+}
+
+#[tokio::test]
+async fn test_listen_to_committed_changes() {
+    let port = get_pg_port(&CMD_IN, &PG_PORT).await;
+    let pool = create_pg_pool(port).await;
+    let user_id =
+        PgUserRepository::new(pool.clone()).insert("user", "pass", "email").await.unwrap();
+    let event_repo = PgEventTypeRepository::new(pool.clone());
+    let journal_repo = PgJournalEntryRepository::new(pool.clone());
+    // The listener keeps a connection of its own, while the test pool has just one.
+    let listener_pool =
+        PgPool::connect_with(pool.connect_options().as_ref().clone()).await.unwrap();
+    let mut changes = PgChangeRepository::new(listener_pool).listen().await.unwrap();
+
+    let event_id = event_repo.insert(user_id, "Run", &[], &[]).await.unwrap();
+    let entry = NewJournalEntry {
+        event_type_id: event_id,
+        description: None,
+        tags: vec![],
+        occurred_at: None,
+        ended_at: None,
+        values: FieldValues::new(),
+    };
+    let entry_id = journal_repo.insert(user_id, &entry).await.unwrap();
+    event_repo.update(user_id, event_id, "Running", &[], &[]).await.unwrap();
+    // The entry is deleted along with its event type.
+    event_repo.delete(user_id, event_id).await.unwrap();
+
+    let mut received = Vec::new();
+    for _ in 0..5 {
+        let change = timeout(Duration::from_secs(5), changes.next()).await.expect("no change");
+        received.push(change.expect("stream ended").unwrap().expect("connection lost"));
+    }
+    assert!(received.iter().all(|c| c.user_id == user_id));
+    assert!(received.windows(2).all(|c| c[0].version < c[1].version));
+    let summary: Vec<_> = received
+        .iter()
+        .map(|ChangeEvent { kind, action, id, .. }| (*kind, *action, id.to_string()))
+        .collect();
+    assert_eq!(Some(event_id), received[4].event_type_id);
+    let (event_id, entry_id) = (event_id.to_string(), entry_id.to_string());
+    assert_eq!(
+        vec![
+            (SyncKind::EventType, ChangeAction::Created, event_id.clone()),
+            (SyncKind::Entry, ChangeAction::Created, entry_id.clone()),
+            (SyncKind::EventType, ChangeAction::Updated, event_id.clone()),
+            (SyncKind::EventType, ChangeAction::Deleted, event_id),
+            (SyncKind::Entry, ChangeAction::Deleted, entry_id),
+        ],
+        summary
+    );
+}
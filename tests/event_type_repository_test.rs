@@ -5,12 +5,16 @@ use common::{
     start_pg_container,
 };
 use ctor::{ctor, dtor};
-use journal_backend::journal::model::EventType;
+use journal_backend::journal::model::{
+    EventType, EventTypeId, EventTypePatch, FieldDefinition, FieldKind, FieldValues, Goal,
+    GoalPeriod, NewJournalEntry, SearchResult,
+};
 use journal_backend::journal::repository::*;
 use journal_backend::model::AppError;
 use journal_backend::user::model::UserId;
 use journal_backend::user::repository::{PgUserRepository, UserRepository};
 use lazy_static::lazy_static;
+use sqlx::types::Json;
 use std::thread;
 use uuid::Uuid;
 
@@ -37,11 +41,14 @@ async fn test_find_by_user_id() {
 
     let user_id = fixture.user_repo.insert("user", "password", "email").await.unwrap();
     let tags = vec!["tag1".to_string(), "tag2".to_string()];
-    let id = event_repo.insert(user_id, "test_event", &tags).await.unwrap();
-    let _ = event_repo.insert(fixture.default_user_id, "other", &vec![]).await.unwrap();
+    let id = event_repo.insert(user_id, "test_event", &tags, &[]).await.unwrap();
+    let _ = event_repo.insert(fixture.default_user_id, "other", &vec![], &[]).await.unwrap();
 
     let events = event_repo.find_by_user_id(user_id).await.unwrap();
-    assert_eq!(vec![EventType { id, user_id, name: "test_event".to_string(), tags }], events);
+    assert_eq!(
+        vec![EventType { id, user_id, name: "test_event".to_string(), tags, fields: Json(vec![]) }],
+        events
+    );
 }
 
 #[tokio::test]
@@ -51,7 +58,7 @@ async fn test_insert() {
     let user_id = fixture.default_user_id;
     let tags = vec!["tag1".to_string(), "tag2".to_string()];
 
-    let event_id = event_repo.insert(user_id, "test_event", &tags).await.unwrap();
+    let event_id = event_repo.insert(user_id, "test_event", &tags, &[]).await.unwrap();
 
     let event = event_repo.find_by_id(user_id, event_id).await.unwrap().expect("not found");
     assert_eq!(user_id, event.user_id);
@@ -59,21 +66,51 @@ async fn test_insert() {
     assert_eq!(tags, event.tags);
 }
 
+#[tokio::test]
+async fn test_insert_with_fields() {
+    let fixture = setup_test().await;
+    let event_repo = &fixture.event_repo;
+    let user_id = fixture.default_user_id;
+    let fields = vec![
+        FieldDefinition {
+            name: "distance".to_string(),
+            kind: FieldKind::Number { unit: Some("km".to_string()) },
+            required: true,
+        },
+        FieldDefinition {
+            name: "surface".to_string(),
+            kind: FieldKind::Enum { values: vec!["road".to_string(), "trail".to_string()] },
+            required: false,
+        },
+    ];
+
+    let event_id = event_repo.insert(user_id, "run", &[], &fields).await.unwrap();
+
+    let event = event_repo.find_by_id(user_id, event_id).await.unwrap().expect("not found");
+    assert_eq!(fields, event.fields.0);
+}
+
 #[tokio::test]
 async fn test_update() {
     let fixture = setup_test().await;
     let event_repo = &fixture.event_repo;
     let user_id = fixture.default_user_id;
     let id = event_repo
-        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()])
+        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()], &[])
         .await
         .unwrap();
 
-    event_repo.update(user_id, id, "new_name", &vec!["new_tag".to_string()]).await.unwrap();
+    event_repo.update(user_id, id, "new_name", &vec!["new_tag".to_string()], &[]).await.unwrap();
 
     let updated = event_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
     assert_eq!(
-        EventType { id, user_id, name: "new_name".to_string(), tags: vec!["new_tag".to_string()] },
+        EventType {
+            id,
+            user_id,
+            name: "new_name".to_string(),
+            tags: vec!["new_tag".to_string()],
+            fields: Json(vec![])
+        },
         updated
     );
 }
@@ -84,13 +121,13 @@ async fn test_update_attempt_wrong_user_id() {
     let event_repo = &fixture.event_repo;
     let user_id = fixture.default_user_id;
     let id = event_repo
-        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()])
+        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()], &[])
         .await
         .unwrap();
 
     let other_user_id = UserId::new(Uuid::new_v4());
     let res_err =
-        event_repo.update(other_user_id, id, "new_name", &vec!["new_tag".to_string()]).await;
+        event_repo.update(other_user_id, id, "new_name", &vec!["new_tag".to_string()], &[]).await;
 
     assert!(matches!(res_err, Err(AppError::DatabaseError(sqlx::Error::RowNotFound))));
 }
@@ -101,16 +138,26 @@ async fn test_update_attempt_remove_used_tag() {
     let event_repo = &fixture.event_repo;
     let user_id = fixture.default_user_id;
     let id = event_repo
-        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()])
+        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()], &[])
         .await
         .unwrap();
     fixture
         .journal_repo
-        .insert(user_id, id, Some("test"), &vec!["tag2".to_string()], None)
+        .insert(
+            user_id,
+            &NewJournalEntry {
+                event_type_id: id,
+                description: Some("test".to_string()),
+                tags: vec!["tag2".to_string()],
+                occurred_at: None,
+                ended_at: None,
+                values: FieldValues::new(),
+            },
+        )
         .await
         .unwrap();
 
-    let res_err = event_repo.update(user_id, id, "new", &vec!["new".to_string()]).await;
+    let res_err = event_repo.update(user_id, id, "new", &vec!["new".to_string()], &[]).await;
     assert!(matches!(res_err, Err(AppError::TagsStillUsed(_))));
     if let Err(AppError::TagsStillUsed(tags)) = res_err {
         assert_eq!(vec!["tag2".to_string()], tags);
@@ -123,29 +170,113 @@ async fn test_update_remove_unused_tag() {
     let event_repo = &fixture.event_repo;
     let user_id = fixture.default_user_id;
     let id = event_repo
-        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()])
+        .insert(user_id, "test_event", &vec!["tag1".to_string(), "tag2".to_string()], &[])
         .await
         .unwrap();
     fixture
         .journal_repo
-        .insert(user_id, id, Some("test"), &vec!["tag1".to_string()], None)
+        .insert(
+            user_id,
+            &NewJournalEntry {
+                event_type_id: id,
+                description: Some("test".to_string()),
+                tags: vec!["tag1".to_string()],
+                occurred_at: None,
+                ended_at: None,
+                values: FieldValues::new(),
+            },
+        )
         .await
         .unwrap();
 
-    event_repo.update(user_id, id, "new", &vec!["tag1".to_string()]).await.unwrap();
+    event_repo.update(user_id, id, "new", &vec!["tag1".to_string()], &[]).await.unwrap();
     let updated = event_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
     assert_eq!(
-        EventType { id, user_id, name: "new".to_string(), tags: vec!["tag1".to_string()] },
+        EventType {
+            id,
+            user_id,
+            name: "new".to_string(),
+            tags: vec!["tag1".to_string()],
+            fields: Json(vec![])
+        },
         updated
     );
 }
 
+#[tokio::test]
+async fn test_update_fields_invalidating_stored_values() {
+    let fixture = setup_test().await;
+    let event_repo = &fixture.event_repo;
+    let user_id = fixture.default_user_id;
+    let field = |name: &str, kind: FieldKind, required: bool| FieldDefinition {
+        name: name.to_string(),
+        kind,
+        required,
+    };
+    let fields = vec![field("distance", FieldKind::Number { unit: None }, false)];
+    let id = event_repo.insert(user_id, "run", &[], &fields).await.unwrap();
+    let mut values = FieldValues::new();
+    values.insert("distance".to_string(), 5.5.into());
+    let entry = NewJournalEntry {
+        event_type_id: id,
+        description: None,
+        tags: vec![],
+        occurred_at: None,
+        ended_at: None,
+        values,
+    };
+    fixture.journal_repo.insert(user_id, &entry).await.unwrap();
+
+    // The stored value is not an integer and a new required field is missing.
+    let changed = vec![
+        field("distance", FieldKind::Integer, false),
+        field("pace", FieldKind::Number { unit: None }, true),
+    ];
+    let result = event_repo.update(user_id, id, "run", &[], &changed).await;
+    assert!(matches!(
+        result,
+        Err(AppError::FieldsStillUsed(fields)) if fields == ["values.distance", "values.pace"]
+    ));
+    let result = event_repo.update(user_id, id, "run", &[], &[]).await;
+    assert!(matches!(
+        result,
+        Err(AppError::FieldsStillUsed(fields)) if fields == ["values.distance"]
+    ));
+
+    let compatible = vec![
+        field("distance", FieldKind::Number { unit: Some("km".to_string()) }, true),
+        field("pace", FieldKind::Number { unit: None }, false),
+    ];
+    assert!(event_repo.update(user_id, id, "run", &[], &compatible).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_patch() {
+    let fixture = setup_test().await;
+    let event_repo = &fixture.event_repo;
+    let user_id = fixture.default_user_id;
+    let tags = vec!["tag1".to_string()];
+    let id = event_repo.insert(user_id, "test_event", &tags, &[]).await.unwrap();
+
+    let patch = EventTypePatch { name: Some("patched".to_string()), ..Default::default() };
+    assert!(event_repo.patch(user_id, id, patch).await.unwrap());
+    let patch = EventTypePatch { tags: Some(Some(vec!["tag2".to_string()])), ..Default::default() };
+    assert!(event_repo.patch(user_id, id, patch).await.unwrap());
+
+    let patched = event_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert_eq!("patched", patched.name);
+    assert_eq!(vec!["tag2".to_string()], patched.tags);
+    let other_user_id = UserId::new(Uuid::new_v4());
+    assert!(!event_repo.patch(other_user_id, id, EventTypePatch::default()).await.unwrap());
+}
+
 #[tokio::test]
 async fn test_delete() {
     let fixture = setup_test().await;
     let event_repo = &fixture.event_repo;
     let user_id = fixture.default_user_id;
-    let id = event_repo.insert(user_id, "test_event", &vec!["tag1".to_string()]).await.unwrap();
+    let id =
+        event_repo.insert(user_id, "test_event", &vec!["tag1".to_string()], &[]).await.unwrap();
 
     let delete_res = event_repo.delete(user_id, id).await.unwrap();
     assert!(delete_res);
@@ -154,6 +285,53 @@ async fn test_delete() {
     assert_eq!(None, found);
 }
 
+#[tokio::test]
+async fn test_upsert_and_delete_goal() {
+    let fixture = setup_test().await;
+    let event_repo = &fixture.event_repo;
+    let user_id = fixture.default_user_id;
+    let other_user_id = fixture.user_repo.insert("other", "password", "email").await.unwrap();
+    let id = event_repo.insert(user_id, "test_event", &[], &[]).await.unwrap();
+    assert_eq!(None, event_repo.find_goal(user_id, id).await.unwrap());
+
+    let goal = Goal { period: GoalPeriod::Week, target: 3 };
+    assert!(!event_repo.upsert_goal(other_user_id, id, &goal).await.unwrap());
+    assert!(event_repo.upsert_goal(user_id, id, &goal).await.unwrap());
+    let goal = Goal { period: GoalPeriod::Day, target: 1 };
+    assert!(event_repo.upsert_goal(user_id, id, &goal).await.unwrap());
+    assert_eq!(Some(goal), event_repo.find_goal(user_id, id).await.unwrap());
+    assert_eq!(None, event_repo.find_goal(other_user_id, id).await.unwrap());
+
+    assert!(!event_repo.delete_goal(other_user_id, id).await.unwrap());
+    assert!(event_repo.delete_goal(user_id, id).await.unwrap());
+    assert_eq!(None, event_repo.find_goal(user_id, id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_search() {
+    let fixture = setup_test().await;
+    let event_repo = &fixture.event_repo;
+    let user_id = fixture.default_user_id;
+    let running_id = event_repo.insert(user_id, "Running", &[], &[]).await.unwrap();
+    let swimming_id =
+        event_repo.insert(user_id, "Swimming", &["outdoor".to_string()], &[]).await.unwrap();
+    let other_user_id = fixture.user_repo.insert("other", "password", "email").await.unwrap();
+    event_repo.insert(other_user_id, "Running", &[], &[]).await.unwrap();
+
+    let ids = |results: Vec<SearchResult>| -> Vec<EventTypeId> {
+        results
+            .into_iter()
+            .filter_map(|r| match r {
+                SearchResult::EventType { id, .. } => Some(id),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(vec![running_id], ids(event_repo.search(user_id, "runnign", 10).await.unwrap()));
+    assert_eq!(vec![swimming_id], ids(event_repo.search(user_id, "swi", 10).await.unwrap()));
+    assert_eq!(vec![swimming_id], ids(event_repo.search(user_id, "outdor", 10).await.unwrap()));
+}
+
 struct TestFixture<U: UserRepository, E: EventTypeRepository, J: JournalEntryRepository> {
     user_repo: U,
     event_repo: E,
@@ -0,0 +1,90 @@
+pub mod common;
+
+use common::{
+    Channel, ContainerCommand, channel, clean_up, create_pg_pool, execute_blocking, get_pg_port,
+    start_pg_container,
+};
+use ctor::{ctor, dtor};
+use journal_backend::idempotency::model::{IdempotentRequest, StoredResponse};
+use journal_backend::idempotency::repository::{IdempotencyRepository, PgIdempotencyRepository};
+use journal_backend::user::model::UserId;
+use journal_backend::user::repository::{PgUserRepository, UserRepository};
+use lazy_static::lazy_static;
+use std::thread;
+use std::time::Duration;
+
+lazy_static! {
+    static ref CMD_IN: Channel<ContainerCommand> = channel();
+    static ref PG_PORT: Channel<u16> = channel();
+    static ref STOP: Channel<()> = channel();
+}
+
+#[ctor]
+fn on_startup() {
+    thread::spawn(|| execute_blocking(start_pg_container(&CMD_IN, &PG_PORT, &STOP)));
+}
+
+#[dtor]
+fn on_destroy() {
+    clean_up(&CMD_IN, &STOP);
+}
+
+const TTL: Duration = Duration::from_secs(60);
+const PROCESSING_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[tokio::test]
+async fn test_reserve_complete_and_replay() {
+    let (repo, user_id) = setup_test().await;
+
+    assert_eq!(None, repo.reserve(user_id, "key", b"hash", TTL, PROCESSING_TIMEOUT).await.unwrap());
+    let in_progress = IdempotentRequest { request_hash: b"hash".to_vec(), response: None };
+    assert_eq!(
+        Some(in_progress),
+        repo.reserve(user_id, "key", b"other", TTL, PROCESSING_TIMEOUT).await.unwrap()
+    );
+
+    let response = StoredResponse {
+        status: 200,
+        content_type: Some("application/json".to_string()),
+        body: br#"{"id":1}"#.to_vec(),
+    };
+    repo.complete(user_id, "key", &response).await.unwrap();
+    let completed = IdempotentRequest { request_hash: b"hash".to_vec(), response: Some(response) };
+    assert_eq!(
+        Some(completed),
+        repo.reserve(user_id, "key", b"hash", TTL, PROCESSING_TIMEOUT).await.unwrap()
+    );
+
+    repo.delete(user_id, "key").await.unwrap();
+    assert_eq!(None, repo.reserve(user_id, "key", b"hash", TTL, PROCESSING_TIMEOUT).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_reserve_expired_key() {
+    let (repo, user_id) = setup_test().await;
+
+    assert_eq!(
+        None,
+        repo.reserve(user_id, "key", b"hash", Duration::ZERO, PROCESSING_TIMEOUT).await.unwrap()
+    );
+    assert_eq!(
+        None,
+        repo.reserve(user_id, "key", b"other", TTL, PROCESSING_TIMEOUT).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_reserve_key_of_interrupted_request() {
+    let (repo, user_id) = setup_test().await;
+
+    assert_eq!(None, repo.reserve(user_id, "key", b"hash", TTL, PROCESSING_TIMEOUT).await.unwrap());
+    assert_eq!(None, repo.reserve(user_id, "key", b"other", TTL, Duration::ZERO).await.unwrap());
+}
+
+async fn setup_test() -> (impl IdempotencyRepository, UserId) {
+    let port = get_pg_port(&CMD_IN, &PG_PORT).await;
+    let pool = create_pg_pool(port).await;
+    let user_id =
+        PgUserRepository::new(pool.clone()).insert("user", "pass", "email").await.unwrap();
+    (PgIdempotencyRepository::new(pool), user_id)
+}
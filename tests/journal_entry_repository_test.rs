@@ -1,22 +1,33 @@
 pub mod common;
 
-use chrono::Utc;
+use chrono::{DateTime, Datelike, DurationRound, NaiveDateTime, TimeDelta, Utc};
 use common::{
     Channel, ContainerCommand, channel, clean_up, create_pg_pool, execute_blocking, get_pg_port,
     start_pg_container,
 };
 use ctor::{ctor, dtor};
-use journal_backend::journal::model::{EventTypeId, JournalEntry, SearchFilter, SortOrder};
+use futures_util::TryStreamExt;
+use journal_backend::journal::model::{
+    BatchItemResult, BatchMode, BatchUpdate, ChangeCursor, CountGroup, CountsQuery, Cursor,
+    EntryBatch, EntryCount, EventTypeData, EventTypeId, ExportEntry, FieldDefinition, FieldKind,
+    FieldStats, FieldValues, GoalPeriod, ImportEntry, JournalEntry, JournalEntryId,
+    JournalEntryPatch, JournalEntryUpdate, NewJournalEntry, NewTimer, Percentile, PeriodCounts,
+    SavedSearch, SavedSearchData, SearchFilter, SearchResult, SortOrder, StatsQuery, SyncChange,
+    SyncChangeResult, SyncChanges, SyncKind, SyncStatus, TimeBucket,
+};
 use journal_backend::journal::repository::{
     EventTypeRepository, JournalEntryRepository, PgEventTypeRepository, PgJournalEntryRepository,
 };
 use journal_backend::model::AppError;
-use journal_backend::user::model::UserId;
+use journal_backend::user::model::{UpdateSettingsRequest, UserId};
 use journal_backend::user::repository::{PgUserRepository, UserRepository};
 use lazy_static::lazy_static;
-use std::ops::Sub;
+use serde_json::json;
+use sqlx::types::Json;
+use std::ops::{Add, Sub};
 use std::thread;
 use std::time::Duration;
+use uuid::Uuid;
 
 lazy_static! {
     static ref CMD_IN: Channel<ContainerCommand> = channel();
@@ -41,9 +52,13 @@ async fn test_insert() {
     let user_id = fixture.default_user_id;
     let event_id = fixture.default_event_type_id;
     let tags = vec!["tag1".to_string(), "tag2".to_string()];
-    let now = Utc::now();
+    let recorded_after = now();
+    let occurred_at = recorded_after.sub(Duration::from_secs(3600));
 
-    let id = journal_repo.insert(user_id, event_id, Some("test"), &tags, Some(now)).await.unwrap();
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &tags, Some(occurred_at)))
+        .await
+        .unwrap();
 
     let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
     let expected = JournalEntry {
@@ -52,9 +67,16 @@ async fn test_insert() {
         event_type_id: event_id,
         description: Some("test".to_string()),
         tags,
-        created_at: now,
+        occurred_at,
+        ended_at: None,
+        duration_secs: None,
+        running: false,
+        recorded_at: entry.recorded_at,
+        values: Json(FieldValues::new()),
+        snippet: None,
     };
     assert_eq!(expected, entry);
+    assert!(entry.recorded_at >= recorded_after);
 }
 
 #[tokio::test]
@@ -64,9 +86,10 @@ async fn test_insert_with_invalid_tag() {
     let user_id = fixture.default_user_id;
     let event_id = fixture.default_event_type_id;
     let tags = vec!["tag1".to_string(), "new1".to_string()];
-    let now = Utc::now();
+    let now = now();
 
-    let res_err = journal_repo.insert(user_id, event_id, Some("test"), &tags, Some(now)).await;
+    let res_err =
+        journal_repo.insert(user_id, &new_entry(event_id, Some("test"), &tags, Some(now))).await;
     assert!(matches!(res_err, Err(AppError::EventTypeValidation)));
 }
 
@@ -77,11 +100,16 @@ async fn test_update() {
     let user_id = fixture.default_user_id;
     let event_id = fixture.default_event_type_id;
     let tags = vec!["tag1".to_string(), "tag2".to_string()];
-    let now = Utc::now();
-    let id =
-        journal_repo.insert(user_id, event_id, Some("test"), &Vec::new(), Some(now)).await.unwrap();
+    let now = now();
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &Vec::new(), Some(now)))
+        .await
+        .unwrap();
 
-    let update_res = journal_repo.update(user_id, id, Some("updated"), &tags).await.unwrap();
+    let update_res = journal_repo
+        .update(user_id, id, &entry_update(None, Some("updated"), &tags, None))
+        .await
+        .unwrap();
     assert!(update_res);
 
     let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
@@ -91,7 +119,13 @@ async fn test_update() {
         event_type_id: event_id,
         description: Some("updated".to_string()),
         tags,
-        created_at: now,
+        occurred_at: now,
+        ended_at: None,
+        duration_secs: None,
+        running: false,
+        recorded_at: entry.recorded_at,
+        values: Json(FieldValues::new()),
+        snippet: None,
     };
     assert_eq!(expected, entry);
 }
@@ -103,21 +137,70 @@ async fn test_update_with_invalid_tag() {
     let user_id = fixture.default_user_id;
     let event_id = fixture.default_event_type_id;
     let tags = vec!["tag1".to_string(), "unknown".to_string()];
-    let now = Utc::now();
-    let id =
-        journal_repo.insert(user_id, event_id, Some("test"), &Vec::new(), Some(now)).await.unwrap();
+    let now = now();
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &Vec::new(), Some(now)))
+        .await
+        .unwrap();
 
-    let res_err = journal_repo.update(user_id, id, Some("updated"), &tags).await;
+    let res_err =
+        journal_repo.update(user_id, id, &entry_update(None, Some("updated"), &tags, None)).await;
     assert!(matches!(res_err, Err(AppError::EventTypeValidation)));
 }
 
+#[tokio::test]
+async fn test_patch() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &Vec::new(), None))
+        .await
+        .unwrap();
+
+    let patch =
+        JournalEntryPatch { description: Some(Some("patched".to_string())), ..Default::default() };
+    assert!(journal_repo.patch(user_id, id, patch).await.unwrap());
+    let patch =
+        JournalEntryPatch { tags: Some(Some(vec!["tag1".to_string()])), ..Default::default() };
+    assert!(journal_repo.patch(user_id, id, patch).await.unwrap());
+
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert_eq!(Some("patched"), entry.description.as_deref());
+    assert_eq!(vec!["tag1".to_string()], entry.tags);
+    let missing_id = JournalEntryId::new(Uuid::new_v4());
+    assert!(!journal_repo.patch(user_id, missing_id, JournalEntryPatch::default()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_update_entry_of_other_user() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let other_user_id = fixture.user_repo.insert("other", "other", "other").await.unwrap();
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &Vec::new(), None))
+        .await
+        .unwrap();
+
+    let update = entry_update(None, Some("updated"), &["unknown".to_string()], None);
+    assert!(!journal_repo.update(other_user_id, id, &update).await.unwrap());
+    let missing_id = JournalEntryId::new(Uuid::new_v4());
+    assert!(!journal_repo.update(user_id, missing_id, &update).await.unwrap());
+}
+
 #[tokio::test]
 async fn test_delete() {
     let fixture = setup_test().await;
     let journal_repo = &fixture.journal_repo;
     let user_id = fixture.default_user_id;
     let event_id = fixture.default_event_type_id;
-    let id = journal_repo.insert(user_id, event_id, Some("test"), &Vec::new(), None).await.unwrap();
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &Vec::new(), None))
+        .await
+        .unwrap();
 
     let delete_res = journal_repo.delete(user_id, id).await.unwrap();
     assert!(delete_res);
@@ -134,20 +217,34 @@ async fn test_find_empty_filters() {
     let event_type_id = fixture.default_event_type_id;
     let tags = vec!["tag1".to_string()];
     let description = Some("test".to_string());
-    let created_at = Utc::now();
+    let occurred_at = now();
     let id = journal_repo
-        .insert(user_id, event_type_id, Some("test"), &tags, Some(created_at))
+        .insert(user_id, &new_entry(event_type_id, Some("test"), &tags, Some(occurred_at)))
         .await
         .unwrap();
 
     // entry for another user that shouldn't be found by filter
     let other_user = fixture.user_repo.insert("other", "other", "other").await.unwrap();
-    let other_event = fixture.event_repo.insert(other_user, "other", &vec![]).await.unwrap();
-    let _ = journal_repo.insert(other_user, other_event, None, &vec![], None).await.unwrap();
+    let other_event = fixture.event_repo.insert(other_user, "other", &vec![], &[]).await.unwrap();
+    let _ =
+        journal_repo.insert(other_user, &new_entry(other_event, None, &[], None)).await.unwrap();
 
     let entries = journal_repo.find(user_id, &SearchFilter::default()).await.unwrap();
     assert_eq!(
-        vec![JournalEntry { id, user_id, event_type_id, description, tags, created_at }],
+        vec![JournalEntry {
+            id,
+            user_id,
+            event_type_id,
+            description,
+            tags,
+            occurred_at,
+            ended_at: None,
+            duration_secs: None,
+            running: false,
+            recorded_at: entries[0].recorded_at,
+            values: Json(FieldValues::new()),
+            snippet: None,
+        }],
         entries
     );
 }
@@ -161,21 +258,30 @@ async fn test_find_all_filters() {
     let tags = vec!["tag1".to_string(), "tag2".to_string()];
     let description = Some("test".to_string());
     let one_minute = Duration::from_secs(60);
-    let created_at = Utc::now().sub(one_minute);
+    let occurred_at = now().sub(one_minute);
     let id = journal_repo
-        .insert(user_id, event_type_id, Some("test"), &tags, Some(created_at))
+        .insert(user_id, &new_entry(event_type_id, Some("test"), &tags, Some(occurred_at)))
         .await
         .unwrap();
 
     // entry with other event type that shouldn't be found by filter
-    let other_event = fixture.event_repo.insert(user_id, "other", &vec![]).await.unwrap();
-    let _ = journal_repo.insert(user_id, other_event, None, &vec![], None).await.unwrap();
+    let other_event = fixture.event_repo.insert(user_id, "other", &vec![], &[]).await.unwrap();
+    let _ = journal_repo.insert(user_id, &new_entry(other_event, None, &[], None)).await.unwrap();
 
     let filter = SearchFilter {
+        q: Some("test".to_string()),
+        query: Some(r#"type:Default_Event tag:tag1 -tag:tag3 "test""#.to_string()),
         event_type_id: Some(event_type_id),
-        tags: vec!["tag1".to_string()],
-        before: Some(Utc::now()),
-        after: Some(created_at.sub(one_minute)),
+        tags_all: vec!["tag1".to_string()],
+        tags_any: vec!["tag2".to_string(), "tag3".to_string()],
+        tags_none: vec!["tag3".to_string()],
+        before: Some(now()),
+        after: Some(occurred_at.sub(one_minute)),
+        overlaps_from: Some(occurred_at.sub(one_minute)),
+        overlaps_to: Some(now()),
+        on: Some(occurred_at.date_naive()),
+        from_date: Some(occurred_at.date_naive()),
+        to_date: Some(occurred_at.date_naive()),
         sort: Some(SortOrder::Desc),
         offset: Some(0),
         limit: Some(10),
@@ -183,11 +289,967 @@ async fn test_find_all_filters() {
 
     let entries = journal_repo.find(user_id, &filter).await.unwrap();
     assert_eq!(
-        vec![JournalEntry { id, user_id, event_type_id, description, tags, created_at }],
+        vec![JournalEntry {
+            id,
+            user_id,
+            event_type_id,
+            description,
+            tags,
+            occurred_at,
+            ended_at: None,
+            duration_secs: None,
+            running: false,
+            recorded_at: entries[0].recorded_at,
+            values: Json(FieldValues::new()),
+            snippet: Some("<b>test</b>".to_string()),
+        }],
         entries
     );
 }
 
+#[tokio::test]
+async fn test_update_move_to_other_event_type() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let tags = vec!["tag1".to_string()];
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &tags, None))
+        .await
+        .unwrap();
+    let other_event = fixture.event_repo.insert(user_id, "other", &tags, &[]).await.unwrap();
+    let corrected_at = now().sub(Duration::from_secs(3600));
+
+    let update_res = journal_repo
+        .update(
+            user_id,
+            id,
+            &entry_update(Some(other_event), Some("moved"), &tags, Some(corrected_at)),
+        )
+        .await
+        .unwrap();
+    assert!(update_res);
+
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    let expected = JournalEntry {
+        id,
+        user_id,
+        event_type_id: other_event,
+        description: Some("moved".to_string()),
+        tags,
+        occurred_at: corrected_at,
+        ended_at: None,
+        duration_secs: None,
+        running: false,
+        recorded_at: entry.recorded_at,
+        values: Json(FieldValues::new()),
+        snippet: None,
+    };
+    assert_eq!(expected, entry);
+}
+
+#[tokio::test]
+async fn test_update_move_with_tag_missing_in_target_event_type() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let tags = vec!["tag2".to_string()];
+    let id = journal_repo
+        .insert(user_id, &new_entry(event_id, Some("test"), &tags, None))
+        .await
+        .unwrap();
+    let other_event =
+        fixture.event_repo.insert(user_id, "other", &["tag1".to_string()], &[]).await.unwrap();
+
+    let res_err =
+        journal_repo.update(user_id, id, &entry_update(Some(other_event), None, &tags, None)).await;
+    assert!(matches!(res_err, Err(AppError::EventTypeValidation)));
+}
+
+#[tokio::test]
+async fn test_insert_with_duration() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let occurred_at = now().sub(Duration::from_secs(3600));
+    let ended_at = occurred_at.add(Duration::from_secs(1800));
+    let entry = NewJournalEntry {
+        ended_at: Some(ended_at),
+        ..new_entry(event_id, None, &[], Some(occurred_at))
+    };
+
+    let id = journal_repo.insert(user_id, &entry).await.unwrap();
+
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert_eq!(Some(ended_at), entry.ended_at);
+    assert_eq!(Some(1800), entry.duration_secs);
+    assert!(!entry.running);
+}
+
+#[tokio::test]
+async fn test_insert_ended_before_start_fails() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let occurred_at = now();
+    let ended_at = occurred_at.sub(Duration::from_secs(60));
+    let entry = NewJournalEntry {
+        ended_at: Some(ended_at),
+        ..new_entry(event_id, None, &[], Some(occurred_at))
+    };
+
+    let res_err = journal_repo.insert(user_id, &entry).await;
+    assert!(matches!(
+        res_err,
+        Err(AppError::DatabaseError(sqlx::Error::Database(e))) if e.is_check_violation()
+    ));
+}
+
+#[tokio::test]
+async fn test_start_and_stop_timer() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id = fixture.default_event_type_id;
+    let timer = NewTimer {
+        event_type_id,
+        description: Some("run".to_string()),
+        tags: vec![],
+        values: FieldValues::new(),
+    };
+
+    let id = journal_repo.start_timer(user_id, &timer).await.unwrap();
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert!(entry.running);
+    assert_eq!(None, entry.ended_at);
+
+    assert!(journal_repo.stop_timer(user_id, id).await.unwrap());
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert!(!entry.running);
+    assert!(entry.ended_at.is_some_and(|ended_at| ended_at > entry.occurred_at));
+    assert!(entry.duration_secs.is_some());
+
+    // already stopped
+    assert!(!journal_repo.stop_timer(user_id, id).await.unwrap());
+
+    // The entry can't end before it starts.
+    let id = journal_repo.start_timer(user_id, &timer).await.unwrap();
+    let update = entry_update(None, None, &[], Some(now() + TimeDelta::hours(1)));
+    assert!(journal_repo.update(user_id, id, &update).await.unwrap());
+    assert!(!journal_repo.stop_timer(user_id, id).await.unwrap());
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert!(entry.running);
+}
+
+#[tokio::test]
+async fn test_find_overlapping() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id = fixture.default_event_type_id;
+    let one_hour = Duration::from_secs(3600);
+    let start = now().sub(one_hour * 3);
+    let span = NewJournalEntry {
+        ended_at: Some(start.add(one_hour)),
+        ..new_entry(event_type_id, None, &[], Some(start))
+    };
+    let span_id = journal_repo.insert(user_id, &span).await.unwrap();
+    let point = new_entry(event_type_id, None, &[], Some(start.add(one_hour * 2)));
+    let _ = journal_repo.insert(user_id, &point).await.unwrap();
+    let timer =
+        NewTimer { event_type_id, description: None, tags: vec![], values: FieldValues::new() };
+    let running_id = journal_repo.start_timer(user_id, &timer).await.unwrap();
+
+    let filter = SearchFilter {
+        overlaps_from: Some(start.add(one_hour / 2)),
+        overlaps_to: Some(start.add(one_hour + one_hour / 2)),
+        ..Default::default()
+    };
+    let entries = journal_repo.find(user_id, &filter).await.unwrap();
+    assert_eq!(vec![span_id], entries.iter().map(|e| e.id).collect::<Vec<_>>());
+
+    let filter = SearchFilter { overlaps_from: Some(now()), ..Default::default() };
+    let entries = journal_repo.find(user_id, &filter).await.unwrap();
+    assert_eq!(vec![running_id], entries.iter().map(|e| e.id).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_insert_with_field_values() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id =
+        fixture.event_repo.insert(user_id, "weight", &[], &weight_fields()).await.unwrap();
+    let values = FieldValues::from_iter([("weight".to_string(), json!(81.5))]);
+    let entry =
+        NewJournalEntry { values: values.clone(), ..new_entry(event_type_id, None, &[], None) };
+
+    let id = journal_repo.insert(user_id, &entry).await.unwrap();
+
+    let entry = journal_repo.find_by_id(user_id, id).await.unwrap().expect("not found");
+    assert_eq!(values, entry.values.0);
+}
+
+#[tokio::test]
+async fn test_insert_with_invalid_field_values() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id =
+        fixture.event_repo.insert(user_id, "weight", &[], &weight_fields()).await.unwrap();
+    let values = FieldValues::from_iter([
+        ("weight".to_string(), json!("heavy")),
+        ("unknown".to_string(), json!(1)),
+    ]);
+    let entry = NewJournalEntry { values, ..new_entry(event_type_id, None, &[], None) };
+
+    let res_err = journal_repo.insert(user_id, &entry).await;
+    let Err(AppError::Validation(fields)) = res_err else {
+        panic!("unexpected result: {res_err:?}");
+    };
+    let fields: Vec<String> = fields.into_iter().map(|f| f.0).collect();
+    assert_eq!(vec!["values.unknown".to_string(), "values.weight".to_string()], fields);
+}
+
+#[tokio::test]
+async fn test_update_with_missing_required_field_value() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id =
+        fixture.event_repo.insert(user_id, "weight", &[], &weight_fields()).await.unwrap();
+    let values = FieldValues::from_iter([("weight".to_string(), json!(80))]);
+    let entry = NewJournalEntry { values, ..new_entry(event_type_id, None, &[], None) };
+    let id = journal_repo.insert(user_id, &entry).await.unwrap();
+
+    let res_err = journal_repo.update(user_id, id, &entry_update(None, None, &[], None)).await;
+    assert!(matches!(res_err, Err(AppError::Validation(_))));
+}
+
+#[tokio::test]
+async fn test_stats_by_local_day() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id =
+        fixture.event_repo.insert(user_id, "weight", &[], &weight_fields()).await.unwrap();
+    // The first entry falls on the 2nd of March in Prague.
+    for (occurred_at, weight) in
+        [("2024-03-01T23:30:00Z", 80), ("2024-03-02T10:00:00Z", 82), ("2024-03-03T10:00:00Z", 84)]
+    {
+        let values = FieldValues::from_iter([("weight".to_string(), json!(weight))]);
+        let occurred_at = Some(occurred_at.parse().unwrap());
+        let entry = NewJournalEntry { values, ..new_entry(event_type_id, None, &[], occurred_at) };
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+    let stats = StatsQuery {
+        field: "weight".to_string(),
+        bucket: TimeBucket::Day,
+        timezone: Some("Europe/Prague".to_string()),
+        percentiles: vec![0.5],
+    };
+
+    let result = journal_repo.stats(user_id, &SearchFilter::default(), &stats).await.unwrap();
+
+    let expected = vec![
+        FieldStats {
+            bucket: "2024-03-02T00:00:00".parse().unwrap(),
+            count: 2,
+            sum: 162.0,
+            avg: 81.0,
+            min: 80.0,
+            max: 82.0,
+            percentiles: vec![Percentile { percentile: 0.5, value: 81.0 }],
+        },
+        FieldStats {
+            bucket: "2024-03-03T00:00:00".parse().unwrap(),
+            count: 1,
+            sum: 84.0,
+            avg: 84.0,
+            min: 84.0,
+            max: 84.0,
+            percentiles: vec![Percentile { percentile: 0.5, value: 84.0 }],
+        },
+    ];
+    assert_eq!(expected, result);
+}
+
+#[tokio::test]
+async fn test_counts_by_week_event_type_and_tag() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let tags = ["a".to_string(), "b".to_string()];
+    let run_id = fixture.event_repo.insert(user_id, "run", &tags, &[]).await.unwrap();
+    let swim_id = fixture.event_repo.insert(user_id, "swim", &[], &[]).await.unwrap();
+    // Monday 2024-03-04 starts the second week.
+    for (event_type_id, tags, occurred_at) in [
+        (run_id, &tags[..], "2024-03-01T10:00:00Z"),
+        (run_id, &tags[..1], "2024-03-02T10:00:00Z"),
+        (swim_id, &[][..], "2024-03-03T10:00:00Z"),
+        (run_id, &tags[1..], "2024-03-04T10:00:00Z"),
+    ] {
+        let entry = new_entry(event_type_id, None, tags, Some(occurred_at.parse().unwrap()));
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+    let first_week = "2024-02-26T00:00:00".parse().unwrap();
+    let second_week = "2024-03-04T00:00:00".parse().unwrap();
+    let count = |bucket, event_type_id: Option<EventTypeId>, tag: Option<&str>, count| EntryCount {
+        bucket,
+        event_type_id,
+        tag: tag.map(str::to_string),
+        count,
+    };
+
+    let counts = CountsQuery { bucket: TimeBucket::Week, timezone: None, group_by: vec![] };
+    let result = journal_repo.counts(user_id, &SearchFilter::default(), &counts).await.unwrap();
+    assert_eq!(vec![count(first_week, None, None, 3), count(second_week, None, None, 1)], result);
+
+    let counts = CountsQuery {
+        bucket: TimeBucket::Week,
+        timezone: None,
+        group_by: vec![CountGroup::EventType, CountGroup::Tag],
+    };
+    let filter = SearchFilter { event_type_id: Some(run_id), ..Default::default() };
+    let result = journal_repo.counts(user_id, &filter, &counts).await.unwrap();
+    assert_eq!(
+        vec![
+            count(first_week, Some(run_id), Some("a"), 2),
+            count(first_week, Some(run_id), Some("b"), 1),
+            count(second_week, Some(run_id), Some("b"), 1),
+        ],
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_find_and_count_by_local_day_across_dst() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id = fixture.default_event_type_id;
+    let settings =
+        UpdateSettingsRequest { timezone: "Europe/Prague".to_string(), search_language: None };
+    fixture.user_repo.upsert_settings(user_id, &settings).await.unwrap();
+    // Clocks in Prague move forward on 2024-03-31, so the local day lasts 23 hours.
+    for occurred_at in [
+        "2024-03-30T22:30:00Z",
+        "2024-03-30T23:30:00Z",
+        "2024-03-31T21:30:00Z",
+        "2024-03-31T22:30:00Z",
+    ] {
+        let entry =
+            new_entry(event_type_id, Some(occurred_at), &[], Some(occurred_at.parse().unwrap()));
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+
+    let filter = SearchFilter {
+        on: Some("2024-03-31".parse().unwrap()),
+        sort: Some(SortOrder::Asc),
+        ..Default::default()
+    };
+    let entries = journal_repo.find(user_id, &filter).await.unwrap();
+    let descriptions: Vec<_> = entries.iter().filter_map(|e| e.description.as_deref()).collect();
+    assert_eq!(vec!["2024-03-30T23:30:00Z", "2024-03-31T21:30:00Z"], descriptions);
+
+    let filter = SearchFilter {
+        from_date: Some("2024-03-31".parse().unwrap()),
+        to_date: Some("2024-04-01".parse().unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(3, journal_repo.find(user_id, &filter).await.unwrap().len());
+
+    let counts = CountsQuery { bucket: TimeBucket::Day, timezone: None, group_by: vec![] };
+    let result = journal_repo.counts(user_id, &SearchFilter::default(), &counts).await.unwrap();
+    let result: Vec<_> = result.into_iter().map(|c| (c.bucket.to_string(), c.count)).collect();
+    assert_eq!(
+        vec![
+            ("2024-03-30 00:00:00".to_string(), 1),
+            ("2024-03-31 00:00:00".to_string(), 2),
+            ("2024-04-01 00:00:00".to_string(), 1),
+        ],
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_count_per_period() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id = fixture.default_event_type_id;
+    for occurred_at in [Some(now()), Some("2024-03-01T10:00:00Z".parse().unwrap()), None] {
+        let entry = new_entry(event_type_id, None, &[], occurred_at);
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+
+    let result = journal_repo.count_per_period(user_id, event_type_id, GoalPeriod::Month).await;
+
+    let current_period = now().date_naive().with_day(1).unwrap();
+    let PeriodCounts { current_period: found_period, counts } = result.unwrap();
+    assert_eq!(current_period, found_period);
+    assert_eq!(vec![("2024-03-01".parse().unwrap(), 1), (current_period, 2)], counts);
+}
+
+#[tokio::test]
+async fn test_find_full_text() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id = fixture.default_event_type_id;
+    for description in ["Went running in the park", "Dentists checkup", "Visited the dentist again"]
+    {
+        let entry = new_entry(event_type_id, Some(description), &[], None);
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+    let search = |q: &str| SearchFilter { q: Some(q.to_string()), ..Default::default() };
+
+    // The default `simple` configuration doesn't stem the words.
+    assert!(journal_repo.find(user_id, &search("run")).await.unwrap().is_empty());
+
+    let settings = UpdateSettingsRequest {
+        timezone: "UTC".to_string(),
+        search_language: Some("english".to_string()),
+    };
+    fixture.user_repo.upsert_settings(user_id, &settings).await.unwrap();
+    assert_eq!(1, journal_repo.find(user_id, &search("run")).await.unwrap().len());
+
+    let entries = journal_repo.find(user_id, &search("visit dentist")).await.unwrap();
+    assert_eq!(
+        vec!["<b>Visited</b> the <b>dentist</b> again"],
+        entries.iter().filter_map(|e| e.snippet.as_deref()).collect::<Vec<_>>()
+    );
+
+    // Entries matching more terms rank higher.
+    let entries = journal_repo.find(user_id, &search("dentist or visit")).await.unwrap();
+    let descriptions: Vec<_> = entries.iter().filter_map(|e| e.description.as_deref()).collect();
+    assert_eq!(vec!["Visited the dentist again", "Dentists checkup"], descriptions);
+
+    let entries = journal_repo.find(user_id, &search("dentist -checkup")).await.unwrap();
+    assert_eq!(1, entries.len());
+}
+
+#[tokio::test]
+async fn test_search_similar_and_prefix() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_type_id = fixture.default_event_type_id;
+    let tags = ["tag1".to_string()];
+    for (description, tags) in [
+        ("Appointment with the dentist", &[][..]),
+        ("Morning run", &[][..]),
+        ("Evening walk", &tags[..]),
+    ] {
+        let entry = new_entry(event_type_id, Some(description), tags, None);
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+    let descriptions = |results: Vec<SearchResult>| -> Vec<String> {
+        results
+            .into_iter()
+            .filter_map(|r| match r {
+                SearchResult::Entry { description, .. } => description,
+                _ => None,
+            })
+            .collect()
+    };
+
+    let misspelled = journal_repo.search(user_id, "dentsit", 10).await.unwrap();
+    assert_eq!(vec!["Appointment with the dentist"], descriptions(misspelled));
+    let prefix = journal_repo.search(user_id, "mor", 10).await.unwrap();
+    assert_eq!(vec!["Morning run"], descriptions(prefix));
+    let by_tag = journal_repo.search(user_id, "TAG", 10).await.unwrap();
+    assert_eq!(vec!["Evening walk"], descriptions(by_tag));
+    assert!(journal_repo.search(user_id, "%", 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_find_by_tag_queries() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let tags: Vec<String> = ["a", "b", "c", "d"].into_iter().map(String::from).collect();
+    let event_type_id = fixture.event_repo.insert(user_id, "tagged", &tags, &[]).await.unwrap();
+    for (description, entry_tags) in
+        [("ab", ["a", "b"].as_slice()), ("abc", &["a", "b", "c"]), ("bd", &["b", "d"])]
+    {
+        let entry_tags: Vec<String> = entry_tags.iter().map(|t| t.to_string()).collect();
+        let entry = new_entry(event_type_id, Some(description), &entry_tags, None);
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+    let find = |tags_all: &[&str], tags_any: &[&str], tags_none: &[&str]| {
+        let to_vec = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+        SearchFilter {
+            tags_all: to_vec(tags_all),
+            tags_any: to_vec(tags_any),
+            tags_none: to_vec(tags_none),
+            sort: Some(SortOrder::Asc),
+            ..Default::default()
+        }
+    };
+    let descriptions = |entries: Vec<JournalEntry>| -> Vec<String> {
+        entries.into_iter().filter_map(|e| e.description).collect()
+    };
+
+    let result = journal_repo.find(user_id, &find(&["b"], &["a", "d"], &["c"])).await.unwrap();
+    assert_eq!(vec!["ab", "bd"], descriptions(result));
+    let result = journal_repo.find(user_id, &find(&[], &["c", "d"], &[])).await.unwrap();
+    assert_eq!(vec!["abc", "bd"], descriptions(result));
+    let result = journal_repo.find(user_id, &find(&[], &[], &["a"])).await.unwrap();
+    assert_eq!(vec!["bd"], descriptions(result));
+}
+
+#[tokio::test]
+async fn test_find_by_search_query() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let tags: Vec<String> = ["morning", "indoor"].into_iter().map(String::from).collect();
+    let run_id = fixture.event_repo.insert(user_id, "Run", &tags, &[]).await.unwrap();
+    let swim_id = fixture.event_repo.insert(user_id, "Swim", &tags, &[]).await.unwrap();
+    for (event_type_id, description, tags, occurred_at) in [
+        (run_id, "Knee pain after the run", &tags[..1], "2026-01-02T07:00:00Z"),
+        (run_id, "Treadmill, knee pain", &tags[..], "2026-01-03T07:00:00Z"),
+        (run_id, "Pain in the knee", &tags[..1], "2026-01-04T07:00:00Z"),
+        (run_id, "Knee pain", &tags[..1], "2025-12-31T07:00:00Z"),
+        (swim_id, "Knee pain", &tags[..1], "2026-01-05T07:00:00Z"),
+    ] {
+        let entry =
+            new_entry(event_type_id, Some(description), tags, Some(occurred_at.parse().unwrap()));
+        journal_repo.insert(user_id, &entry).await.unwrap();
+    }
+    let filter = |query: &str| SearchFilter {
+        query: Some(query.to_string()),
+        sort: Some(SortOrder::Asc),
+        ..Default::default()
+    };
+
+    let query = r#"type:run tag:morning -tag:indoor after:2026-01-01 "knee pain""#;
+    let entries = journal_repo.find(user_id, &filter(query)).await.unwrap();
+    let descriptions: Vec<_> = entries.into_iter().filter_map(|e| e.description).collect();
+    assert_eq!(vec!["Knee pain after the run"], descriptions);
+
+    let query = "-type:run on:2026-01-05 knee";
+    assert_eq!(1, journal_repo.find(user_id, &filter(query)).await.unwrap().len());
+
+    let res_err = journal_repo.find(user_id, &filter("after:someday")).await;
+    let Err(AppError::Validation(fields)) = res_err else {
+        panic!("unexpected result: {res_err:?}");
+    };
+    assert_eq!("query: invalid date or time `someday` for `after` at column 7", fields[0].0);
+}
+
+#[tokio::test]
+async fn test_saved_search_crud() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let filter = SearchFilter {
+        query: Some("tag:morning knee".to_string()),
+        tags_none: vec!["indoor".to_string()],
+        sort: Some(SortOrder::Desc),
+        ..Default::default()
+    };
+    let data = SavedSearchData { name: "Knee".to_string(), filter: filter.clone() };
+
+    let id = journal_repo.insert_saved_search(user_id, &data).await.unwrap();
+    let expected = SavedSearch { id, user_id, name: "Knee".to_string(), filter: Json(filter) };
+    let found = journal_repo.find_saved_search(user_id, id).await.unwrap();
+    assert_eq!(Some(expected.clone()), found);
+    assert_eq!(vec![expected], journal_repo.find_saved_searches(user_id).await.unwrap());
+
+    let res_err = journal_repo.insert_saved_search(user_id, &data).await;
+    assert!(matches!(res_err, Err(AppError::DatabaseError(_))), "unexpected result: {res_err:?}");
+
+    let data = SavedSearchData { name: "All".to_string(), filter: SearchFilter::default() };
+    assert!(journal_repo.update_saved_search(user_id, id, &data).await.unwrap());
+    let found = journal_repo.find_saved_search(user_id, id).await.unwrap().expect("not found");
+    assert_eq!("All", found.name);
+    assert_eq!(SearchFilter::default(), found.filter.0);
+
+    assert!(journal_repo.delete_saved_search(user_id, id).await.unwrap());
+    assert!(!journal_repo.delete_saved_search(user_id, id).await.unwrap());
+    assert!(!journal_repo.update_saved_search(user_id, id, &data).await.unwrap());
+    assert_eq!(None, journal_repo.find_saved_search(user_id, id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_saved_search_feed_token() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let other_user = fixture.user_repo.insert("other", "other", "other").await.unwrap();
+    let data = SavedSearchData { name: "Runs".to_string(), filter: SearchFilter::default() };
+    let id = journal_repo.insert_saved_search(user_id, &data).await.unwrap();
+
+    assert!(!journal_repo.delete_saved_search_feed_token(user_id, id).await.unwrap());
+    assert!(!journal_repo.update_saved_search_feed_token(other_user, id, b"first").await.unwrap());
+    assert!(journal_repo.update_saved_search_feed_token(user_id, id, b"first").await.unwrap());
+    let found = journal_repo.find_saved_search_by_feed_token(b"first").await.unwrap();
+    assert_eq!(Some(id), found.map(|search| search.id));
+
+    // A new token replaces the previous one.
+    assert!(journal_repo.update_saved_search_feed_token(user_id, id, b"second").await.unwrap());
+    assert_eq!(None, journal_repo.find_saved_search_by_feed_token(b"first").await.unwrap());
+
+    assert!(journal_repo.delete_saved_search_feed_token(user_id, id).await.unwrap());
+    assert_eq!(None, journal_repo.find_saved_search_by_feed_token(b"second").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_find_page_by_cursor() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let mut ids = Vec::new();
+    for description in ["a", "b", "c"] {
+        let entry = new_entry(event_id, Some(description), &[], None);
+        ids.push(journal_repo.insert(user_id, &entry).await.unwrap());
+    }
+    let asc = SearchFilter::default();
+    let entry_ids =
+        |entries: Vec<JournalEntry>| -> Vec<_> { entries.into_iter().map(|e| e.id).collect() };
+
+    let first = journal_repo.find_page(user_id, &asc, Cursor::Start, 2).await.unwrap();
+    let cursor = Cursor::after(&first[1]);
+    assert_eq!(ids[..2].to_vec(), entry_ids(first));
+
+    // Entries recorded while paging do not shift the following pages.
+    let entry = new_entry(event_id, Some("d"), &[], None);
+    ids.push(journal_repo.insert(user_id, &entry).await.unwrap());
+    let second = journal_repo.find_page(user_id, &asc, cursor, 2).await.unwrap();
+    assert_eq!(ids[2..].to_vec(), entry_ids(second));
+
+    let limited = SearchFilter { offset: Some(1), limit: Some(1), ..Default::default() };
+    assert_eq!(4, journal_repo.count(user_id, &limited).await.unwrap());
+    let filter = SearchFilter { q: Some("d".to_string()), ..Default::default() };
+    assert_eq!(1, journal_repo.count(user_id, &filter).await.unwrap());
+    let (entries, total) = journal_repo.find_with_total(user_id, &limited).await.unwrap();
+    assert_eq!((vec![ids[1]], 4), (entry_ids(entries), total));
+
+    let desc = SearchFilter { sort: Some(SortOrder::Desc), ..Default::default() };
+    let first = journal_repo.find_page(user_id, &desc, Cursor::Start, 3).await.unwrap();
+    let cursor = Cursor::after(&first[2]);
+    let second = journal_repo.find_page(user_id, &desc, cursor, 3).await.unwrap();
+    assert_eq!(vec![ids[0]], entry_ids(second));
+
+    // Back-dated entries are paged by the time they occurred at, like the sorted listing.
+    let entry = new_entry(event_id, Some("e"), &[], Some(now().sub(TimeDelta::hours(1))));
+    let back_dated_id = journal_repo.insert(user_id, &entry).await.unwrap();
+    let first = journal_repo.find_page(user_id, &asc, Cursor::Start, 1).await.unwrap();
+    assert_eq!(vec![back_dated_id], entry_ids(first));
+    let listed = journal_repo.find(user_id, &asc).await.unwrap();
+    assert_eq!(back_dated_id, listed[0].id);
+}
+
+#[tokio::test]
+async fn test_batch_atomic_and_best_effort() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let tag = vec!["tag1".to_string()];
+    let unknown_tag = vec!["unknown".to_string()];
+    let existing_id =
+        journal_repo.insert(user_id, &new_entry(event_id, None, &[], None)).await.unwrap();
+    let missing_id = JournalEntryId::new(Uuid::new_v4());
+    let batch = |mode| EntryBatch {
+        mode,
+        create: vec![
+            new_entry(event_id, Some("created"), &tag, None),
+            new_entry(event_id, Some("invalid"), &unknown_tag, None),
+        ],
+        update: vec![BatchUpdate {
+            id: existing_id,
+            update: entry_update(None, Some("updated"), &tag, None),
+        }],
+        delete: vec![missing_id],
+    };
+
+    let result = journal_repo.batch(user_id, &batch(BatchMode::Atomic)).await.unwrap();
+    assert!(!result.committed);
+    assert_eq!(vec![200, 400], result.create.iter().map(|r| r.status).collect::<Vec<_>>());
+    assert_eq!(1, journal_repo.find(user_id, &SearchFilter::default()).await.unwrap().len());
+
+    let result = journal_repo.batch(user_id, &batch(BatchMode::BestEffort)).await.unwrap();
+    assert!(result.committed);
+    assert_eq!(None, result.create[1].id);
+    assert_eq!(vec![BatchItemResult::succeeded(existing_id)], result.update);
+    assert_eq!(vec![BatchItemResult::failed(Some(missing_id), &AppError::NotFound)], result.delete);
+    let created_id = result.create[0].id.expect("not created");
+    let created = journal_repo.find_by_id(user_id, created_id).await.unwrap().expect("not found");
+    assert_eq!(Some("created".to_string()), created.description);
+    let updated = journal_repo.find_by_id(user_id, existing_id).await.unwrap().expect("not found");
+    assert_eq!(Some("updated".to_string()), updated.description);
+}
+
+#[tokio::test]
+async fn test_export() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let settings =
+        UpdateSettingsRequest { timezone: "Europe/Prague".to_string(), search_language: None };
+    fixture.user_repo.upsert_settings(user_id, &settings).await.unwrap();
+    let run_id = fixture.event_repo.insert(user_id, "Run", &[], &[]).await.unwrap();
+    let start: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+    let tag = vec!["tag1".to_string()];
+    // More entries than fetched from the cursor at once.
+    let create = (0..501)
+        .map(|i| new_entry(event_id, None, &tag, Some(start + TimeDelta::minutes(i))))
+        .chain([new_entry(run_id, Some("run"), &[], Some(start))])
+        .collect();
+    let batch = EntryBatch { mode: BatchMode::Atomic, create, update: vec![], delete: vec![] };
+    assert!(journal_repo.batch(user_id, &batch).await.unwrap().committed);
+
+    let filter =
+        SearchFilter { tags_all: tag.clone(), sort: Some(SortOrder::Desc), ..Default::default() };
+    let entries: Vec<ExportEntry> =
+        journal_repo.export(user_id, &filter).await.unwrap().try_collect().await.unwrap();
+    assert_eq!(501, entries.len());
+    assert_eq!(start + TimeDelta::minutes(500), entries[0].entry.occurred_at);
+    assert!(entries.windows(2).all(|e| e[0].entry.occurred_at > e[1].entry.occurred_at));
+    assert!(entries.iter().all(|e| e.event_type == "default_event"));
+
+    let filter = SearchFilter { query: Some("type:run".to_string()), ..Default::default() };
+    let entries: Vec<ExportEntry> =
+        journal_repo.export(user_id, &filter).await.unwrap().try_collect().await.unwrap();
+    assert_eq!(1, entries.len());
+    assert_eq!("Run", entries[0].event_type);
+    let local: NaiveDateTime = "2026-01-01T01:00:00".parse().unwrap();
+    assert_eq!(local, entries[0].local_occurred_at);
+}
+
+#[tokio::test]
+async fn test_calendar_token() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let other_user = fixture.user_repo.insert("other", "other", "other").await.unwrap();
+
+    journal_repo.upsert_calendar_token(user_id, b"first").await.unwrap();
+    journal_repo.upsert_calendar_token(other_user, b"other").await.unwrap();
+    assert_eq!(Some(user_id), journal_repo.find_calendar_user(b"first").await.unwrap());
+
+    // A new token replaces the previous one.
+    journal_repo.upsert_calendar_token(user_id, b"second").await.unwrap();
+    assert_eq!(None, journal_repo.find_calendar_user(b"first").await.unwrap());
+    assert_eq!(Some(user_id), journal_repo.find_calendar_user(b"second").await.unwrap());
+
+    assert!(journal_repo.delete_calendar_token(user_id).await.unwrap());
+    assert!(!journal_repo.delete_calendar_token(user_id).await.unwrap());
+    assert_eq!(None, journal_repo.find_calendar_user(b"second").await.unwrap());
+    assert_eq!(Some(other_user), journal_repo.find_calendar_user(b"other").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_import() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let weight_id =
+        fixture.event_repo.insert(user_id, "Weight", &[], &weight_fields()).await.unwrap();
+    let import_entry = |line, event_type: &str, tags: &[&str]| ImportEntry {
+        line,
+        event_type: event_type.to_string(),
+        occurred_at: "2026-01-02T07:00:00Z".parse().unwrap(),
+        ended_at: None,
+        description: Some(format!("line {line}")),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+    };
+    let entries = vec![
+        import_entry(2, "default_event", &["tag1", "new"]),
+        import_entry(3, "Run", &["outdoor"]),
+        import_entry(4, "Run", &[]),
+    ];
+
+    let report = journal_repo.import(user_id, &entries, false).await.unwrap();
+    assert_eq!(
+        (false, 3, vec!["Run".to_string()]),
+        (report.committed, report.imported, report.created_event_types)
+    );
+    assert!(journal_repo.find(user_id, &SearchFilter::default()).await.unwrap().is_empty());
+
+    let report = journal_repo.import(user_id, &entries, true).await.unwrap();
+    assert!(report.committed);
+    assert_eq!(3, journal_repo.find(user_id, &SearchFilter::default()).await.unwrap().len());
+    let event_type = fixture.event_repo.find_by_id(user_id, fixture.default_event_type_id).await;
+    assert_eq!(vec!["tag1", "tag2", "new"], event_type.unwrap().expect("not found").tags);
+
+    // Re-importing leaves the event types which already have all the tags untouched.
+    let before = journal_repo.find_changes(user_id, ChangeCursor::default(), 100).await.unwrap();
+    journal_repo.import(user_id, &entries, true).await.unwrap();
+    let changes = journal_repo.find_changes(user_id, before.cursor, 100).await.unwrap();
+    assert!(changes.event_types.is_empty());
+    assert_eq!(3, changes.entries.len());
+
+    // Entries of the event type with a required custom field are not valid.
+    let report =
+        journal_repo.import(user_id, &[import_entry(2, "Weight", &[])], true).await.unwrap();
+    assert!(!report.committed);
+    assert_eq!(0, report.imported);
+    assert_eq!(2, report.errors[0].line);
+    assert!(report.errors[0].message.starts_with("validation error"));
+    let filter = SearchFilter { event_type_id: Some(weight_id), ..Default::default() };
+    assert!(journal_repo.find(user_id, &filter).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_find_changes() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let other_user = fixture.user_repo.insert("other", "other", "other").await.unwrap();
+    fixture.event_repo.insert(other_user, "Other", &[], &[]).await.unwrap();
+
+    let changes = journal_repo.find_changes(user_id, ChangeCursor::default(), 100).await.unwrap();
+    assert_eq!(vec![event_id], changes.event_types.iter().map(|e| e.item.id).collect::<Vec<_>>());
+    assert!(changes.entries.is_empty() && !changes.has_more);
+    let cursor = changes.cursor;
+
+    let kept_id =
+        journal_repo.insert(user_id, &new_entry(event_id, None, &[], None)).await.unwrap();
+    let deleted_id =
+        journal_repo.insert(user_id, &new_entry(event_id, None, &[], None)).await.unwrap();
+    let update = entry_update(None, Some("updated"), &[], None);
+    journal_repo.update(user_id, kept_id, &update).await.unwrap();
+    journal_repo.delete(user_id, deleted_id).await.unwrap();
+
+    // The deleted entry is only reported by its tombstone, after the update of the kept one.
+    let first = journal_repo.find_changes(user_id, cursor, 1).await.unwrap();
+    assert!(first.event_types.is_empty() && first.deleted.is_empty() && first.has_more);
+    assert_eq!(Some("updated"), first.entries[0].item.description.as_deref());
+    assert_eq!(first.cursor, ChangeCursor(first.entries[0].version));
+    let second = journal_repo.find_changes(user_id, first.cursor, 1).await.unwrap();
+    assert!(second.entries.is_empty() && !second.has_more);
+    assert_eq!(
+        vec![(SyncKind::Entry, deleted_id.to_string())],
+        second.deleted.iter().map(|t| (t.kind, t.id.to_string())).collect::<Vec<_>>()
+    );
+    let empty = journal_repo.find_changes(user_id, second.cursor, 1).await.unwrap();
+    assert_eq!(SyncChanges { cursor: second.cursor, ..Default::default() }, empty);
+
+    // Entries deleted along with their event type get tombstones as well.
+    fixture.event_repo.delete(user_id, event_id).await.unwrap();
+    let changes = journal_repo.find_changes(user_id, second.cursor, 100).await.unwrap();
+    let deleted: Vec<_> = changes.deleted.iter().map(|t| (t.kind, t.id.to_string())).collect();
+    assert_eq!(2, deleted.len());
+    assert!(deleted.contains(&(SyncKind::EventType, event_id.to_string())));
+    assert!(deleted.contains(&(SyncKind::Entry, kept_id.to_string())));
+}
+
+#[tokio::test]
+async fn test_push_changes() {
+    let fixture = setup_test().await;
+    let journal_repo = &fixture.journal_repo;
+    let user_id = fixture.default_user_id;
+    let event_id = fixture.default_event_type_id;
+    let changes = journal_repo.find_changes(user_id, ChangeCursor::default(), 100).await.unwrap();
+    let event_version = changes.event_types[0].version;
+    let run_id = EventTypeId::new(Uuid::new_v4());
+    let entry_id = JournalEntryId::new(Uuid::new_v4());
+    let run = || EventTypeData { name: "Run".to_string(), tags: vec![], fields: vec![] };
+
+    let changes = vec![
+        SyncChange::CreateEventType { id: run_id, event_type: run() },
+        SyncChange::CreateEntry { id: entry_id, entry: new_entry(run_id, Some("run"), &[], None) },
+        SyncChange::UpdateEventType {
+            id: event_id,
+            base_version: event_version,
+            event_type: EventTypeData { name: "renamed".to_string(), tags: vec![], fields: vec![] },
+        },
+        SyncChange::UpdateEntry {
+            id: entry_id,
+            base_version: 0,
+            update: entry_update(None, Some("stale"), &[], None),
+        },
+        SyncChange::CreateEntry { id: entry_id, entry: new_entry(run_id, None, &[], None) },
+        SyncChange::CreateEntry {
+            id: JournalEntryId::new(Uuid::new_v4()),
+            entry: new_entry(EventTypeId::new(Uuid::new_v4()), None, &[], None),
+        },
+        SyncChange::DeleteEntry { id: JournalEntryId::new(Uuid::new_v4()), base_version: 1 },
+    ];
+    let results = journal_repo.push_changes(user_id, &changes).await.unwrap();
+
+    let statuses: Vec<_> = results.iter().map(|r| r.status).collect();
+    use SyncStatus::{Applied, Conflict, Rejected};
+    assert_eq!(vec![Applied, Applied, Applied, Conflict, Conflict, Rejected, Rejected], statuses);
+    let entry_version = results[1].version.expect("no version");
+    assert_eq!(Some(entry_version), results[3].version);
+    assert_eq!(Some(entry_version), results[4].version);
+    assert!(results[2].version > Some(event_version));
+    let entry = journal_repo.find_by_id(user_id, entry_id).await.unwrap().expect("not found");
+    assert_eq!((run_id, Some("run")), (entry.event_type_id, entry.description.as_deref()));
+    let event_type = fixture.event_repo.find_by_id(user_id, event_id).await.unwrap();
+    assert_eq!("renamed", event_type.expect("not found").name);
+
+    // Changes based on a deleted row conflict without a version.
+    let changes = vec![
+        SyncChange::DeleteEntry { id: entry_id, base_version: entry_version },
+        SyncChange::UpdateEntry {
+            id: entry_id,
+            base_version: entry_version,
+            update: entry_update(None, None, &[], None),
+        },
+    ];
+    let results = journal_repo.push_changes(user_id, &changes).await.unwrap();
+    assert_eq!(vec![SyncChangeResult::applied(None), SyncChangeResult::conflict(None)], results);
+    assert_eq!(None, journal_repo.find_by_id(user_id, entry_id).await.unwrap());
+}
+
+fn weight_fields() -> Vec<FieldDefinition> {
+    vec![FieldDefinition {
+        name: "weight".to_string(),
+        kind: FieldKind::Number { unit: Some("kg".to_string()) },
+        required: true,
+    }]
+}
+
+fn new_entry(
+    event_type_id: EventTypeId,
+    description: Option<&str>,
+    tags: &[String],
+    occurred_at: Option<DateTime<Utc>>,
+) -> NewJournalEntry {
+    NewJournalEntry {
+        event_type_id,
+        description: description.map(str::to_string),
+        tags: tags.to_vec(),
+        occurred_at,
+        ended_at: None,
+        values: FieldValues::new(),
+    }
+}
+
+fn entry_update(
+    event_type_id: Option<EventTypeId>,
+    description: Option<&str>,
+    tags: &[String],
+    occurred_at: Option<DateTime<Utc>>,
+) -> JournalEntryUpdate {
+    JournalEntryUpdate {
+        event_type_id,
+        description: description.map(str::to_string),
+        tags: tags.to_vec(),
+        occurred_at,
+        ended_at: None,
+        values: FieldValues::new(),
+    }
+}
+
+/// Returns current time truncated to microseconds, which is the precision of Postgres timestamps.
+fn now() -> DateTime<Utc> {
+    Utc::now().duration_trunc(TimeDelta::microseconds(1)).unwrap()
+}
+
 struct TestFixture<U: UserRepository, E: EventTypeRepository, J: JournalEntryRepository> {
     user_repo: U,
     event_repo: E,
@@ -206,7 +1268,7 @@ async fn setup_test()
     let default_user_id = user_repo.insert("default", "default", "default").await.unwrap();
     let tags = vec!["tag1".to_string(), "tag2".to_string()];
     let default_event_type_id =
-        event_repo.insert(default_user_id, "default_event", &tags).await.unwrap();
+        event_repo.insert(default_user_id, "default_event", &tags, &[]).await.unwrap();
 
     TestFixture { user_repo, event_repo, journal_repo, default_user_id, default_event_type_id }
 }
@@ -5,6 +5,7 @@ use common::{
     start_pg_container,
 };
 use ctor::{ctor, dtor};
+use journal_backend::user::model::{UpdateSettingsRequest, UserSettings};
 use journal_backend::user::repository::{PgUserRepository, UserRepository};
 use lazy_static::lazy_static;
 use std::thread;
@@ -63,6 +64,37 @@ async fn test_update_password() {
     assert_eq!("new", user_from_db.password);
 }
 
+#[tokio::test]
+async fn test_upsert_settings() {
+    let repo = setup_user_repository().await;
+    let id = repo.insert("user", "password", "email").await.unwrap();
+    assert_eq!(None, repo.find_settings(id).await.unwrap());
+
+    let settings =
+        UpdateSettingsRequest { timezone: "Europe/Prague".to_string(), search_language: None };
+    repo.upsert_settings(id, &settings).await.unwrap();
+    let expected = UserSettings { timezone: "Europe/Prague".to_string(), ..Default::default() };
+    assert_eq!(Some(expected), repo.find_settings(id).await.unwrap());
+
+    let settings = UpdateSettingsRequest {
+        timezone: "America/New_York".to_string(),
+        search_language: Some("english".to_string()),
+    };
+    repo.upsert_settings(id, &settings).await.unwrap();
+    let expected = UserSettings {
+        timezone: "America/New_York".to_string(),
+        search_language: "english".to_string(),
+    };
+    assert_eq!(Some(expected), repo.find_settings(id).await.unwrap());
+
+    // The stored language is kept when only the time zone is sent.
+    let settings = UpdateSettingsRequest { timezone: "UTC".to_string(), search_language: None };
+    repo.upsert_settings(id, &settings).await.unwrap();
+    let expected =
+        UserSettings { timezone: "UTC".to_string(), search_language: "english".to_string() };
+    assert_eq!(Some(expected), repo.find_settings(id).await.unwrap());
+}
+
 async fn setup_user_repository() -> impl UserRepository {
     let port = get_pg_port(&CMD_IN, &PG_PORT).await;
     let pool = create_pg_pool(port).await;